@@ -1,5 +1,30 @@
 //! This module has is used for the ratatui App
 
+use crate::bulk::BulkAction;
+use crate::entry::SshConfigEntry;
+use crate::error::Error;
+use crate::history::{History, PendingWrite};
+use ratatui::widgets::ListItem;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::keys::KeyReport;
+use crate::known_hosts::{KnownHost, Link};
+use crate::lint::Diagnostic;
+use crate::liststate_utils::ListStateManager;
+use crate::profile;
+use crate::reachability::Status;
+use crate::settings::{Profile, Settings};
+use crate::tag_tree::TagTree;
+
+/// Field of the entry that is being typed into while in edit mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditField {
+    Host,              // Renaming the host
+    Value(usize),      // Value of the option at the given index
+    NewKey,            // Key of an option that is being added
+    NewValue(String),  // Value of an option that is being added, with its key
+}
+
 // Define the AppMode enum here
 #[derive(Debug)]
 pub enum AppMode {
@@ -9,7 +34,73 @@ pub enum AppMode {
         query: String,
         cursor_position: usize,
         matches: Vec<usize>,
-        current_match: Option<usize>,
+    },
+    Edit {
+        index: usize,                // Index of the entry being edited
+        entry: SshConfigEntry,       // Working copy, only written back on save
+        selected: usize,             // Selected row: 0 is the Host, the options follow
+        field: Option<EditField>,    // Field being typed into, if any
+        input: String,
+        message: Option<String>,     // Validation errors and save status
+    },
+    Lint {
+        diagnostics: Vec<Diagnostic>,
+        selected: usize,
+    },
+    Tags {
+        selected: usize,             // Selected group in the tag panel
+    },
+    Keys {
+        report: KeyReport,
+        selected: usize,             // Selected row: the keys, then the missing key files
+    },
+    KnownHosts {
+        path: PathBuf,
+        hosts: Vec<KnownHost>,
+        links: Vec<Link>,            // Entries of each known_hosts line and whether it is stale
+        selected: usize,
+        deleting: Option<Vec<usize>>,  // Lines to delete, shown to confirm it
+        message: Option<String>,     // Result of the last deletion
+    },
+    MoveTag {
+        index: usize,                // Index of the entry being moved
+        tags: Vec<String>,           // Existing tags to pick from
+        selected: Option<usize>,     // Picked tag, if any
+        input: String,               // Tag to move to, empty to remove the tag
+        message: Option<String>,
+    },
+    Review {
+        writes: Vec<PendingWrite>,   // Files that would change, with their diff
+        scroll: usize,               // First line of the diff shown
+        quitting: bool,              // Opened by quitting with unsaved changes
+        message: Option<String>,     // Write errors
+    },
+    Bulk {
+        marked: Vec<usize>,          // Entries the action applies to
+        selected: usize,             // Selected action of the menu
+        action: Option<BulkAction>,  // Picked action, the menu is shown until then
+        input: String,               // Option, tag or file typed for the action
+        summary: Option<Vec<String>>,  // What the action will do, shown to confirm it
+        message: Option<String>,     // Invalid input and export errors
+    },
+    NewHost {
+        template: usize,             // Template the form was filled from, 0 for none
+        selected: usize,             // Selected row: 0 is the template, the fields follow
+        values: Vec<String>,         // Value of each field, see `host_form::FormField::ALL`
+        message: Option<String>,     // Why the host cannot be added
+    },
+    Profiles {
+        selected: usize,             // Selected profile, see `App::profiles`
+        message: Option<String>,     // Why the profile cannot be opened
+    },
+    ProfileDiff {
+        profile: Profile,            // Profile compared with the open config
+        diffs: Vec<profile::EntryDiff>,
+        scroll: usize,               // First line shown
+    },
+    Conflict {
+        entries: Vec<SshConfigEntry>,  // The entries as they are on disk now
+        changed: Vec<PathBuf>,       // Files that changed on disk
     },
 }
 
@@ -18,13 +109,6 @@ impl AppMode {
         matches!(self, AppMode::Search { .. })
     }
 
-    pub fn get_search_query(&self) -> Option<&str> {
-        match self {
-            AppMode::Search { query, .. } => Some(query),
-            _ => None,
-        }
-    }
-
     pub fn update_search(&mut self, new_query: String, matches: Vec<usize>) {
         if let AppMode::Search { query, cursor_position, .. } = self {
             *query = new_query;
@@ -33,9 +117,106 @@ impl AppMode {
                 query: query.clone(),
                 cursor_position: *cursor_position,
                 matches,
-                current_match: None,
             };
         }
     }
 }
 
+
+/// The whole state of the TUI. It is only changed by `update::update`, and drawn by `ui::draw`.
+pub struct App {
+    pub mode: AppMode,
+    pub popup_open: bool,
+    pub entries: Vec<SshConfigEntry>,
+    pub tree: TagTree,                      // Entries grouped by tag, the rows of the host list
+    pub hosts: Vec<ListItem<'static>>,      // Items of the host list, see `refresh_hosts`
+    pub list_state: ListStateManager,
+    pub statuses: HashMap<String, Status>,  // Reachability of the hosts, keyed by the Host line
+    pub history: History,                   // Changes that were not written yet
+    pub status: Option<String>,             // Result of the last action, for the status bar
+    pub error: Option<Error>,               // Shown in a banner in place of the status bar
+    pub changed_on_disk: Vec<PathBuf>,      // Files that changed while a popup was open, read once it closes
+    pub ssh_dir: PathBuf,                   // Where the keys and known_hosts are looked for
+    pub config_path: PathBuf,               // Config file that is open, new hosts without a tag go there
+    pub profiles: Vec<Profile>,             // Config files that can be opened instead
+    pub connect_program: String,            // Program run to connect, shown in the help
+    pub settings: Settings,                 // Keys of the host list and colors
+}
+
+impl App {
+    pub fn new(entries: Vec<SshConfigEntry>, ssh_dir: PathBuf, connect_program: &str, settings: Settings) -> Self {
+        let mut app = Self {
+            mode: AppMode::Normal,
+            popup_open: false,
+            tree: TagTree::new(&entries),
+            entries,
+            hosts: Vec::new(),
+            list_state: ListStateManager::new(),
+            statuses: HashMap::new(),
+            history: History::default(),
+            status: None,
+            error: None,
+            changed_on_disk: Vec::new(),
+            profiles: profile::profiles(&settings.profiles, &ssh_dir.join("config")),
+            config_path: ssh_dir.join("config"),
+            ssh_dir,
+            connect_program: connect_program.to_string(),
+            settings,
+        };
+        app.refresh_hosts();
+        if !app.hosts.is_empty() {
+            app.list_state.select(0);
+        }
+        app
+    }
+
+    /// Sets the config file the entries were read from, listing it with the profiles when it is
+    /// none of them.
+    pub fn set_config_path(&mut self, path: PathBuf) {
+        self.profiles = profile::profiles(&self.settings.profiles, &path);
+        self.config_path = path;
+    }
+
+    /// Shows the entries of another config file, the state that belongs to the current entries
+    /// (changes, marks, reachability and collapsed tags) is dropped.
+    pub fn open(&mut self, path: PathBuf, entries: Vec<SshConfigEntry>) {
+        self.config_path = path;
+        self.tree = TagTree::new(&entries);
+        self.entries = entries;
+        self.history = History::default();
+        self.error = None;
+        self.statuses.clear();
+        self.list_state.clear_marks();
+        self.mode = AppMode::Normal;
+        self.popup_open = false;
+        self.refresh_hosts();
+        self.list_state.select(0);
+    }
+
+    /// Regroups the entries by tag and rebuilds the items of the hosts list, after the entries
+    /// changed or a group was collapsed.
+    pub fn refresh_hosts(&mut self) {
+        self.tree.rebuild(&self.entries);
+        self.hosts = self.tree.items(&self.entries, &self.statuses, self.list_state.marked(), &self.settings.theme);
+    }
+
+    /// Index of the entry at the selected row of the hosts list, `None` when a tag header is selected.
+    pub fn selected_entry(&self) -> Option<usize> {
+        self.tree.entry_at(self.list_state.get_index())
+    }
+
+    /// Number of rows in the list: the search results while searching, the hosts otherwise.
+    pub fn visible_rows(&self) -> usize {
+        match &self.mode {
+            AppMode::Search { query, matches, .. } if !query.is_empty() => matches.len(),
+            _ => self.hosts.len(),
+        }
+    }
+
+    /// Keeps the selection inside the hosts list, after entries were removed.
+    pub fn clamp_selection(&mut self) {
+        if self.list_state.get_index() >= self.hosts.len() {
+            self.list_state.select(self.hosts.len().saturating_sub(1));
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! This module applies one action to every marked entry at once: setting or removing an option,
+//! moving to another tag, deleting and exporting
+//!
+//! Each action is described by a summary before it is applied, and the config changes are a single
+//! `Change` so that one undo reverts all of them.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::config;
+use crate::entry::{self, EntryKind, SshConfigEntry};
+use crate::history::Change;
+
+/// An action of the bulk menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkAction {
+    SetOption,
+    RemoveOption,
+    ChangeTag,
+    Delete,
+    Export,
+}
+
+impl BulkAction {
+    /// Every action, in the order of the menu
+    pub const ALL: [BulkAction; 5] = [
+        BulkAction::SetOption,
+        BulkAction::RemoveOption,
+        BulkAction::ChangeTag,
+        BulkAction::Delete,
+        BulkAction::Export,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BulkAction::SetOption => "Set an option",
+            BulkAction::RemoveOption => "Remove an option",
+            BulkAction::ChangeTag => "Change the tag",
+            BulkAction::Delete => "Delete",
+            BulkAction::Export => "Export to a file",
+        }
+    }
+
+    /// What has to be typed for the action, `None` when it needs nothing.
+    pub fn prompt(self) -> Option<&'static str> {
+        match self {
+            BulkAction::SetOption => Some("Option and value, e.g. User deploy: "),
+            BulkAction::RemoveOption => Some("Option: "),
+            BulkAction::ChangeTag => Some("Tag, empty removes it: "),
+            BulkAction::Delete => None,
+            BulkAction::Export => Some("File: "),
+        }
+    }
+
+    /// Text the input starts with.
+    pub fn default_input(self) -> String {
+        match self {
+            BulkAction::Export => "~/ssh-config-export.conf".to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Splits `User deploy` into the option and its value, checking that the option can be written.
+fn parse_option(input: &str) -> Result<(String, String), String> {
+    let (key, value) = input.trim().split_once(char::is_whitespace).unwrap_or((input.trim(), ""));
+    let value = value.trim();
+    entry::validate_option(key, value)?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Checks the option typed for `RemoveOption`.
+fn parse_option_name(input: &str) -> Result<String, String> {
+    let key = input.trim();
+    entry::validate_option_name(key)?;
+    Ok(key.to_string())
+}
+
+/// The path typed for `Export`, with `~` expanded.
+fn export_path(input: &str) -> Result<PathBuf, String> {
+    match input.trim() {
+        "" => Err("The file cannot be empty".to_string()),
+        path => Ok(PathBuf::from(shellexpand::tilde(path).into_owned())),
+    }
+}
+
+/// Gives `key` the single value `value`: the first line with the option is replaced and the others
+/// are removed, or the option is added at the end.
+fn set_option(entry: &mut SshConfigEntry, key: &str, value: &str) {
+    let mut found = false;
+    entry.options.retain_mut(|(k, v)| {
+        if !k.eq_ignore_ascii_case(key) {
+            return true;
+        }
+        if found {
+            return false;
+        }
+        found = true;
+        *v = value.to_string();
+        true
+    });
+    if !found {
+        entry.add_option(key.to_string(), value.to_string());
+    }
+}
+
+/// Values of `key` in the entry, joined for the summary.
+fn option_values(entry: &SshConfigEntry, key: &str) -> Option<String> {
+    let values = entry.options.iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.as_str())
+        .collect::<Vec<_>>();
+    if values.is_empty() { None } else { Some(values.join(", ")) }
+}
+
+/// Describes what the action does to each marked entry, before it is applied.
+///
+/// # Returns
+/// The lines of the summary, or why the input cannot be used.
+pub fn summary(entries: &[SshConfigEntry], marked: &[usize], action: BulkAction, input: &str) -> Result<Vec<String>, String> {
+    let marked = marked.iter().filter_map(|&index| entries.get(index)).collect::<Vec<_>>();
+    let hosts = match marked.len() {
+        1 => "1 entry".to_string(),
+        n => format!("{} entries", n),
+    };
+
+    let mut lines = Vec::new();
+    match action {
+        BulkAction::SetOption => {
+            let (key, value) = parse_option(input)?;
+            lines.push(format!("Set {} {} on {}:", key, value, hosts));
+            for entry in marked {
+                let current = option_values(entry, &key).unwrap_or_else(|| "not set".to_string());
+                lines.push(format!("  {}: {} → {}", entry.label(), current, value));
+            }
+        }
+        BulkAction::RemoveOption => {
+            let key = parse_option_name(input)?;
+            lines.push(format!("Remove {} from {}:", key, hosts));
+            for entry in marked {
+                lines.push(match option_values(entry, &key) {
+                    Some(values) => format!("  {}: {}", entry.label(), values),
+                    None => format!("  {}: not set, unchanged", entry.label()),
+                });
+            }
+        }
+        BulkAction::ChangeTag => {
+            let tag = input.trim();
+            lines.push(match tag {
+                "" => format!("Remove the tag of {}:", hosts),
+                tag => format!("Move {} to {}:", hosts, tag),
+            });
+            for entry in marked {
+                let current = entry.tag.as_deref().unwrap_or("(untagged)");
+                lines.push(match entry.kind {
+                    EntryKind::Global => format!("  {}: skipped, the global options stay at the top", entry.label()),
+                    _ => format!("  {}: {} → {}", entry.label(), current, if tag.is_empty() { "(untagged)" } else { tag }),
+                });
+            }
+        }
+        BulkAction::Delete => {
+            lines.push(format!("Delete {}:", hosts));
+            lines.extend(marked.iter().map(|entry| format!("  {} ({}:{})", entry.label(), entry.source.display(), entry.line)));
+        }
+        BulkAction::Export => {
+            let path = export_path(input)?;
+            let replaced = if path.exists() { ", replacing it" } else { "" };
+            lines.push(format!("Write {} to {}{}:", hosts, path.display(), replaced));
+            lines.extend(marked.iter().map(|entry| format!("  {}", entry.label())));
+        }
+    }
+    Ok(lines)
+}
+
+/// Builds the change that applies the action to the marked entries, all the other actions than
+/// `Export` change the config.
+pub fn change(entries: &[SshConfigEntry], marked: &[usize], action: BulkAction, input: &str) -> Result<Change, String> {
+    let description = |what: String| match marked.len() {
+        1 => format!("{} on 1 entry", what),
+        n => format!("{} on {} entries", what, n),
+    };
+    // Replaces each marked entry by the entry the closure gives, when it changed
+    let replace_each = |what: String, update: &dyn Fn(&mut SshConfigEntry)| {
+        marked.iter().fold(Change::new(description(what)), |change, &index| {
+            let mut updated = entries[index].clone();
+            update(&mut updated);
+            if updated == entries[index] {
+                change
+            } else {
+                change.then(Change::replace(String::new(), entries, index, updated))
+            }
+        })
+    };
+
+    match action {
+        BulkAction::SetOption => {
+            let (key, value) = parse_option(input)?;
+            Ok(replace_each(format!("set {} {}", key, value), &|entry| set_option(entry, &key, &value)))
+        }
+        BulkAction::RemoveOption => {
+            let key = parse_option_name(input)?;
+            Ok(replace_each(format!("remove {}", key), &|entry| entry.options.retain(|(k, _)| !k.eq_ignore_ascii_case(&key))))
+        }
+        BulkAction::Delete => {
+            // From the last one, so that the indices of the others do not move
+            Ok(marked.iter().rev().fold(Change::new(description("delete".to_string())), |change, &index| {
+                change.then(Change::remove(String::new(), entries, index))
+            }))
+        }
+        BulkAction::ChangeTag => {
+            let tag = input.trim();
+            let mut change = Change::new(description(match tag {
+                "" => "remove the tag".to_string(),
+                tag => format!("move to {}", tag),
+            }));
+
+            // Each move shifts the entries after it, the moves are chained on a copy of the entries
+            let mut current = entries.to_vec();
+            let mut positions = marked.iter()
+                .copied()
+                .filter(|&index| entries[index].kind != EntryKind::Global)
+                .collect::<Vec<_>>();
+            for i in 0..positions.len() {
+                let from = positions[i];
+                let (moved, to) = config::move_change(&current, from, tag)?;
+                moved.apply(&mut current);
+                for position in positions.iter_mut().skip(i + 1) {
+                    if *position > from {
+                        *position -= 1;
+                    }
+                    if *position >= to {
+                        *position += 1;
+                    }
+                }
+                change = change.then(moved);
+            }
+            Ok(change)
+        }
+        BulkAction::Export => Err("Exporting does not change the config".to_string()),
+    }
+}
+
+/// Writes the marked entries to the file typed for `Export`, as a config that can be included.
+///
+/// # Returns
+/// The path of the file.
+pub fn export(entries: &[SshConfigEntry], marked: &[usize], input: &str) -> io::Result<PathBuf> {
+    let path = export_path(input).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let exported = marked.iter().filter_map(|&index| entries.get(index)).cloned().collect::<Vec<_>>();
+    fs::write(&path, config::serialize_ssh_config(&exported))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::History;
+
+    fn labels(entries: &[SshConfigEntry]) -> Vec<String> {
+        entries.iter().map(SshConfigEntry::label).collect()
+    }
+
+    #[test]
+    fn set_option_replaces_every_value_and_is_undone_at_once() {
+        let mut entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("User", "root")]),
+            SshConfigEntry::with_options(EntryKind::Host, "db", None, &[]),
+            SshConfigEntry::with_options(EntryKind::Host, "git", None, &[("IdentityFile", "a"), ("IdentityFile", "b")]),
+        ];
+        let original = entries.clone();
+
+        assert_eq!(summary(&entries, &[0, 1], BulkAction::SetOption, "User deploy").unwrap(), vec![
+            "Set User deploy on 2 entries:",
+            "  web: root → deploy",
+            "  db: not set → deploy",
+        ]);
+
+        let mut history = History::default();
+        let bulk = change(&entries, &[0, 1, 2], BulkAction::SetOption, "IdentityFile c").unwrap();
+        history.apply(&mut entries, bulk);
+        assert_eq!(entries[0].options, vec![
+            ("User".to_string(), "root".to_string()),
+            ("IdentityFile".to_string(), "c".to_string()),
+        ]);
+        assert_eq!(entries[2].options, vec![("IdentityFile".to_string(), "c".to_string())]);
+
+        assert_eq!(history.undo(&mut entries).as_deref(), Some("set IdentityFile c on 3 entries"));
+        assert_eq!(entries, original);
+    }
+
+    #[test]
+    fn invalid_input_is_refused() {
+        let entries = vec![SshConfigEntry::with_options(EntryKind::Host, "web", None, &[])];
+        assert!(summary(&entries, &[0], BulkAction::SetOption, "Port abc").is_err());
+        assert!(summary(&entries, &[0], BulkAction::SetOption, "User").is_err());
+        assert!(summary(&entries, &[0], BulkAction::RemoveOption, "Host").is_err());
+        assert!(change(&entries, &[0], BulkAction::ChangeTag, "a\nb").is_err());
+        assert!(change(&entries, &[0], BulkAction::ChangeTag, "web-servers").is_ok());
+    }
+
+    #[test]
+    fn remove_option_and_delete() {
+        let mut entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("User", "root"), ("Port", "22")]),
+            SshConfigEntry::with_options(EntryKind::Host, "db", None, &[("User", "admin")]),
+            SshConfigEntry::with_options(EntryKind::Host, "git", None, &[]),
+        ];
+        let mut history = History::default();
+
+        let bulk = change(&entries, &[0, 1, 2], BulkAction::RemoveOption, "user").unwrap();
+        history.apply(&mut entries, bulk);
+        assert_eq!(entries[0].options, vec![("Port".to_string(), "22".to_string())]);
+        assert!(entries[1].options.is_empty());
+
+        let bulk = change(&entries, &[0, 2], BulkAction::Delete, "").unwrap();
+        history.apply(&mut entries, bulk);
+        assert_eq!(labels(&entries), vec!["db"]);
+    }
+
+    #[test]
+    fn change_tag_moves_every_entry_to_the_section_of_the_tag() {
+        let mut entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "a", Some("dev"), &[]),
+            SshConfigEntry::with_options(EntryKind::Host, "b", Some("dev"), &[]),
+            SshConfigEntry::with_options(EntryKind::Host, "c", Some("dev"), &[]),
+            SshConfigEntry::with_options(EntryKind::Host, "d", Some("prod"), &[]),
+        ];
+        let original = entries.clone();
+        let mut history = History::default();
+
+        let bulk = change(&entries, &[0, 2], BulkAction::ChangeTag, "prod").unwrap();
+        history.apply(&mut entries, bulk);
+        assert_eq!(labels(&entries), vec!["b", "d", "a", "c"]);
+        assert!(entries[1..].iter().all(|entry| entry.tag.as_deref() == Some("prod")));
+
+        history.undo(&mut entries);
+        assert_eq!(entries, original);
+    }
+
+    #[test]
+    fn export_writes_the_marked_entries() {
+        let entries = vec![SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("User", "root")]), SshConfigEntry::with_options(EntryKind::Host, "db", None, &[])];
+        let input = std::env::temp_dir().join(format!("ssh-config-export-{}.conf", std::process::id()));
+
+        let path = export(&entries, &[0], &input.display().to_string()).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert!(contents.contains("Host web"));
+        assert!(contents.contains("User root"));
+        assert!(!contents.contains("Host db"));
+    }
+}
@@ -0,0 +1,516 @@
+//! This module has the command line interface. Without a subcommand the TUI is started.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use crate::config;
+use crate::connect;
+use crate::entry::{self, EntryKind, SshConfigEntry};
+use crate::exchange::{self, ExchangeFormat};
+use crate::lint;
+use crate::profile::{self, EntryDiff};
+use crate::resolve::{self, OptionSource};
+use crate::settings::{self, Profile};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "TUI to manage the ~/.ssh/config")]
+pub struct Cli {
+    /// Command run by the connect action, `{host}` is replaced by the host alias
+    #[arg(long, env = "SSH_CONFIG_CONNECT", default_value = connect::DEFAULT_TEMPLATE)]
+    pub connect: String,
+
+    /// Config file to open instead of ~/.ssh/config, like `ssh -F`
+    #[arg(short = 'F', long = "config", value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Profile to open, from the `[profiles]` of the settings
+    #[arg(long, global = true, conflicts_with = "config")]
+    pub profile: Option<String>,
+
+    /// Lowest level logged to $XDG_STATE_HOME/ssh-config/ssh-config.log: off, error, warn, info,
+    /// debug or trace
+    #[arg(long, env = "SSH_CONFIG_LOG_LEVEL", default_value = "info", value_name = "LEVEL", global = true)]
+    pub log_level: log::LevelFilter,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// The config file to open: the one given, the file of the profile, or ~/.ssh/config.
+    pub fn config_path(&self, profiles: &[Profile]) -> Result<PathBuf, String> {
+        match (&self.config, &self.profile) {
+            (Some(path), _) => Ok(path.clone()),
+            (None, Some(name)) => profiles.iter()
+                .find(|profile| &profile.name == name)
+                .map(|profile| profile.path.clone())
+                .ok_or_else(|| format!("Unknown profile '{}', see [profiles] in {}", name, settings::path().display())),
+            (None, None) => Ok(config::default_path()),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check the config for problems, exits with 1 when errors are found
+    Lint {
+        /// Config file to check instead of ~/.ssh/config
+        file: Option<PathBuf>,
+    },
+    /// Compare the hosts with another config file, exits with 1 when they differ
+    Diff {
+        /// Config file, or name of a profile
+        other: String,
+    },
+    /// List the entries
+    List {
+        /// Only list the entries with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        #[arg(long, value_enum, default_value_t = Format::Plain)]
+        format: Format,
+    },
+    /// Print the entry of a host
+    Show {
+        host: String,
+        /// Print the effective options, like `ssh -G`, with the entry each one comes from
+        #[arg(long)]
+        effective: bool,
+    },
+    /// Add a new host
+    Add {
+        host: String,
+        /// Tag section to add the host to
+        #[arg(long)]
+        tag: Option<String>,
+        /// Option of the host as KEY=VALUE, can be given several times
+        #[arg(short, long = "option", value_name = "KEY=VALUE")]
+        options: Vec<String>,
+    },
+    /// Remove a host
+    Remove {
+        host: String,
+    },
+    /// Set an option of a host, replacing its current value
+    Set {
+        host: String,
+        key: String,
+        value: String,
+    },
+    /// List the tags with the number of entries in each
+    Tags,
+    /// Write the entries as JSON, YAML, an Ansible inventory or CSV
+    Export {
+        #[arg(long, value_enum)]
+        format: ExchangeFormat,
+        /// Only export the entries with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// File to write instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Add or update entries from a JSON, YAML, Ansible inventory or CSV file, `-` reads the
+    /// standard input
+    Import {
+        file: PathBuf,
+        /// Format of the file, taken from its extension when not given
+        #[arg(long, value_enum)]
+        format: Option<ExchangeFormat>,
+        /// Replace the entries of the config file instead of merging the imported ones into them
+        #[arg(long)]
+        replace: bool,
+        /// Print the files that would be written instead of writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Output formats of `ssh-config list`
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Plain,
+    Tsv,
+    Json,
+}
+
+/// Runs a subcommand against the config at `config_path`, `profiles` are those of the settings.
+///
+/// # Returns
+/// The exit code of the process.
+pub fn run(command: Command, config_path: &str, profiles: &[Profile]) -> i32 {
+    if let Command::Lint { file } = &command {
+        let path = file.as_ref().map_or(config_path.to_string(), |file| file.display().to_string());
+        return run_lint(&path);
+    }
+
+    // A config that does not exist yet is empty, `add` and `import` create it
+    let mut entries = match config::read_or_empty(config_path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read {}", e);
+            return 2;
+        }
+    };
+
+    if let Command::Diff { other } = &command {
+        return run_diff(&entries, config_path, &profile::find(profiles, other));
+    }
+
+    let result = match command {
+        Command::Lint { .. } | Command::Diff { .. } => unreachable!("handled above"),
+        Command::List { tag, format } => {
+            list(&entries, tag.as_deref(), format);
+            Ok(())
+        }
+        Command::Show { host, effective } => show(&entries, &host, effective),
+        Command::Add { host, tag, options } => add(&mut entries, config_path, &host, tag, &options),
+        Command::Remove { host } => remove(&mut entries, &host),
+        Command::Set { host, key, value } => set(&mut entries, &host, &key, &value),
+        Command::Tags => {
+            tags(&entries);
+            Ok(())
+        }
+        Command::Export { format, tag, output } => export(&entries, format, tag.as_deref(), output.as_deref()),
+        Command::Import { file, format, replace, dry_run } => import(&mut entries, config_path, &file, format, replace, dry_run),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Runs `ssh-config lint`, printing the diagnostics on stdout.
+///
+/// # Returns
+/// The exit code: 0 when there are no errors, 1 when there are and 2 when the file cannot be read.
+pub fn run_lint(path: &str) -> i32 {
+    let entries = match config::read_ssh_config(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read {}", e);
+            return 2;
+        }
+    };
+
+    let diagnostics = lint::lint(&entries);
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+
+    if lint::has_errors(&diagnostics) { 1 } else { 0 }
+}
+
+/// Prints the entries that differ between the config at `path` and the `entries` of `config_path`.
+///
+/// # Returns
+/// The exit code: 0 when the configs have the same entries, 1 when they differ, 2 when `path`
+/// cannot be read.
+fn run_diff(entries: &[SshConfigEntry], config_path: &str, path: &Path) -> i32 {
+    let other = match config::read_ssh_config(&path.display().to_string()) {
+        Ok(other) => other,
+        Err(e) => {
+            eprintln!("Failed to read {}", e);
+            return 2;
+        }
+    };
+
+    let diffs = profile::compare(entries, &other);
+    if diffs.is_empty() {
+        return 0;
+    }
+    println!("--- {}\n+++ {}", config_path, path.display());
+    for diff in &diffs {
+        match diff {
+            EntryDiff::OnlyLeft(label) => println!("- {}", label),
+            EntryDiff::OnlyRight(label) => println!("+ {}", label),
+            EntryDiff::Changed(label, options) => {
+                println!("~ {}", label);
+                for option in options {
+                    println!("    {}", option);
+                }
+            }
+        }
+    }
+    1
+}
+
+/// Index of the Host entry named `host`, either by its whole Host line or by one of its aliases.
+pub fn find_host(entries: &[SshConfigEntry], host: &str) -> Option<usize> {
+    entries.iter().position(|entry| {
+        entry.kind == EntryKind::Host && (entry.host == host || entry.patterns().contains(&host))
+    })
+}
+
+fn find_host_or_err(entries: &[SshConfigEntry], host: &str) -> Result<usize, String> {
+    find_host(entries, host).ok_or_else(|| format!("Host '{}' not found", host))
+}
+
+/// Writes the file an entry belongs to after it was changed.
+fn save(entries: &[SshConfigEntry], source: &Path) -> Result<(), String> {
+    config::save_ssh_config(source, entries)
+        .map_err(|e| format!("Failed to write {}: {}", source.display(), e))
+}
+
+fn list(entries: &[SshConfigEntry], tag: Option<&str>, format: Format) {
+    let selected = entries.iter()
+        .filter(|entry| tag.is_none() || entry.tag.as_deref() == tag)
+        .collect::<Vec<_>>();
+
+    match format {
+        Format::Plain => {
+            for entry in selected {
+                println!("{}", entry.label());
+            }
+        }
+        Format::Tsv => {
+            println!("host\tkind\thostname\tuser\tport\ttag\tfile");
+            for entry in selected {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    entry.host,
+                    entry.kind.keyword(),
+                    entry.get_option("HostName").unwrap_or_default(),
+                    entry.get_option("User").unwrap_or_default(),
+                    entry.get_option("Port").unwrap_or_default(),
+                    entry.tag.as_deref().unwrap_or_default(),
+                    entry.source.display(),
+                );
+            }
+        }
+        Format::Json => {
+            let json = selected.iter()
+                .map(|entry| serde_json::json!({
+                    "host": entry.host,
+                    "kind": entry.kind.keyword(),
+                    "tag": entry.tag,
+                    "file": entry.source.display().to_string(),
+                    "line": entry.line,
+                    "options": entry.options.iter()
+                        .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+                        .collect::<Vec<_>>(),
+                }))
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        }
+    }
+}
+
+fn show(entries: &[SshConfigEntry], host: &str, effective: bool) -> Result<(), String> {
+    if !effective {
+        let index = find_host_or_err(entries, host)?;
+        print!("{}", config::serialize_ssh_config(&entries[index..=index]));
+        return Ok(());
+    }
+
+    // The effective options work for any host name, defined or not
+    let resolution = resolve::resolve(entries, host);
+    for option in &resolution.options {
+        let origin = match option.source {
+            OptionSource::Entry(i) => format!("{}:{}", entries[i].source.display(), entries[i].line),
+            OptionSource::Default => "default".to_string(),
+        };
+        println!("{} {}\t# {}", option.key, option.value, origin);
+    }
+    for note in &resolution.notes {
+        println!("# {}", note);
+    }
+    Ok(())
+}
+
+fn add(
+    entries: &mut Vec<SshConfigEntry>,
+    config_path: &str,
+    host: &str,
+    tag: Option<String>,
+    options: &[String],
+) -> Result<(), String> {
+    entry::validate_host(host)?;
+    if find_host(entries, host).is_some() {
+        return Err(format!("Host '{}' already exists", host));
+    }
+
+    let mut new_entry = SshConfigEntry::new(EntryKind::Host, host, tag, PathBuf::from(config_path));
+    for option in options {
+        let (key, value) = option.split_once('=')
+            .ok_or_else(|| format!("Invalid option '{}', expected KEY=VALUE", option))?;
+        entry::validate_option(key.trim(), value.trim())?;
+        new_entry.add_option(key.trim().to_string(), value.trim().to_string());
+    }
+
+    let index = config::insert_entry(entries, new_entry);
+    save(entries, &entries[index].source)
+}
+
+/// Removes the Host entry named `host`. When `host` is one of several aliases of the entry, only
+/// that alias is removed from its Host line and the entry stays for the others.
+fn remove(entries: &mut Vec<SshConfigEntry>, host: &str) -> Result<(), String> {
+    let index = find_host_or_err(entries, host)?;
+    if entries[index].host == host {
+        let removed = entries.remove(index);
+        return save(entries, &removed.source);
+    }
+
+    let others = entries[index].patterns().into_iter()
+        .filter(|pattern| *pattern != host)
+        .collect::<Vec<_>>()
+        .join(" ");
+    eprintln!("Removed '{}' from 'Host {}'", host, entries[index].host);
+    entries[index].host = others;
+    save(entries, &entries[index].source)
+}
+
+/// Sets an option of the Host entry named `host`. The options of an entry with several aliases
+/// are shared by all of them, so it must be named by its whole Host line.
+fn set(entries: &mut [SshConfigEntry], host: &str, key: &str, value: &str) -> Result<(), String> {
+    let index = find_host_or_err(entries, host)?;
+    if entries[index].host != host {
+        return Err(format!(
+            "'{}' is one of the aliases of 'Host {}', give the whole Host line to change the options of all of them",
+            host, entries[index].host,
+        ));
+    }
+    entry::validate_option(key, value)?;
+    entries[index].set_option(key, value);
+    save(entries, &entries[index].source)
+}
+
+fn tags(entries: &[SshConfigEntry]) {
+    let mut counts: Vec<(Option<&str>, usize)> = Vec::new();
+    for entry in entries.iter().filter(|entry| entry.kind == EntryKind::Host) {
+        match counts.iter_mut().find(|(tag, _)| *tag == entry.tag.as_deref()) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((entry.tag.as_deref(), 1)),
+        }
+    }
+
+    for (tag, count) in counts {
+        println!("{}\t{}", tag.unwrap_or("(untagged)"), count);
+    }
+}
+
+fn export(entries: &[SshConfigEntry], format: ExchangeFormat, tag: Option<&str>, output: Option<&Path>) -> Result<(), String> {
+    let selected = entries.iter()
+        .filter(|entry| tag.is_none() || entry.tag.as_deref() == tag)
+        .cloned()
+        .collect::<Vec<_>>();
+    let exported = exchange::export(&selected, format)?;
+
+    match output {
+        Some(path) => fs::write(path, exported).map_err(|e| format!("Failed to write {}: {}", path.display(), e)),
+        None => {
+            print!("{}", exported);
+            Ok(())
+        }
+    }
+}
+
+fn import(
+    entries: &mut Vec<SshConfigEntry>,
+    config_path: &str,
+    file: &Path,
+    format: Option<ExchangeFormat>,
+    replace: bool,
+    dry_run: bool,
+) -> Result<(), String> {
+    let format = format.or_else(|| ExchangeFormat::from_path(file))
+        .ok_or_else(|| format!("Cannot tell the format of {} from its extension, use --format", file.display()))?;
+    let mut contents = String::new();
+    let read = if file == Path::new("-") {
+        io::stdin().read_to_string(&mut contents).map(|_| ())
+    } else {
+        fs::read_to_string(file).map(|read| contents = read)
+    };
+    read.map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+
+    let config_file = PathBuf::from(config_path);
+    let imported = exchange::import(&contents, format, &config_file)?;
+    let before = entries.clone();
+
+    let summary = if replace {
+        // The global options of the file stay, unless others are imported, and so do the files it includes
+        let keep_global = !imported.iter().any(|entry| entry.kind == EntryKind::Global);
+        let count = imported.len();
+        let (kept, others): (Vec<_>, Vec<_>) = entries.drain(..)
+            .filter(|entry| entry.source != config_file || (keep_global && entry.kind == EntryKind::Global))
+            .partition(|entry| entry.source == config_file);
+        entries.extend(kept.into_iter().chain(imported).chain(others));
+        format!("{} entries imported, replacing the entries of {}", count, config_path)
+    } else {
+        let merged = exchange::merge(entries, imported);
+        format!("{} entries added, {} updated", merged.added, merged.updated)
+    };
+
+    // Only the files whose contents change are written
+    let mut sources: Vec<&Path> = Vec::new();
+    for entry in before.iter().chain(entries.iter()) {
+        if !sources.contains(&entry.source.as_path()) {
+            sources.push(&entry.source);
+        }
+    }
+    for source in sources {
+        let contents = config::file_contents(source, entries);
+        if contents == config::file_contents(source, &before) {
+            continue;
+        }
+        if dry_run {
+            println!("==> {} <==\n{}", source.display(), contents);
+        } else {
+            save(entries, source)?;
+        }
+    }
+
+    eprintln!("{}", summary);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+Host web db
+    HostName 10.0.0.1
+    User deploy
+
+Host cache
+    HostName 10.0.0.3
+";
+
+    /// Writes `contents` to a file of its own and reads it back.
+    fn read(name: &str, contents: &str) -> (PathBuf, Vec<SshConfigEntry>) {
+        let dir = std::env::temp_dir().join(format!("ssh-config-cli-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        fs::write(&path, contents).unwrap();
+        let entries = config::read_ssh_config(&path.display().to_string()).unwrap();
+        (path, entries)
+    }
+
+    #[test]
+    fn removing_an_alias_keeps_the_others() {
+        let (path, mut entries) = read("remove", CONFIG);
+        remove(&mut entries, "db").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), CONFIG.replace("Host web db", "Host web"));
+
+        remove(&mut entries, "web").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Host cache\n    HostName 10.0.0.3\n");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn an_alias_does_not_set_the_options_of_the_others() {
+        let (path, mut entries) = read("set", CONFIG);
+        assert!(set(&mut entries, "db", "User", "x").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), CONFIG);
+
+        set(&mut entries, "web db", "User", "x").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), CONFIG.replace("User deploy", "User x"));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,80 +1,455 @@
 //! This module has functions to handle the reading and writing of the configuration file
 
-use regex::Regex;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use crate::entry::SshConfigEntry;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use crate::entry::{self, EntryKind, Inclusion, SshConfigEntry};
+use crate::error::Error;
+use crate::history::Change;
+use crate::layout::{self, Line};
 
 
+/// Maximum nesting of `Include` directives, the same limit used by OpenSSH.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The config file of the user, opened when no other file is given.
+pub fn default_path() -> PathBuf {
+    PathBuf::from(shellexpand::tilde("~/.ssh/config").into_owned())
+}
+
 /// Reads and parses the SSH config file at the given path.
 ///
+/// `Host` and `Match` lines start a new entry, and the options found before the first of them in a
+/// file become an `EntryKind::Global` entry. `Include` directives are followed recursively. The entries of the included files are placed
+/// right after the section holding the directive and keep the path of the file they came from in
+/// `SshConfigEntry::source`.
+///
 /// # Arguments
 /// * `path` - The path to the SSH config file.
 ///
 /// # Returns
-/// A vector of `SshConfigEntry` structs parsed from the file.
-pub fn read_ssh_config(path: &str) -> io::Result<Vec<SshConfigEntry>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+/// A vector of `SshConfigEntry` structs parsed from the file and the files it includes, or why the
+/// file could not be read. A broken include is skipped rather than failing the whole config.
+pub fn read_ssh_config(path: &str) -> Result<Vec<SshConfigEntry>, Error> {
+    let path = Path::new(path);
+    // Relative includes are resolved against the directory of the main file (~/.ssh)
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
     let mut entries = Vec::new();
-    let mut current_host = None;
-    let mut options = Vec::new();
-    let mut comments = Vec::new();
-    let mut current_tag: Option<String> = None;
-    let mut previous_tag: Option<String> = None;
-
-
-    let re_section = Regex::new(r"^\s*Host\s+(.+?)\s*$").unwrap();
-    let re_option = Regex::new(r"^\s*(\S+)\s+(.+?)\s*$").unwrap();
-    let re_comment = Regex::new(r"^\s*#.*$").unwrap();  // Catch all comment lines
-    let re_tag = Regex::new(r"^\s*# -+ ([^-\n]+) -+\s*$").unwrap();  // Specific tag format
-
-    for line in reader.lines() {
-        let line = line?.trim().to_string();
-
-        if let Some(caps) = re_tag.captures(&line) {
-            // Update the current tag when a tag-like comment is found
-            current_tag = Some(caps[1].trim().to_string());
-            // Continue to the next iteration to prevent adding tags as comments
-            continue;
+    let mut stack = Vec::new();
+
+    read_config_file(path, base_dir, &mut stack, &mut entries, None)?;
+
+    Ok(entries)
+}
+
+/// Reads the config like `read_ssh_config`, a missing file being an empty config as it is for ssh.
+/// The file is created when the first host is written.
+pub fn read_or_empty(path: &str) -> Result<Vec<SshConfigEntry>, Error> {
+    match read_ssh_config(path) {
+        Err(e) if e.is_not_found() => {
+            log::info!("{} does not exist, starting with no entries", path);
+            Ok(Vec::new())
         }
-        if re_comment.is_match(&line) {
-            // Before adding the comment, check if it's not a tag line
-            if re_tag.is_match(&line) {
-                continue; // Skip this as it's a tag, not a regular comment
-            } else {
-                comments.push(line);
+        result => result,
+    }
+}
+
+/// Parses a single config file and appends its entries to `entries`.
+///
+/// # Arguments
+/// * `path` - The file to read.
+/// * `base_dir` - The directory relative `Include` paths are resolved against.
+/// * `stack` - The canonical paths of the files being read, used to detect include cycles.
+/// * `entries` - Where the parsed entries are appended.
+fn read_config_file(
+    path: &Path,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    entries: &mut Vec<SshConfigEntry>,
+    included_by: Option<&Inclusion>,
+) -> Result<(), Error> {
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let parser = layout::Parser::new();
+    let mut lines = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        match line {
+            Ok(line) => lines.push(parser.parse(&line)),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                return Err(Error::Parse { path: path.to_path_buf(), line: index + 1, message: "not valid UTF-8".to_string() });
+            }
+            Err(e) => return Err(Error::io(path, e)),
+        }
+    }
+
+    stack.push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+
+    for block in layout::blocks(&lines) {
+        let mut entry = SshConfigEntry::new(block.kind, &block.host, block.tag, path.to_path_buf());
+        entry.line = block.line;
+        entry.included_by = included_by.cloned().map(Box::new);
+        // Entries of files included from inside the block
+        let mut included = Vec::new();
+        for (number, line) in lines.iter().enumerate().take(block.end).skip(block.start).map(|(i, line)| (i + 1, line)) {
+            match line {
+                Line::Option(key, value) => {
+                    // The Include line is kept as an option so that it is written back in place
+                    if key.eq_ignore_ascii_case("include") {
+                        let inclusion = Inclusion {
+                            source: path.to_path_buf(),
+                            kind: entry.kind,
+                            host: entry.host.clone(),
+                            patterns: value.clone(),
+                        };
+                        read_includes(&inclusion, base_dir, stack, &mut included);
+                    }
+                    entry.add_option(key.clone(), value.clone());
+                    entry.option_lines.push((key.clone(), value.clone(), number));
+                }
+                Line::Comment(comment) => entry.add_comment(comment.clone()),
+                _ => {}
+            }
+        }
+        entries.push(entry);
+        entries.append(&mut included);
+    }
+
+    stack.pop();
+
+    Ok(())
+}
+
+/// Reads the files matched by the patterns of an `Include` directive.
+///
+/// Each pattern goes through tilde expansion and globbing. Files that are already being read are
+/// skipped to break include cycles, and files that cannot be read are logged and skipped so that
+/// one broken include does not hide the rest of the config. The entries read remember the Include
+/// line, `resolve` applies them in its place.
+fn read_includes(inclusion: &Inclusion, base_dir: &Path, stack: &mut Vec<PathBuf>, entries: &mut Vec<SshConfigEntry>) {
+    if stack.len() >= MAX_INCLUDE_DEPTH {
+        log::warn!("Include {} ignored: nested too deeply", inclusion.patterns);
+        return;
+    }
+
+    for pattern in inclusion.patterns.split_whitespace() {
+        for path in expand_include(pattern, base_dir) {
+            let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if stack.contains(&canonical) {
+                log::warn!("Include cycle detected, skipping {}", path.display());
+                continue;
             }
-        } else if let Some(caps) = re_section.captures(&line) {
-            if let Some(host) = current_host.replace(caps[1].to_string()) {
-                entries.push(SshConfigEntry {
-                    host,
-                    options: options.drain(..).collect(),
-                    comments: comments.drain(..).collect(),
-                    tag: previous_tag.clone(),
-                });
+
+            log::debug!("Reading included file {}", path.display());
+            if let Err(e) = read_config_file(&path, base_dir, stack, entries, Some(inclusion)) {
+                log::warn!("Failed to read included file {}: {}", path.display(), e);
             }
-        } else if let Some(caps) = re_option.captures(&line) {
-            options.push((caps[1].to_string(), caps[2].to_string()));
         }
+    }
+}
 
-        //println!("current = {}, previous = {}",
-        //         current_tag.as_ref().map_or("None", String::as_str),
-        //         previous_tag.as_ref().map_or("None", String::as_str));
+/// Expands `~` and glob patterns of an `Include` argument into the files it matches, sorted by name.
+fn expand_include(pattern: &str, base_dir: &Path) -> Vec<PathBuf> {
+    let expanded = shellexpand::tilde(pattern).into_owned();
+    let full_pattern = if Path::new(&expanded).is_absolute() {
+        PathBuf::from(expanded)
+    } else {
+        base_dir.join(expanded)
+    };
 
-        if current_tag != previous_tag {
-            previous_tag = current_tag.clone();
+    match glob::glob(&full_pattern.to_string_lossy()) {
+        Ok(paths) => paths
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect(),
+        Err(e) => {
+            log::warn!("Invalid Include pattern {}: {}", pattern, e);
+            Vec::new()
         }
     }
+}
+
+
+/// Inserts a new entry into the section of its tag.
+///
+/// The entry goes after the last entry with the same tag and moves to the file of that entry. When
+/// the tag has no entries yet, it goes at the end of its own file, and an untagged entry goes
+/// before the first tag marker of its file so that it does not pick up that tag when read again.
+///
+/// # Returns
+/// The index of the inserted entry.
+pub fn insert_entry(entries: &mut Vec<SshConfigEntry>, mut entry: SshConfigEntry) -> usize {
+    let end_of_file = entries.iter()
+        .rposition(|other| other.source == entry.source)
+        .map_or(entries.len(), |last| last + 1);
+    let same_tag = entries.iter().rposition(|other| {
+        other.kind != EntryKind::Global
+            && other.tag == entry.tag
+            && (entry.tag.is_some() || other.source == entry.source)
+    });
 
-    if let Some(host) = current_host {
-        entries.push(SshConfigEntry {
-            host,
-            options,
-            comments,
-            tag: previous_tag
-        });
+    let position = match same_tag {
+        Some(last) => {
+            entry.source = entries[last].source.clone();
+            last + 1
+        }
+        None if entry.tag.is_none() => entries.iter()
+            .position(|other| other.source == entry.source && other.kind != EntryKind::Global)
+            .unwrap_or(end_of_file),
+        None => end_of_file,
+    };
+
+    entries.insert(position, entry);
+    position
+}
+
+/// Builds the change that moves the entry at `index` to the section of `tag`, an empty tag removes
+/// its tag. When the tag lives in another file the entry moves to that file.
+///
+/// # Returns
+/// The change and the index of the entry once it is applied.
+pub fn move_change(entries: &[SshConfigEntry], index: usize, tag: &str) -> Result<(Change, usize), String> {
+    entry::validate_tag(tag)?;
+
+    if index >= entries.len() {
+        return Err(format!("Entry {} no longer exists", index));
     }
+    let description = match tag {
+        "" => format!("remove the tag of {}", entries[index].label()),
+        tag => format!("move {} to {}", entries[index].label(), tag),
+    };
 
-    Ok(entries)
+    // Where the entry lands is found by moving it in a copy of the entries
+    let mut updated = entries.to_vec();
+    let mut moved = updated.remove(index);
+    moved.set_tag(tag.to_string());
+    let new_index = insert_entry(&mut updated, moved);
+
+    let change = Change::remove(description, entries, index)
+        .then(Change::insert(String::new(), new_index, updated[new_index].clone()));
+    Ok((change, new_index))
+}
+
+/// Serializes the entries into the SSH config file format, as a new file.
+///
+/// A `# ---- Tag ----` marker is written whenever the tag changes from one entry to the next, so
+/// that `read_ssh_config` assigns the same tags when the file is read again. The comments of an
+/// entry go above its section line, followed by the options in their order. Global options are
+/// written without a section line, so they must be the first entry of the file.
+///
+/// # Arguments
+/// * `entries` - The entries to serialize.
+///
+/// # Returns
+/// The contents of the config file as a `String`.
+pub fn serialize_ssh_config(entries: &[SshConfigEntry]) -> String {
+    layout::render("", entries)
+}
+
+/// The contents of `source` once the entries read from it are written, see `layout::render`.
+/// The lines of the entries that did not change are kept as they are in the file.
+pub fn file_contents(source: &Path, entries: &[SshConfigEntry]) -> String {
+    let file_entries = entries.iter()
+        .filter(|entry| entry.source == source)
+        .cloned()
+        .collect::<Vec<_>>();
+    let current = fs::read_to_string(source).unwrap_or_default();
+    layout::render(&current, &file_entries)
+}
+
+/// Writes the entries that were read from `source` back to that file, leaving the entries of
+/// every other file out and the lines of the unchanged entries as they were.
+///
+/// # Arguments
+/// * `source` - The config file to write, as found in `SshConfigEntry::source`.
+/// * `entries` - All the entries, from every file.
+pub fn save_ssh_config(source: &Path, entries: &[SshConfigEntry]) -> io::Result<()> {
+    write_atomic(source, &file_contents(source, entries))
+}
+
+/// Returns the path with `suffix` appended to its file name (`config` -> `config.bak`).
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Replaces the contents of `path` by writing a temporary file and renaming it over the target.
+/// The original file, if any, is copied to `<path>.bak` and its permissions are kept. A symlink,
+/// e.g. to a dotfiles repository, stays in place and the file it points to is written.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let target = fs::canonicalize(path).ok();
+    let path = target.as_deref().unwrap_or(path);
+    let tmp_path = with_suffix(path, ".tmp");
+    let backup_path = with_suffix(path, ".bak");
+
+    // The directory of a config written for the first time, e.g. ~/.ssh, private like ssh makes it
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty() && !dir.exists()) {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+
+    let mut tmp_file = File::create(&tmp_path)?;
+    tmp_file.write_all(contents.as_bytes())?;
+    tmp_file.sync_all()?;
+    drop(tmp_file);
+
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(&tmp_path, metadata.permissions())?;
+        fs::copy(path, &backup_path)?;
+        log::debug!("Backup of {} written to {}", path.display(), backup_path.display());
+    }
+
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::resolve;
+
+    const CONFIG: &str = "\
+# ssh config of the team
+ServerAliveInterval 60
+
+Host alpha
+  HostName alpha.example.com
+  # the deploy user
+  User deploy
+
+# ---- prod ----
+# behind the bastion
+Host gamma
+    HostName=10.0.0.3
+    Port=2222
+    ProxyJump bastion
+";
+
+    /// Writes `contents` to a file of its own and reads it back.
+    fn read(name: &str, contents: &str) -> (PathBuf, Vec<SshConfigEntry>) {
+        let dir = std::env::temp_dir().join(format!("ssh-config-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        fs::write(&path, contents).unwrap();
+        let entries = read_ssh_config(&path.display().to_string()).unwrap();
+        (path, entries)
+    }
+
+    #[test]
+    fn an_unchanged_config_is_written_as_it_was() {
+        let (path, entries) = read("unchanged", CONFIG);
+        assert_eq!(file_contents(&path, &entries), CONFIG);
+
+        let gamma = &entries[2];
+        assert_eq!(gamma.options[1], ("Port".to_string(), "2222".to_string()));
+        assert_eq!((gamma.line, gamma.option_line(1)), (11, 13));
+        assert_eq!(gamma.comments, ["# behind the bastion"]);
+        assert_eq!(entries[0].comments, ["# ssh config of the team"]);
+        assert_eq!(entries[1].comments, ["# the deploy user"]);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn only_the_changed_lines_are_written_again() {
+        let (path, mut entries) = read("changed", CONFIG);
+        entries[2].set_option("Port", "22");
+        entries[1].add_option("IdentityFile".to_string(), "~/.ssh/deploy".to_string());
+        entries[2].options.retain(|(key, _)| key != "ProxyJump");
+
+        assert_eq!(file_contents(&path, &entries), CONFIG
+            .replace("Port=2222", "Port=22")
+            .replace("  User deploy\n", "  User deploy\n  IdentityFile ~/.ssh/deploy\n")
+            .replace("    ProxyJump bastion\n", ""));
+
+        // What is written reads back the same
+        save_ssh_config(&path, &entries).unwrap();
+        let read_again = read_ssh_config(&path.display().to_string()).unwrap();
+        let strip = |entries: &[SshConfigEntry]| entries.iter().map(|entry| SshConfigEntry { line: 0, option_lines: Vec::new(), ..entry.clone() }).collect::<Vec<_>>();
+        assert_eq!(strip(&read_again), strip(&entries));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn new_and_moved_hosts_keep_their_comments_and_tags() {
+        let (path, mut entries) = read("moved", CONFIG);
+        let mut web = SshConfigEntry::new(EntryKind::Host, "web", Some("prod".to_string()), path.clone());
+        web.add_comment("# new".to_string());
+        web.add_option("User".to_string(), "www".to_string());
+        insert_entry(&mut entries, web);
+        // alpha joins the prod tag, after gamma
+        let mut alpha = entries.remove(1);
+        alpha.tag = Some("prod".to_string());
+        entries.push(alpha);
+
+        let contents = file_contents(&path, &entries);
+        assert_eq!(contents, "\
+# ssh config of the team
+ServerAliveInterval 60
+
+# ---- prod ----
+# behind the bastion
+Host gamma
+    HostName=10.0.0.3
+    Port=2222
+    ProxyJump bastion
+
+# new
+Host web
+    User www
+
+Host alpha
+  HostName alpha.example.com
+  # the deploy user
+  User deploy
+");
+        fs::write(&path, &contents).unwrap();
+        let read_again = read_ssh_config(&path.display().to_string()).unwrap();
+        assert_eq!(read_again.iter().map(|entry| (entry.label(), entry.tag.clone(), entry.comments.clone())).collect::<Vec<_>>(), [
+            ("(global options)".to_string(), None, vec!["# ssh config of the team".to_string()]),
+            ("gamma".to_string(), Some("prod".to_string()), vec!["# behind the bastion".to_string()]),
+            ("web".to_string(), Some("prod".to_string()), vec!["# new".to_string()]),
+            ("alpha".to_string(), Some("prod".to_string()), vec!["# the deploy user".to_string()]),
+        ]);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_symlinked_config_is_written_through_the_link() {
+        let (path, _) = read("symlink", "");
+        let dir = path.parent().unwrap();
+        let target = dir.join("dotfiles-config");
+        fs::write(&target, CONFIG).unwrap();
+        let link = dir.join("link");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, "Host alpha\n").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "Host alpha\n");
+        assert_eq!(fs::read_to_string(dir.join("dotfiles-config.bak")).unwrap(), CONFIG);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn included_entries_apply_at_the_include_line_of_their_block() {
+        let (path, _) = read("include", "");
+        let dir = path.parent().unwrap();
+        fs::write(dir.join("first"), "ServerAliveInterval 30\n").unwrap();
+        fs::write(dir.join("lab"), "User lab\n\nHost lab-*\n    Port 2200\n").unwrap();
+        fs::write(&path, "Include first\nServerAliveInterval 60\n\nHost db\n    Include lab\n    User db\n\nHost *\n    Port 22\n").unwrap();
+        let entries = read_ssh_config(&path.display().to_string()).unwrap();
+
+        let labels: Vec<String> = entries.iter().map(|entry| entry.label()).collect();
+        assert_eq!(labels, ["(global options)", "(global options)", "db", "(included in Host db)", "lab-*", "*"]);
+        assert_eq!(entries[3].included_by.as_ref().unwrap().patterns, "lab");
+
+        // The included file comes before the options after the Include line
+        assert_eq!(resolve(&entries, "db").get("ServerAliveInterval"), Some("30"));
+        assert_eq!(resolve(&entries, "db").get("User"), Some("lab"));
+        // and only applies under its block
+        assert_eq!(resolve(&entries, "web").get("User"), resolve(&[], "web").get("User"));
+        assert_eq!(resolve(&entries, "lab-1").get("Port"), Some("22"));
+    }
 }
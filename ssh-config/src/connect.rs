@@ -0,0 +1,86 @@
+//! This module builds the command used to connect to the selected host from the TUI
+
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use crate::entry::{EntryKind, SshConfigEntry};
+
+/// Template used when no other command is configured
+pub const DEFAULT_TEMPLATE: &str = "ssh {host}";
+
+/// A command line template where `{host}` is replaced by the alias of the selected host,
+/// e.g. `ssh {host}`, `mosh {host}` or `sftp {host}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectCommand {
+    template: Vec<String>,
+}
+
+impl ConnectCommand {
+    /// Creates the command from a template. The template is split on whitespace, and `{host}` is
+    /// appended at the end when the template does not have it.
+    pub fn new(template: &str) -> Self {
+        let mut template = template.split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if template.is_empty() {
+            return Self::new(DEFAULT_TEMPLATE);
+        }
+        if !template.iter().any(|arg| arg.contains("{host}")) {
+            template.push("{host}".to_string());
+        }
+        Self { template }
+    }
+
+    /// Name of the program that is run, shown in messages
+    pub fn program(&self) -> &str {
+        &self.template[0]
+    }
+
+    /// Builds the command for `host`, inheriting the terminal of the TUI. The OpenSSH programs are
+    /// given `config` with `-F`, unless the template already has a `-F`.
+    pub fn command(&self, host: &str, config: Option<&Path>) -> Command {
+        let mut args = self.template.iter().map(|arg| arg.replace("{host}", host));
+        let program = args.next().unwrap_or_default();
+        let mut command = Command::new(&program);
+
+        let name = Path::new(&program).file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let takes_config = ["ssh", "scp", "sftp"].contains(&name) && !self.template.iter().any(|arg| arg == "-F");
+        if let (Some(config), true) = (config, takes_config) {
+            command.arg("-F").arg(config);
+        }
+        command.args(args);
+        command
+    }
+
+    /// Runs the command for `host` and waits for it to exit. When it fails the user is asked to
+    /// press Enter, so that its error output can be read before the TUI takes the screen back.
+    pub fn run(&self, host: &str, config: Option<&Path>) -> io::Result<ExitStatus> {
+        log::info!("Running {} for {}", self.program(), host);
+        let result = self.command(host, config).status();
+
+        let failure = match &result {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(format!("{} exited with {}", self.program(), status)),
+            Err(e) => Some(format!("Failed to run {}: {}", self.program(), e)),
+        };
+        if let Some(failure) = failure {
+            log::warn!("{}", failure);
+            print!("{}. Press Enter to go back.", failure);
+            io::stdout().flush()?;
+            io::stdin().lock().read_line(&mut String::new())?;
+        }
+
+        result
+    }
+}
+
+/// The alias to pass to the connect command for an entry: the first pattern of a Host line that is
+/// not a wildcard nor a negation. Match blocks, global options and wildcard-only hosts have none.
+pub fn connect_alias(entry: &SshConfigEntry) -> Option<&str> {
+    if entry.kind != EntryKind::Host {
+        return None;
+    }
+    entry.patterns()
+        .into_iter()
+        .find(|pattern| !pattern.starts_with('!') && !pattern.contains('*') && !pattern.contains('?'))
+}
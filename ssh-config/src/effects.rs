@@ -0,0 +1,69 @@
+//! This module runs the file reads and writes that `update` asks for with `Command::Io`
+//!
+//! `update` only changes the state, so that it can be tested without files. What it needs from
+//! the disk is run here, between two events, and its result comes back as the next `AppEvent`.
+
+use crate::app::App;
+use crate::bulk;
+use crate::config;
+use crate::keys;
+use crate::known_hosts;
+use crate::lint;
+use crate::update::{AppEvent, Io};
+
+/// Runs the reads and writes of `io` for the state of `app`, giving the event with their result.
+pub fn run(app: &App, io: Io) -> AppEvent {
+    match io {
+        Io::Review { quitting } => AppEvent::Reviewed { writes: app.history.pending_writes(&app.entries), quitting },
+        Io::Write { writes, quitting } => {
+            for write in &writes {
+                log::info!("Writing {}", write.path.display());
+                if let Err(e) = write.write() {
+                    log::error!("Failed to write {}: {}", write.path.display(), e);
+                    let error = format!("Failed to write {}: {}", write.path.display(), e);
+                    return AppEvent::Written { written: Err(error), quitting };
+                }
+            }
+            AppEvent::Written { written: Ok(writes.len()), quitting }
+        }
+        Io::Export { marked, input } => {
+            let exported = bulk::export(&app.entries, &marked, &input)
+                .map(|path| format!("Exported {} entries to {}", marked.len(), path.display()))
+                .map_err(|e| e.to_string());
+            AppEvent::Exported(exported)
+        }
+        Io::Summary { marked, action, input } => AppEvent::Summary(bulk::summary(&app.entries, &marked, action, &input)),
+        Io::Lint => AppEvent::Linted(lint::lint(&app.entries)),
+        Io::Keys => AppEvent::Keys(keys::inventory(&app.entries, &app.ssh_dir)),
+        Io::KnownHosts { delete } => {
+            let path = app.ssh_dir.join("known_hosts");
+            let lines = delete.len();
+            let message = if delete.is_empty() {
+                None
+            } else {
+                Some(match known_hosts::remove_lines(&path, &delete.iter().collect::<Vec<_>>()) {
+                    Ok(()) => format!("Deleted {} line(s), the old file is in known_hosts.bak", lines),
+                    Err(e) => {
+                        log::error!("Failed to update {}: {}", path.display(), e);
+                        format!("Failed to update {}: {}", path.display(), e)
+                    }
+                })
+            };
+            let hosts = known_hosts::read_known_hosts(&path).map_err(|e| {
+                log::error!("Failed to read {}: {}", path.display(), e);
+                format!("Failed to read {}: {}", path.display(), e)
+            });
+            AppEvent::KnownHosts { path, hosts, message }
+        }
+        Io::Reload(changed) => {
+            let entries = config::read_or_empty(&app.config_path.display().to_string());
+            AppEvent::Reloaded { changed, entries }
+        }
+        Io::Profile { profile, diff } => {
+            let path = profile.path.display().to_string();
+            // A missing profile can be opened to start it, but there is nothing to compare with
+            let entries = if diff { config::read_ssh_config(&path) } else { config::read_or_empty(&path) };
+            AppEvent::Profile { profile, diff, entries }
+        }
+    }
+}
@@ -1,19 +1,101 @@
 //! This module contains the `SshConfigEntry` struct and its associated methods.
 
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::path::PathBuf;
+
+/// Kind of section an entry represents in the config file
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    #[default]
+    #[serde(alias = "Host")]
+    Host,    // `Host <patterns>` block
+    #[serde(alias = "Match")]
+    Match,   // `Match <criteria>` block, the criteria are kept in `host`
+    Global,  // Options before the first Host or Match, they apply to every host
+}
+
+impl EntryKind {
+    /// Keyword that starts the section in the config file, empty for the global options
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            EntryKind::Host => "Host",
+            EntryKind::Match => "Match",
+            EntryKind::Global => "",
+        }
+    }
+}
 
 /// Represents an SSH configuration entry.
 /// Each entry is composed of:
+/// * kind (Host, Match or global options)
 /// * host
 /// * options (Hash Map)
 /// * comments (Vector)
 /// * tag
-#[derive(Debug, Clone)]
+/// * source and line (where the entry starts in the config files)
+///
+/// With serde the options are a list of `{key, value}`, as keys can repeat and their order matters,
+/// and the source is called `file`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SshConfigEntry {
+    #[serde(default)]
+    pub kind: EntryKind,
     pub host: String,
+    #[serde(default, with = "option_list")]
     pub options: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub comments: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tag: Option<String>, // it is option so that it can be None or String.
+    #[serde(default, rename = "file")]
+    pub source: PathBuf,     // needed to write the entry back to the right file when using Include
+    #[serde(default)]
+    pub line: usize,         // line of the Host/Match line, 0 for entries that are not in a file yet
+    #[serde(skip)]
+    pub included_by: Option<Box<Inclusion>>, // the Include line that read the entry, None in the main file
+    #[serde(skip)]
+    pub option_lines: Vec<(String, String, usize)>, // the options as read from the file with their line, see `option_line`
+}
+
+/// The Include line an entry was read through.
+///
+/// ssh reads an included file where the Include line is, and only when the block around it
+/// applies, so the options before the first Host of that file are not global.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inclusion {
+    pub source: PathBuf,   // File with the Include line
+    pub kind: EntryKind,   // Block with the Include line
+    pub host: String,
+    pub patterns: String,  // Argument of the Include line
+}
+
+impl Inclusion {
+    /// Whether `entry` was read by the `Include patterns` line of `parent`.
+    pub fn is_from(&self, parent: &SshConfigEntry, patterns: &str) -> bool {
+        self.source == parent.source && self.kind == parent.kind && self.host == parent.host && self.patterns == patterns
+    }
+}
+
+/// (De)serializes the options of an entry as a list of `{key, value}`
+mod option_list {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct OptionPair<T> {
+        key: T,
+        value: T,
+    }
+
+    pub fn serialize<S: Serializer>(options: &[(String, String)], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(options.iter().map(|(key, value)| OptionPair { key, value }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, String)>, D::Error> {
+        let pairs = Vec::<OptionPair<String>>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().map(|pair| (pair.key, pair.value)).collect())
+    }
 }
 
 /// This Display is considered a trait and works like a file descriptor that calls the string formatter
@@ -23,7 +105,7 @@ pub struct SshConfigEntry {
 /// `"println!("{}", entry);`
 impl fmt::Display for SshConfigEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Host: {}", self.host)?;
+        writeln!(f, "{}", self.label())?;
         for (key, value) in &self.options {
             writeln!(f, "  {} => {}", key, value)?;
         }
@@ -39,59 +121,417 @@ impl fmt::Display for SshConfigEntry {
 
 impl SshConfigEntry {
 
+    /// Creates an entry without options that is not in a file yet.
+    pub fn new(kind: EntryKind, host: &str, tag: Option<String>, source: PathBuf) -> Self {
+        Self {
+            kind,
+            host: host.to_string(),
+            options: Vec::new(),
+            comments: Vec::new(),
+            tag,
+            source,
+            line: 0,
+            included_by: None,
+            option_lines: Vec::new(),
+        }
+    }
+
+    /// Entry of the file `config` with the given options, for the tests.
+    #[cfg(test)]
+    pub fn with_options(kind: EntryKind, host: &str, tag: Option<&str>, options: &[(&str, &str)]) -> Self {
+        let mut entry = Self::new(kind, host, tag.map(str::to_string), PathBuf::from("config"));
+        for (key, value) in options {
+            entry.add_option(key.to_string(), value.to_string());
+        }
+        entry
+    }
+
+    /// Name of the entry as shown in the hosts list
+    pub fn label(&self) -> String {
+        match self.kind {
+            EntryKind::Host => self.host.clone(),
+            EntryKind::Match => format!("Match {}", self.host),
+            EntryKind::Global => match &self.included_by {
+                Some(by) if by.kind != EntryKind::Global => format!("(included in {} {})", by.kind.keyword(), by.host),
+                _ => "(global options)".to_string(),
+            },
+        }
+    }
+
     pub fn add_option(&mut self, key: String, value: String) {
         self.options.push((key, value));
     }
 
+    /// Value of the first option with this keyword, keywords are case-insensitive.
+    pub fn get_option(&self, key: &str) -> Option<&str> {
+        self.options.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of the first option with this keyword, or adds the option at the end.
+    pub fn set_option(&mut self, key: &str, value: &str) {
+        match self.options.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            Some(option) => option.1 = value.to_string(),
+            None => self.add_option(key.to_string(), value.to_string()),
+        }
+    }
+
+    /// Line of the option at `index` in the file, or of the entry when the option was not read
+    /// from the file as it is now, e.g. after its value changed.
+    pub fn option_line(&self, index: usize) -> usize {
+        let (key, value) = &self.options[index];
+        // The same option can be repeated, the n-th one is on the line of the n-th one read
+        let nth = self.options[..index].iter().filter(|(k, v)| k == key && v == value).count();
+        self.option_lines.iter()
+            .filter(|(k, v, _)| k == key && v == value)
+            .nth(nth)
+            .map_or(self.line, |(_, _, line)| *line)
+    }
+
+    /// The patterns of a Host line, `Host web-* !web-test` gives `web-*` and `!web-test`.
+    pub fn patterns(&self) -> Vec<&str> {
+        if self.kind != EntryKind::Host {
+            return Vec::new();
+        }
+        self.host.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|pattern| !pattern.is_empty())
+            .collect()
+    }
+
+    /// First pattern of this Host line that `other` also lists, `Host db` shares `db` with `Host web db`.
+    /// Negated patterns are left out, they do not define a host.
+    pub fn shared_pattern(&self, other: &SshConfigEntry) -> Option<&str> {
+        let theirs = other.patterns();
+        self.patterns()
+            .into_iter()
+            .filter(|pattern| !pattern.starts_with('!'))
+            .find(|pattern| theirs.iter().any(|their| their.eq_ignore_ascii_case(pattern)))
+    }
+
+    /// Whether this is a Host entry whose patterns match `hostname`.
+    pub fn matches_host(&self, hostname: &str) -> bool {
+        self.kind == EntryKind::Host && match_pattern_list(&self.patterns(), hostname)
+    }
+
+    /// The options of the entry parsed into `SshOption`, see `SshOption::from_pair`.
+    pub fn typed_options(&self) -> Vec<SshOption> {
+        self.options.iter()
+            .map(|(key, value)| SshOption::from_pair(key, value))
+            .collect()
+    }
+
     pub fn add_comment(&mut self, comment: String) {
         self.comments.push(comment);
     }
 
+    /// Sets the tag, an empty tag removes it.
     pub fn set_tag(&mut self, tag: String) {
-        self.tag = Some(tag);
+        self.tag = if tag.is_empty() { None } else { Some(tag) };
     }
+}
 
-    /// This function is supposed to work like the Display trait but instead of just putting the entry
-    /// the call to the method *display()* needs to be there.
-    ///
-    /// `println!("{}", entry.display());`
-    pub fn display(&self) -> String {
-        let mut result = format!("Host: {}\n", self.host);
-        for (key, value) in &self.options {
-            result.push_str(&format!("  {} => {}\n", key, value));
-        }
-        for comment in &self.comments {
-            result.push_str(&format!("  // {}\n", comment));
+/// Matches `text` against an OpenSSH pattern, where `*` matches any sequence of characters and `?`
+/// a single one. Case is ignored, like ssh does for host names.
+pub fn match_pattern(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern and of the text it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, start)) = backtrack {
+            // Let the last `*` swallow one more character and try again
+            p = star + 1;
+            t = start + 1;
+            backtrack = Some((star, start + 1));
+        } else {
+            return false;
         }
-        if let Some(tag) = &self.tag {
-            result.push_str(&format!("  >> {}\n", tag));
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `hostname` against a list of patterns like the ones of a Host line.
+///
+/// The host matches when one of the patterns matches and none of the negated (`!pattern`) ones do.
+pub fn match_pattern_list(patterns: &[&str], hostname: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if match_pattern(negated, hostname) {
+                return false;
+            }
+        } else if match_pattern(pattern, hostname) {
+            matched = true;
         }
-        result
     }
+    matched
 }
 
-/// Log levels for SSH configuration
+/// Options that can be given several times and add up instead of the first one winning.
+pub fn is_multi_valued(key: &str) -> bool {
+    ["identityfile", "certificatefile", "localforward", "remoteforward", "dynamicforward", "sendenv", "setenv", "include"]
+        .contains(&key.to_lowercase().as_str())
+}
+
+/// A hop of a `ProxyJump` value, `[user@]host[:port]` or `ssh://[user@]host[:port]`
 #[derive(Debug, Clone, PartialEq)]
+pub struct JumpHost {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+/// Parses the hops of a `ProxyJump` value. The value `none` has no hops.
+pub fn parse_jump_specs(value: &str) -> Vec<JumpHost> {
+    if value.trim().eq_ignore_ascii_case("none") {
+        return Vec::new();
+    }
+
+    value.split(',')
+        .map(str::trim)
+        .filter(|hop| !hop.is_empty())
+        .map(|hop| {
+            let hop = hop.strip_prefix("ssh://").unwrap_or(hop);
+            let (user, hop) = match hop.rsplit_once('@') {
+                Some((user, host)) => (Some(user.to_string()), host),
+                None => (None, hop),
+            };
+            // Either `[ipv6]:port` or `host:port`
+            let (host, port) = match hop.strip_prefix('[') {
+                Some(rest) => {
+                    let (host, port) = rest.split_once(']').unwrap_or((rest, ""));
+                    (host, port.strip_prefix(':'))
+                }
+                None => match hop.split_once(':') {
+                    Some((host, port)) => (host, Some(port)),
+                    None => (hop, None),
+                },
+            };
+            JumpHost { user, host: host.to_string(), port: port.and_then(|port| port.parse().ok()) }
+        })
+        .collect()
+}
+
+/// Host names of the hops of a `ProxyJump` value: `user@bastion:2222,inner` gives `bastion` and
+/// `inner`. The value `none` has no hops.
+pub fn parse_jump_hosts(value: &str) -> Vec<String> {
+    parse_jump_specs(value).into_iter().map(|hop| hop.host).collect()
+}
+
+/// Checks that a host alias can be written to the config file.
+pub fn validate_host(host: &str) -> Result<(), String> {
+    if host.trim().is_empty() {
+        return Err("Host cannot be empty".to_string());
+    }
+    if host.contains('#') {
+        return Err("Host cannot contain '#'".to_string());
+    }
+    Ok(())
+}
+
+/// Checks that a tag can be written as a `# ---- tag ----` marker.
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    if tag.contains('\n') {
+        return Err(format!("Invalid tag '{}', a tag is a single line", tag.escape_debug()));
+    }
+    Ok(())
+}
+
+/// Checks that an option name can be written to the config file.
+pub fn validate_option_name(key: &str) -> Result<(), String> {
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid option name '{}'", key));
+    }
+    if key.eq_ignore_ascii_case("host") || key.eq_ignore_ascii_case("match") {
+        return Err(format!("'{}' starts a new section and cannot be used as an option", key));
+    }
+    Ok(())
+}
+
+/// Checks that an option can be written to the config file.
+///
+/// The values of the options known by `SshOption` must parse, any other option only needs a value.
+pub fn validate_option(key: &str, value: &str) -> Result<(), String> {
+    validate_option_name(key)?;
+    if value.trim().is_empty() {
+        return Err(format!("{} needs a value", key));
+    }
+    SshOption::parse(key, value).map(|_| ())
+}
+
+/// Parses a `yes`/`no` value.
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!("{} must be yes or no, not '{}'", key, value)),
+    }
+}
+
+fn format_bool(value: bool) -> String {
+    if value { "yes".to_string() } else { "no".to_string() }
+}
+
+/// Parses a number of the given type, used for ports and counters.
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("Invalid {} '{}'", key, value))
+}
+
+/// Log levels for SSH configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SshOptionLogLevel {
-    DEBUG,
-    INFO,
-    WARNING,
-    ERROR,
+    Quiet,
+    Fatal,
+    Error,
+    Info,
+    Verbose,
+    Debug,
+    Debug1,
+    Debug2,
+    Debug3,
+}
+
+impl SshOptionLogLevel {
+    const ALL: [(SshOptionLogLevel, &'static str); 9] = [
+        (SshOptionLogLevel::Quiet, "QUIET"),
+        (SshOptionLogLevel::Fatal, "FATAL"),
+        (SshOptionLogLevel::Error, "ERROR"),
+        (SshOptionLogLevel::Info, "INFO"),
+        (SshOptionLogLevel::Verbose, "VERBOSE"),
+        (SshOptionLogLevel::Debug, "DEBUG"),
+        (SshOptionLogLevel::Debug1, "DEBUG1"),
+        (SshOptionLogLevel::Debug2, "DEBUG2"),
+        (SshOptionLogLevel::Debug3, "DEBUG3"),
+    ];
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(value))
+            .map(|(level, _)| *level)
+    }
+
+    fn as_str(&self) -> &'static str {
+        Self::ALL.iter()
+            .find(|(level, _)| level == self)
+            .map(|(_, name)| *name)
+            .unwrap_or("INFO")
+    }
+}
+
+/// Values of `StrictHostKeyChecking`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SshStrictHostKeyChecking {
+    Yes,
+    No,         // `no` and `off`
+    Ask,
+    AcceptNew,
+}
+
+/// Values of `ControlMaster`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SshControlMaster {
+    Yes,
+    No,
+    Ask,
+    Auto,
+    AutoAsk,
+}
+
+/// Values of `ControlPersist`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SshControlPersist {
+    Yes,            // Keep the master open until it is told to exit, same as `0`
+    No,
+    Seconds(u64),   // Close the master after being idle for this long
+}
+
+/// Parses an OpenSSH time value like `600`, `10m` or `1h30m` into seconds.
+fn parse_duration(value: &str) -> Option<u64> {
+    let mut total: u64 = 0;
+    let mut number = String::new();
+
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let multiplier = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        let amount: u64 = number.parse().ok()?;
+        total = total.checked_add(amount.checked_mul(multiplier)?)?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        total = total.checked_add(number.parse().ok()?)?;
+    } else if value.is_empty() {
+        return None;
+    }
+
+    Some(total)
+}
+
+/// Formats seconds with the largest units that fit, the inverse of `parse_duration`.
+fn format_duration(seconds: u64) -> String {
+    if seconds == 0 {
+        return "0".to_string();
+    }
+
+    let mut rest = seconds;
+    let mut result = String::new();
+    for (unit, size) in [("w", 7 * 24 * 60 * 60), ("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60)] {
+        if rest >= size {
+            result.push_str(&format!("{}{}", rest / size, unit));
+            rest %= size;
+        }
+    }
+    if rest > 0 {
+        // Plain seconds when there are no other units keeps `600`-style values readable
+        if result.is_empty() {
+            result = rest.to_string();
+        } else {
+            result.push_str(&format!("{}s", rest));
+        }
+    }
+    result
 }
 
 /// SSH configuration options
+///
+/// Keywords are matched case-insensitively. Options that are not modeled here, and options whose
+/// value does not parse when using `SshOption::from_pair`, are kept verbatim in `Other`.
+///
+/// The typed form is for validating and showing options: it spells keywords the usual way and
+/// normalizes values, e.g. `StrictHostKeyChecking off` is `no` and `ControlPersist 90m` is
+/// `1h30m`. The entries keep the options as they were written.
 #[derive(Debug, Clone, PartialEq)]
 pub enum SshOption {
     HostName(String),
     User(String),
     Port(u16),
     IdentityFile(String),
-    StrictHostKeyChecking(bool),
+    StrictHostKeyChecking(SshStrictHostKeyChecking),
     ForwardX11(bool),
     LogLevel(SshOptionLogLevel),
-    ControlMaster(),
+    ControlMaster(SshControlMaster),
     ControlPath(String),
-    ControlPersist(),
+    ControlPersist(SshControlPersist),
     TCPKeepAlive(bool),
     ServerAliveInterval(u16),
     ServerAliveCountMax(u16),
@@ -99,4 +539,161 @@ pub enum SshOption {
     ProxyJump(String),
     ProxyCommand(String),
     LocalForward(String),
+    Other(String, String),
+}
+
+impl SshOption {
+
+    /// Parses a raw `(key, value)` option of an entry.
+    ///
+    /// # Returns
+    /// The typed option, `Other` for keywords that are not modeled, or an error message when the
+    /// value is not valid for the keyword.
+    pub fn parse(key: &str, value: &str) -> Result<SshOption, String> {
+        let value = value.trim();
+        let option = match key.to_lowercase().as_str() {
+            "hostname" => SshOption::HostName(value.to_string()),
+            "user" => SshOption::User(value.to_string()),
+            "port" => match parse_number::<u16>("Port", value)? {
+                0 => return Err("Port cannot be 0".to_string()),
+                port => SshOption::Port(port),
+            },
+            "identityfile" => SshOption::IdentityFile(value.to_string()),
+            "stricthostkeychecking" => SshOption::StrictHostKeyChecking(match value.to_lowercase().as_str() {
+                "yes" => SshStrictHostKeyChecking::Yes,
+                "no" | "off" => SshStrictHostKeyChecking::No,
+                "ask" => SshStrictHostKeyChecking::Ask,
+                "accept-new" => SshStrictHostKeyChecking::AcceptNew,
+                _ => return Err(format!("StrictHostKeyChecking must be yes, no, ask or accept-new, not '{}'", value)),
+            }),
+            "forwardx11" => SshOption::ForwardX11(parse_bool("ForwardX11", value)?),
+            "loglevel" => SshOption::LogLevel(SshOptionLogLevel::parse(value)
+                .ok_or_else(|| format!("Invalid LogLevel '{}'", value))?),
+            "controlmaster" => SshOption::ControlMaster(match value.to_lowercase().as_str() {
+                "yes" => SshControlMaster::Yes,
+                "no" => SshControlMaster::No,
+                "ask" => SshControlMaster::Ask,
+                "auto" => SshControlMaster::Auto,
+                "autoask" => SshControlMaster::AutoAsk,
+                _ => return Err(format!("ControlMaster must be yes, no, ask, auto or autoask, not '{}'", value)),
+            }),
+            "controlpath" => SshOption::ControlPath(value.to_string()),
+            "controlpersist" => SshOption::ControlPersist(match value.to_lowercase().as_str() {
+                "yes" => SshControlPersist::Yes,
+                "no" => SshControlPersist::No,
+                duration => SshControlPersist::Seconds(parse_duration(duration)
+                    .ok_or_else(|| format!("Invalid ControlPersist time '{}'", value))?),
+            }),
+            "tcpkeepalive" => SshOption::TCPKeepAlive(parse_bool("TCPKeepAlive", value)?),
+            "serveraliveinterval" => SshOption::ServerAliveInterval(parse_number("ServerAliveInterval", value)?),
+            "serveralivecountmax" => SshOption::ServerAliveCountMax(parse_number("ServerAliveCountMax", value)?),
+            "userknownhostsfile" => SshOption::UserKnownHostsFile(value.to_string()),
+            "proxyjump" => SshOption::ProxyJump(value.to_string()),
+            "proxycommand" => SshOption::ProxyCommand(value.to_string()),
+            "localforward" => SshOption::LocalForward(value.to_string()),
+            _ => SshOption::Other(key.to_string(), value.to_string()),
+        };
+        Ok(option)
+    }
+
+    /// Same as `parse`, but an invalid value is kept as `Other` instead of failing.
+    pub fn from_pair(key: &str, value: &str) -> SshOption {
+        SshOption::parse(key, value).unwrap_or_else(|_| SshOption::Other(key.to_string(), value.to_string()))
+    }
+
+    /// Keyword of the option as written in the config file.
+    pub fn keyword(&self) -> &str {
+        match self {
+            SshOption::HostName(_) => "HostName",
+            SshOption::User(_) => "User",
+            SshOption::Port(_) => "Port",
+            SshOption::IdentityFile(_) => "IdentityFile",
+            SshOption::StrictHostKeyChecking(_) => "StrictHostKeyChecking",
+            SshOption::ForwardX11(_) => "ForwardX11",
+            SshOption::LogLevel(_) => "LogLevel",
+            SshOption::ControlMaster(_) => "ControlMaster",
+            SshOption::ControlPath(_) => "ControlPath",
+            SshOption::ControlPersist(_) => "ControlPersist",
+            SshOption::TCPKeepAlive(_) => "TCPKeepAlive",
+            SshOption::ServerAliveInterval(_) => "ServerAliveInterval",
+            SshOption::ServerAliveCountMax(_) => "ServerAliveCountMax",
+            SshOption::UserKnownHostsFile(_) => "UserKnownHostsFile",
+            SshOption::ProxyJump(_) => "ProxyJump",
+            SshOption::ProxyCommand(_) => "ProxyCommand",
+            SshOption::LocalForward(_) => "LocalForward",
+            SshOption::Other(key, _) => key,
+        }
+    }
+
+    /// Value of the option as written in the config file.
+    pub fn value(&self) -> String {
+        match self {
+            SshOption::HostName(value)
+            | SshOption::User(value)
+            | SshOption::IdentityFile(value)
+            | SshOption::ControlPath(value)
+            | SshOption::UserKnownHostsFile(value)
+            | SshOption::ProxyJump(value)
+            | SshOption::ProxyCommand(value)
+            | SshOption::LocalForward(value)
+            | SshOption::Other(_, value) => value.clone(),
+            SshOption::Port(value)
+            | SshOption::ServerAliveInterval(value)
+            | SshOption::ServerAliveCountMax(value) => value.to_string(),
+            SshOption::ForwardX11(value) | SshOption::TCPKeepAlive(value) => format_bool(*value),
+            SshOption::LogLevel(level) => level.as_str().to_string(),
+            SshOption::StrictHostKeyChecking(value) => match value {
+                SshStrictHostKeyChecking::Yes => "yes",
+                SshStrictHostKeyChecking::No => "no",
+                SshStrictHostKeyChecking::Ask => "ask",
+                SshStrictHostKeyChecking::AcceptNew => "accept-new",
+            }.to_string(),
+            SshOption::ControlMaster(value) => match value {
+                SshControlMaster::Yes => "yes",
+                SshControlMaster::No => "no",
+                SshControlMaster::Ask => "ask",
+                SshControlMaster::Auto => "auto",
+                SshControlMaster::AutoAsk => "autoask",
+            }.to_string(),
+            SshOption::ControlPersist(value) => match value {
+                SshControlPersist::Yes => "yes".to_string(),
+                SshControlPersist::No => "no".to_string(),
+                SshControlPersist::Seconds(seconds) => format_duration(*seconds),
+            },
+        }
+    }
+
+    /// The option as a `(key, value)` pair in its usual spelling, to show it. This is not the pair
+    /// it was parsed from, write the options of the entry instead.
+    pub fn to_pair(&self) -> (String, String) {
+        (self.keyword().to_string(), self.value())
+    }
+}
+
+impl fmt::Display for SshOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.keyword(), self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_options_are_shown_in_their_usual_spelling() {
+        let pairs = [
+            (("stricthostkeychecking", "off"), ("StrictHostKeyChecking", "no")),
+            (("ControlPersist", "90m"), ("ControlPersist", "1h30m")),
+            (("LogLevel", "verbose"), ("LogLevel", "VERBOSE")),
+            (("port", "022"), ("Port", "22")),
+            (("Port", "ssh"), ("Port", "ssh")),
+            (("SendEnv", "LANG LC_*"), ("SendEnv", "LANG LC_*")),
+        ];
+        for ((key, value), expected) in pairs {
+            let (key, value) = SshOption::from_pair(key, value).to_pair();
+            assert_eq!((key.as_str(), value.as_str()), expected);
+        }
+        assert!(SshOption::parse("Port", "ssh").is_err());
+    }
 }
@@ -0,0 +1,51 @@
+//! This module has the errors that are shown in the TUI instead of ending it
+//!
+//! A config that cannot be read opens the TUI without entries and an error banner, the banner goes
+//! away once the file is read again. A terminal that fails to draw, or while another program owns
+//! it, is reported the same way, only a terminal that cannot be set up ends the TUI.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    Io { path: PathBuf, source: io::Error },                  // A config file could not be read
+    Parse { path: PathBuf, line: usize, message: String },    // A line of a config file is not valid
+    Terminal(io::Error),                                      // Raw mode, the alternate screen or a draw
+}
+
+impl Error {
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        Error::Io { path: path.to_path_buf(), source }
+    }
+
+    /// Whether the file does not exist, which is an empty config for ssh.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::Io { source, .. } if source.kind() == io::ErrorKind::NotFound)
+    }
+
+    /// Whether the entries of the config are missing, and nothing must be written over the file.
+    pub fn blocks_writes(&self) -> bool {
+        !matches!(self, Error::Terminal(_))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse { path, line, message } => write!(f, "{}, line {}: {}", path.display(), line, message),
+            Error::Terminal(e) => write!(f, "Terminal: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Terminal(source) => Some(source),
+            Error::Parse { .. } => None,
+        }
+    }
+}
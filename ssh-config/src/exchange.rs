@@ -0,0 +1,718 @@
+//! This module converts the entries to and from other formats: JSON, YAML, an Ansible inventory
+//! (INI or YAML) and CSV
+//!
+//! JSON and YAML hold every entry as it is, see the serde attributes of `SshConfigEntry`. The
+//! Ansible inventories only hold the Host entries with a name that is not a pattern, grouped by
+//! tag: `HostName`, `User`, `Port` and `IdentityFile` become the `ansible_*` variables and the
+//! other options go to `ansible_ssh_common_args` as `-o Key=Value`. The CSV has a column for the
+//! common options, the other options go to the `options` column, one `Key=Value` per line.
+
+use clap::ValueEnum;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::config;
+use crate::entry::{self, EntryKind, SshConfigEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExchangeFormat {
+    Json,
+    Yaml,
+    AnsibleIni,
+    AnsibleYaml,
+    Csv,
+}
+
+impl ExchangeFormat {
+    /// The format of a file from its extension, `.ini` being an Ansible inventory. A YAML
+    /// inventory cannot be told apart from the YAML entries, it has to be given.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(ExchangeFormat::Json),
+            "yaml" | "yml" => Some(ExchangeFormat::Yaml),
+            "ini" => Some(ExchangeFormat::AnsibleIni),
+            "csv" => Some(ExchangeFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Options that have an Ansible variable of their own, with the older names that are still read
+const ANSIBLE_VARS: [(&str, &str, &str); 4] = [
+    ("HostName", "ansible_host", "ansible_ssh_host"),
+    ("User", "ansible_user", "ansible_ssh_user"),
+    ("Port", "ansible_port", "ansible_ssh_port"),
+    ("IdentityFile", "ansible_ssh_private_key_file", "ansible_private_key_file"),
+];
+
+/// Variable with the other options, as `-o Key=Value` arguments
+const COMMON_ARGS: &str = "ansible_ssh_common_args";
+
+/// Options with a column of their own in the CSV
+const CSV_COLUMNS: [&str; 5] = ["HostName", "User", "Port", "IdentityFile", "ProxyJump"];
+
+/// Writes the entries in the given format.
+pub fn export(entries: &[SshConfigEntry], format: ExchangeFormat) -> Result<String, String> {
+    match format {
+        ExchangeFormat::Json => serde_json::to_string_pretty(entries)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string()),
+        ExchangeFormat::Yaml => serde_yaml::to_string(entries).map_err(|e| e.to_string()),
+        ExchangeFormat::AnsibleIni => Ok(export_ansible_ini(entries)),
+        ExchangeFormat::AnsibleYaml => export_ansible_yaml(entries),
+        ExchangeFormat::Csv => export_csv(entries),
+    }
+}
+
+/// Reads entries written in the given format, they get `source` as their file. Every entry is
+/// checked like the entries added from the TUI.
+pub fn import(contents: &str, format: ExchangeFormat, source: &Path) -> Result<Vec<SshConfigEntry>, String> {
+    let mut entries = match format {
+        ExchangeFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string())?,
+        ExchangeFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string())?,
+        ExchangeFormat::AnsibleIni => import_ansible_ini(contents)?,
+        ExchangeFormat::AnsibleYaml => import_ansible_yaml(contents)?,
+        ExchangeFormat::Csv => import_csv(contents)?,
+    };
+
+    for imported in &mut entries {
+        if imported.kind != EntryKind::Global {
+            entry::validate_host(&imported.host).map_err(|e| format!("{}: {}", imported.host, e))?;
+        }
+        if let Some(tag) = &imported.tag {
+            entry::validate_tag(tag).map_err(|e| format!("{}: {}", imported.label(), e))?;
+        }
+        for (key, value) in &imported.options {
+            entry::validate_option(key, value).map_err(|e| format!("{}: {}", imported.label(), e))?;
+        }
+        imported.source = source.to_path_buf();
+        imported.line = 0;
+    }
+    Ok(entries)
+}
+
+/// What `merge` did
+#[derive(Debug, Default, PartialEq)]
+pub struct Merged {
+    pub added: usize,
+    pub updated: usize,
+}
+
+/// Merges imported entries into the config: an entry with the same kind and Host line gets the
+/// imported options, which replace all its values of those options, and the imported tag. The
+/// other entries are added to the section of their tag.
+pub fn merge(entries: &mut Vec<SshConfigEntry>, imported: Vec<SshConfigEntry>) -> Merged {
+    let mut merged = Merged::default();
+
+    for new in imported {
+        let existing = entries.iter().position(|entry| {
+            entry.kind == new.kind && (entry.kind == EntryKind::Global || entry.host == new.host)
+        });
+        let Some(index) = existing else {
+            config::insert_entry(entries, new);
+            merged.added += 1;
+            continue;
+        };
+
+        let mut updated = entries[index].clone();
+        updated.options = merge_options(&updated.options, &new.options);
+        if new.tag.is_some() {
+            updated.tag = new.tag;
+        }
+        if updated == entries[index] {
+            continue;
+        }
+
+        merged.updated += 1;
+        if updated.tag == entries[index].tag || updated.kind == EntryKind::Global {
+            entries[index] = updated;
+        } else {
+            // Another tag, the entry moves to its section
+            entries.remove(index);
+            config::insert_entry(entries, updated);
+        }
+    }
+    merged
+}
+
+/// The current options with the values of the imported ones: the first line of an imported option
+/// gets all its imported values, its other lines are removed, and new options go at the end.
+fn merge_options(current: &[(String, String)], imported: &[(String, String)]) -> Vec<(String, String)> {
+    let imported_values = |key: &str| imported.iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut done: Vec<String> = Vec::new();
+    let mut options = Vec::new();
+
+    for (key, value) in current {
+        let values = imported_values(key);
+        if values.is_empty() {
+            options.push((key.clone(), value.clone()));
+        } else if !done.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+            options.extend(values);
+            done.push(key.clone());
+        }
+    }
+    for (key, value) in imported {
+        if !done.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+            options.push((key.clone(), value.clone()));
+        }
+    }
+    options
+}
+
+/// A host of an inventory: its name, group and variables
+struct InventoryHost {
+    name: String,
+    group: Option<String>,
+    vars: Vec<(String, String)>,
+}
+
+/// The inventory hosts of the Host entries: one for each alias of the Host line that is not a
+/// wildcard nor a negation, with the options of the entry.
+fn inventory_hosts(entries: &[SshConfigEntry]) -> Vec<InventoryHost> {
+    entries.iter()
+        .filter(|entry| entry.kind == EntryKind::Host)
+        .flat_map(|entry| {
+            let mut vars = Vec::new();
+            let mut args = Vec::new();
+            for (key, value) in &entry.options {
+                let var = ANSIBLE_VARS.iter().find(|(option, ..)| option.eq_ignore_ascii_case(key));
+                match var {
+                    Some((_, var, _)) if !vars.iter().any(|(v, _)| v == var) => vars.push((var.to_string(), value.clone())),
+                    _ => args.push(format!("-o {}", quote(&format!("{}={}", key, value)))),
+                }
+            }
+            if !args.is_empty() {
+                vars.push((COMMON_ARGS.to_string(), args.join(" ")));
+            }
+            entry.patterns().into_iter()
+                .filter(|name| !name.contains(['*', '?', '!']))
+                .map(|name| InventoryHost { name: name.to_string(), group: entry.tag.clone(), vars: vars.clone() })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Builds the entry of an inventory host from its variables, the unknown ones are left out.
+fn inventory_entry(host: InventoryHost) -> Result<SshConfigEntry, String> {
+    let mut entry = SshConfigEntry::new(EntryKind::Host, &host.name, host.group, Default::default());
+    for (option, var, old_var) in ANSIBLE_VARS {
+        if let Some((_, value)) = host.vars.iter().find(|(name, _)| name == var || name == old_var) {
+            entry.add_option(option.to_string(), value.clone());
+        }
+    }
+    if let Some((_, args)) = host.vars.iter().find(|(name, _)| name == COMMON_ARGS) {
+        let words = split_words(args);
+        let mut words = words.iter();
+        while let Some(word) = words.next() {
+            let option = match word.strip_prefix("-o") {
+                Some("") => words.next().map(String::as_str),
+                Some(option) => Some(option),
+                None => None,
+            };
+            let Some((key, value)) = option.and_then(|option| option.split_once('=')) else {
+                return Err(format!("{}: only -o Key=Value is understood in {}, not '{}'", host.name, COMMON_ARGS, word));
+            };
+            entry.add_option(key.to_string(), value.to_string());
+        }
+    }
+    Ok(entry)
+}
+
+/// Name of the group of a tag, the untagged hosts are `ungrouped` like in Ansible.
+fn group_name(tag: &Option<String>) -> &str {
+    tag.as_deref().unwrap_or("ungrouped")
+}
+
+/// Tag of a group, the implicit groups of Ansible give no tag.
+fn group_tag(group: &str) -> Option<String> {
+    match group {
+        "all" | "ungrouped" => None,
+        group => Some(group.to_string()),
+    }
+}
+
+/// Quotes a value for the inventory and `ansible_ssh_common_args` when it has spaces or quotes,
+/// with double quotes when it has single quotes.
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"') {
+        value.to_string()
+    } else if value.contains('\'') && !value.contains('"') {
+        format!("\"{}\"", value)
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Splits on whitespace outside of single and double quotes, removing the quotes.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '\\') => {
+                word.extend(chars.next());
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Expands the numeric ranges of an inventory host, `web[01:03]` gives `web01` to `web03`.
+fn expand_range(name: &str) -> Vec<String> {
+    let range = name.find('[').and_then(|start| {
+        let end = start + name[start..].find(']')?;
+        let (from, to) = name[start + 1..end].split_once(':')?;
+        let width = if from.starts_with('0') { from.len() } else { 0 };
+        Some((start, end, from.parse::<u64>().ok()?, to.parse::<u64>().ok()?, width))
+    });
+
+    match range {
+        Some((start, end, from, to, width)) if from <= to => (from..=to)
+            .flat_map(|n| expand_range(&format!("{}{:0width$}{}", &name[..start], n, &name[end + 1..], width = width)))
+            .collect(),
+        _ => vec![name.to_string()],
+    }
+}
+
+fn export_ansible_ini(entries: &[SshConfigEntry]) -> String {
+    let hosts = inventory_hosts(entries);
+    let mut groups: Vec<&Option<String>> = Vec::new();
+    for host in &hosts {
+        if !groups.contains(&&host.group) {
+            groups.push(&host.group);
+        }
+    }
+
+    let mut output = String::new();
+    for group in groups {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format!("[{}]\n", group_name(group)));
+        for host in hosts.iter().filter(|host| &host.group == group) {
+            output.push_str(&host.name);
+            for (var, value) in &host.vars {
+                output.push_str(&format!(" {}={}", var, quote(value)));
+            }
+            output.push('\n');
+        }
+    }
+    output
+}
+
+fn import_ansible_ini(contents: &str) -> Result<Vec<SshConfigEntry>, String> {
+    let mut hosts: Vec<InventoryHost> = Vec::new();
+    let mut group_vars: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    // Current section: a group of hosts, its `:vars` or its `:children`
+    let mut section = ("ungrouped".to_string(), "");
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = match name.split_once(':') {
+                Some((group, "vars")) => (group.to_string(), "vars"),
+                Some((group, "children")) => (group.to_string(), "children"),
+                _ => (name.to_string(), ""),
+            };
+            continue;
+        }
+
+        let mut words = split_words(line).into_iter();
+        let first = words.next().unwrap_or_default();
+        match section.1 {
+            "children" => {}
+            "vars" => {
+                let (var, value) = line.split_once('=')
+                    .ok_or_else(|| format!("line {}: expected name=value in [{}:vars]", number + 1, section.0))?;
+                let value = split_words(value).join(" ");
+                group_vars.entry(section.0.clone()).or_default().push((var.trim().to_string(), value));
+            }
+            _ => {
+                let vars = words
+                    .map(|word| word.split_once('=')
+                        .map(|(var, value)| (var.to_string(), value.to_string()))
+                        .ok_or_else(|| format!("line {}: expected name=value, not '{}'", number + 1, word)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for name in expand_range(&first) {
+                    if hosts.iter().any(|host| host.name == name) {
+                        continue;
+                    }
+                    hosts.push(InventoryHost { name, group: group_tag(&section.0), vars: vars.clone() });
+                }
+            }
+        }
+    }
+
+    // The variables of a group apply to its hosts that do not set them
+    for host in &mut hosts {
+        let group = host.group.clone().unwrap_or_else(|| "ungrouped".to_string());
+        for (var, value) in group_vars.get(&group).into_iter().chain(group_vars.get("all")).flatten() {
+            if !host.vars.iter().any(|(name, _)| name == var) {
+                host.vars.push((var.clone(), value.clone()));
+            }
+        }
+    }
+    hosts.into_iter().map(inventory_entry).collect()
+}
+
+fn export_ansible_yaml(entries: &[SshConfigEntry]) -> Result<String, String> {
+    let host_vars = |host: &InventoryHost| {
+        let vars = host.vars.iter()
+            .map(|(var, value)| (Value::from(var.as_str()), Value::from(value.as_str())))
+            .collect::<Mapping>();
+        if vars.is_empty() { Value::Null } else { Value::Mapping(vars) }
+    };
+
+    let mut ungrouped = Mapping::new();
+    let mut children = Mapping::new();
+    for host in inventory_hosts(entries) {
+        let hosts = match &host.group {
+            None => &mut ungrouped,
+            Some(group) => {
+                let group = children.entry(Value::from(group.as_str()))
+                    .or_insert_with(|| Value::Mapping(single("hosts", Value::Mapping(Mapping::new()))));
+                group.get_mut("hosts").and_then(Value::as_mapping_mut).expect("groups are created with hosts")
+            }
+        };
+        hosts.insert(Value::from(host.name.as_str()), host_vars(&host));
+    }
+
+    let mut all = Mapping::new();
+    if !ungrouped.is_empty() {
+        all.insert("hosts".into(), Value::Mapping(ungrouped));
+    }
+    if !children.is_empty() {
+        all.insert("children".into(), Value::Mapping(children));
+    }
+    let inventory = single("all", Value::Mapping(all));
+    serde_yaml::to_string(&inventory).map_err(|e| e.to_string())
+}
+
+/// A mapping with a single key.
+fn single(key: &str, value: Value) -> Mapping {
+    let mut mapping = Mapping::new();
+    mapping.insert(Value::from(key), value);
+    mapping
+}
+
+fn import_ansible_yaml(contents: &str) -> Result<Vec<SshConfigEntry>, String> {
+    let inventory: Value = serde_yaml::from_str(contents).map_err(|e| e.to_string())?;
+    let groups = inventory.as_mapping().ok_or("The inventory is not a mapping of groups")?;
+
+    let mut hosts = Vec::new();
+    for (name, group) in groups {
+        read_yaml_group(name.as_str().unwrap_or_default(), group, &[], &mut hosts)?;
+    }
+    hosts.into_iter().map(inventory_entry).collect()
+}
+
+/// Reads the hosts of a group of a YAML inventory and of its children, the variables of a group
+/// apply to the hosts that do not set them.
+fn read_yaml_group(name: &str, group: &Value, inherited: &[(String, String)], hosts: &mut Vec<InventoryHost>) -> Result<(), String> {
+    let vars_of = |value: Option<&Value>| -> Result<Vec<(String, String)>, String> {
+        let Some(Value::Mapping(vars)) = value else {
+            return Ok(Vec::new());
+        };
+        vars.iter()
+            .map(|(var, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    Value::Number(value) => value.to_string(),
+                    Value::Bool(value) => value.to_string(),
+                    _ => return Err(format!("group {}: the value of {:?} is not a scalar", name, var)),
+                };
+                Ok((var.as_str().unwrap_or_default().to_string(), value))
+            })
+            .collect()
+    };
+
+    let mut vars = vars_of(group.get("vars"))?;
+    for (var, value) in inherited {
+        if !vars.iter().any(|(name, _)| name == var) {
+            vars.push((var.clone(), value.clone()));
+        }
+    }
+
+    if let Some(Value::Mapping(group_hosts)) = group.get("hosts") {
+        for (host, host_vars) in group_hosts {
+            let mut host_vars = vars_of(Some(host_vars))?;
+            for (var, value) in &vars {
+                if !host_vars.iter().any(|(name, _)| name == var) {
+                    host_vars.push((var.clone(), value.clone()));
+                }
+            }
+            for host in expand_range(host.as_str().unwrap_or_default()) {
+                if !hosts.iter().any(|other: &InventoryHost| other.name == host) {
+                    hosts.push(InventoryHost { name: host, group: group_tag(name), vars: host_vars.clone() });
+                }
+            }
+        }
+    }
+    if let Some(Value::Mapping(children)) = group.get("children") {
+        for (child, child_group) in children {
+            read_yaml_group(child.as_str().unwrap_or_default(), child_group, &vars, hosts)?;
+        }
+    }
+    Ok(())
+}
+
+fn export_csv(entries: &[SshConfigEntry]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let header = ["kind", "host", "tag"].iter().chain(&CSV_COLUMNS).chain(&["options"]);
+    writer.write_record(header).map_err(|e| e.to_string())?;
+
+    for entry in entries {
+        let mut columns = vec![String::new(); CSV_COLUMNS.len()];
+        let mut others = Vec::new();
+        for (key, value) in &entry.options {
+            match CSV_COLUMNS.iter().position(|column| column.eq_ignore_ascii_case(key)) {
+                Some(i) if columns[i].is_empty() => columns[i] = value.clone(),
+                _ => others.push(format!("{}={}", key, value)),
+            }
+        }
+
+        let kind = match entry.kind {
+            EntryKind::Global => "global",
+            kind => kind.keyword(),
+        };
+        let mut record = vec![kind.to_string(), entry.host.clone(), entry.tag.clone().unwrap_or_default()];
+        record.extend(columns);
+        record.push(others.join("\n"));
+        writer.write_record(record).map_err(|e| e.to_string())?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// Reads a CSV with a `host` column. `kind`, `tag` and `options` are optional, and every other
+/// column is an option, so that a sheet with `host,HostName,User` can be imported as it is.
+fn import_csv(contents: &str) -> Result<Vec<SshConfigEntry>, String> {
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let column = |name: &str| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name));
+    let host_column = column("host").ok_or("The CSV has no host column")?;
+    let (kind_column, tag_column, options_column) = (column("kind"), column("tag"), column("options"));
+
+    let mut entries = Vec::new();
+    for (number, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| e.to_string())?;
+        let field = |column: Option<usize>| column.and_then(|i| record.get(i)).map(str::trim).unwrap_or_default();
+
+        let kind = match field(kind_column).to_ascii_lowercase().as_str() {
+            "" | "host" => EntryKind::Host,
+            "match" => EntryKind::Match,
+            "global" => EntryKind::Global,
+            other => return Err(format!("row {}: unknown kind '{}'", number + 2, other)),
+        };
+        let tag = Some(field(tag_column).to_string()).filter(|tag| !tag.is_empty());
+        let mut entry = SshConfigEntry::new(kind, field(Some(host_column)), tag, Default::default());
+
+        for (i, header) in headers.iter().enumerate() {
+            let value = record.get(i).unwrap_or_default().trim();
+            if value.is_empty() || [Some(host_column), kind_column, tag_column, options_column].contains(&Some(i)) {
+                continue;
+            }
+            entry.add_option(header.trim().to_string(), value.to_string());
+        }
+        for option in field(options_column).lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = option.split_once('=')
+                .ok_or_else(|| format!("row {}: expected Key=Value in options, not '{}'", number + 2, option))?;
+            entry.add_option(key.trim().to_string(), value.trim().to_string());
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<SshConfigEntry> {
+        vec![
+            SshConfigEntry::with_options(EntryKind::Global, "", None, &[("ServerAliveInterval", "30")]),
+            SshConfigEntry::with_options(EntryKind::Host, "bastion", None, &[("HostName", "203.0.113.1"), ("User", "admin")]),
+            SshConfigEntry::with_options(EntryKind::Host, "web-1", Some("prod"), &[
+                ("HostName", "10.0.0.1"),
+                ("Port", "2222"),
+                ("IdentityFile", "~/.ssh/prod"),
+                ("ProxyJump", "bastion"),
+                ("ProxyCommand", "ssh -W %h:%p jump"),
+            ]),
+            SshConfigEntry::with_options(EntryKind::Host, "db-1", Some("prod"), &[("HostName", "10.0.0.2")]),
+        ]
+    }
+
+    /// Imports what was exported, the entries should come back.
+    fn round_trip(entries: &[SshConfigEntry], format: ExchangeFormat) -> Vec<SshConfigEntry> {
+        let exported = export(entries, format).unwrap();
+        import(&exported, format, Path::new("config")).unwrap()
+    }
+
+    #[test]
+    fn json_and_yaml_keep_every_entry() {
+        let entries = sample();
+        assert_eq!(round_trip(&entries, ExchangeFormat::Json), entries);
+        assert_eq!(round_trip(&entries, ExchangeFormat::Yaml), entries);
+
+        let json = export(&entries[1..2], ExchangeFormat::Json).unwrap();
+        assert!(json.contains(r#""kind": "host""#));
+        assert!(json.contains(r#"{
+        "key": "User",
+        "value": "admin"
+      }"#));
+    }
+
+    #[test]
+    fn ansible_inventories_keep_the_hosts_and_their_groups() {
+        let entries = sample();
+        for format in [ExchangeFormat::AnsibleIni, ExchangeFormat::AnsibleYaml] {
+            let imported = round_trip(&entries, format);
+            // The global options are not in an inventory, the common ansible variables come first
+            assert_eq!(imported.len(), 3, "{:?}", format);
+            assert_eq!(imported[0], entries[1]);
+            assert_eq!(imported[2], entries[3]);
+            assert_eq!(imported[1].tag.as_deref(), Some("prod"));
+            assert_eq!(imported[1].options, vec![
+                ("HostName".to_string(), "10.0.0.1".to_string()),
+                ("Port".to_string(), "2222".to_string()),
+                ("IdentityFile".to_string(), "~/.ssh/prod".to_string()),
+                ("ProxyJump".to_string(), "bastion".to_string()),
+                ("ProxyCommand".to_string(), "ssh -W %h:%p jump".to_string()),
+            ]);
+        }
+
+        assert_eq!(export(&entries, ExchangeFormat::AnsibleIni).unwrap(), r#"[ungrouped]
+bastion ansible_host=203.0.113.1 ansible_user=admin
+
+[prod]
+web-1 ansible_host=10.0.0.1 ansible_port=2222 ansible_ssh_private_key_file=~/.ssh/prod ansible_ssh_common_args="-o ProxyJump=bastion -o 'ProxyCommand=ssh -W %h:%p jump'"
+db-1 ansible_host=10.0.0.2
+"#);
+    }
+
+    #[test]
+    fn every_alias_is_an_inventory_host() {
+        let entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "web db", Some("prod"), &[("User", "deploy")]),
+            SshConfigEntry::with_options(EntryKind::Host, "lab-* !lab-1", None, &[]),
+        ];
+        assert_eq!(export(&entries, ExchangeFormat::AnsibleIni).unwrap(), "[prod]\nweb ansible_user=deploy\ndb ansible_user=deploy\n");
+    }
+
+    #[test]
+    fn inventories_written_by_hand_are_read() {
+        let ini = "\
+# Web servers
+[web-servers]
+web[01:02] ansible_user=deploy
+lb ansible_host=lb.example.com ansible_port=2200
+
+[web-servers:vars]
+ansible_ssh_private_key_file=~/.ssh/web
+
+[all:children]
+web-servers
+";
+        let entries = import(ini, ExchangeFormat::AnsibleIni, Path::new("config")).unwrap();
+        assert_eq!(entries.iter().map(SshConfigEntry::label).collect::<Vec<_>>(), ["web01", "web02", "lb"]);
+        assert_eq!(entries[0].tag.as_deref(), Some("web-servers"));
+        assert_eq!(entries[1].options, vec![
+            ("User".to_string(), "deploy".to_string()),
+            ("IdentityFile".to_string(), "~/.ssh/web".to_string()),
+        ]);
+        assert_eq!(entries[2].get_option("Port"), Some("2200"));
+
+        let yaml = "
+all:
+  vars:
+    ansible_user: ops
+  children:
+    db:
+      hosts:
+        db1:
+          ansible_host: 10.0.1.1
+          ansible_port: 5022
+        db2:
+";
+        let entries = import(yaml, ExchangeFormat::AnsibleYaml, Path::new("config")).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tag.as_deref(), Some("db"));
+        assert_eq!(entries[0].options, vec![
+            ("HostName".to_string(), "10.0.1.1".to_string()),
+            ("User".to_string(), "ops".to_string()),
+            ("Port".to_string(), "5022".to_string()),
+        ]);
+        assert_eq!(entries[1].options, vec![("User".to_string(), "ops".to_string())]);
+    }
+
+    #[test]
+    fn csv_has_columns_for_the_common_options() {
+        let entries = sample();
+        assert_eq!(round_trip(&entries, ExchangeFormat::Csv), entries);
+
+        let csv = "host,User,tag\nweb,deploy,prod\n";
+        let imported = import(csv, ExchangeFormat::Csv, Path::new("config")).unwrap();
+        assert_eq!(imported, vec![SshConfigEntry::with_options(EntryKind::Host, "web", Some("prod"), &[("User", "deploy")])]);
+    }
+
+    #[test]
+    fn invalid_entries_are_refused() {
+        let csv = "host,Port\nweb,abc\n";
+        assert!(import(csv, ExchangeFormat::Csv, Path::new("config")).is_err());
+        let csv = "host,tag\nweb,\"a\nb\"\n";
+        assert!(import(csv, ExchangeFormat::Csv, Path::new("config")).is_err());
+    }
+
+    #[test]
+    fn merge_updates_the_entries_that_exist_and_adds_the_others() {
+        let mut entries = sample();
+        let imported = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "db-1", Some("prod"), &[("HostName", "10.0.0.20"), ("User", "postgres")]),
+            SshConfigEntry::with_options(EntryKind::Host, "bastion", Some("prod"), &[]),
+            SshConfigEntry::with_options(EntryKind::Host, "web-2", Some("prod"), &[("HostName", "10.0.0.3")]),
+        ];
+
+        assert_eq!(merge(&mut entries, imported), Merged { added: 1, updated: 2 });
+        assert_eq!(
+            entries.iter().map(SshConfigEntry::label).collect::<Vec<_>>(),
+            ["(global options)", "web-1", "db-1", "bastion", "web-2"],
+        );
+        assert_eq!(entries[2].options, vec![
+            ("HostName".to_string(), "10.0.0.20".to_string()),
+            ("User".to_string(), "postgres".to_string()),
+        ]);
+        assert_eq!(entries[3].get_option("User"), Some("admin"));
+    }
+}
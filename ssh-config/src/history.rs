@@ -0,0 +1,265 @@
+//! This module keeps the changes made to the entries in the TUI so that they can be undone and
+//! redone until the config files are written
+//!
+//! A change is a list of steps on the entries vector, each one knowing how to revert itself, e.g.
+//! moving a host to another tag removes it from its place and inserts it in the section of the tag.
+
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::config;
+use crate::layout;
+use crate::entry::SshConfigEntry;
+
+/// One edit of the entries vector
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Insert(usize, SshConfigEntry),
+    Remove(usize, SshConfigEntry),
+    Replace(usize, SshConfigEntry, SshConfigEntry),  // Index, entry before and after
+}
+
+impl Step {
+    fn apply(&self, entries: &mut Vec<SshConfigEntry>) {
+        match self {
+            Step::Insert(index, entry) => entries.insert(*index, entry.clone()),
+            Step::Remove(index, _) => {
+                entries.remove(*index);
+            }
+            Step::Replace(index, _, after) => entries[*index] = after.clone(),
+        }
+    }
+
+    fn inverse(&self) -> Step {
+        match self {
+            Step::Insert(index, entry) => Step::Remove(*index, entry.clone()),
+            Step::Remove(index, entry) => Step::Insert(*index, entry.clone()),
+            Step::Replace(index, before, after) => Step::Replace(*index, after.clone(), before.clone()),
+        }
+    }
+
+    /// The entries the step is about, before and after it.
+    fn entries(&self) -> Vec<&SshConfigEntry> {
+        match self {
+            Step::Insert(_, entry) | Step::Remove(_, entry) => vec![entry],
+            Step::Replace(_, before, after) => vec![before, after],
+        }
+    }
+}
+
+/// An operation of the user, described for the status line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub description: String,
+    steps: Vec<Step>,
+}
+
+impl Change {
+    /// A change without steps, for the steps of other changes to be chained to with `then`.
+    pub fn new(description: String) -> Self {
+        Self { description, steps: Vec::new() }
+    }
+
+    /// Adds `entry` at `index`.
+    pub fn insert(description: String, index: usize, entry: SshConfigEntry) -> Self {
+        Self { description, steps: vec![Step::Insert(index, entry)] }
+    }
+
+    /// Removes the entry at `index`.
+    pub fn remove(description: String, entries: &[SshConfigEntry], index: usize) -> Self {
+        Self { description, steps: vec![Step::Remove(index, entries[index].clone())] }
+    }
+
+    /// Puts `entry` in place of the entry at `index`, for renames and option changes.
+    pub fn replace(description: String, entries: &[SshConfigEntry], index: usize, entry: SshConfigEntry) -> Self {
+        Self { description, steps: vec![Step::Replace(index, entries[index].clone(), entry)] }
+    }
+
+    /// Chains another change after this one, its indices being those after this one is applied.
+    pub fn then(mut self, other: Change) -> Self {
+        self.steps.extend(other.steps);
+        self
+    }
+
+    /// Applies the change without recording it, e.g. to a copy of the entries.
+    pub fn apply(&self, entries: &mut Vec<SshConfigEntry>) {
+        for step in &self.steps {
+            step.apply(entries);
+        }
+    }
+
+    fn revert(&self, entries: &mut Vec<SshConfigEntry>) {
+        for step in self.steps.iter().rev() {
+            step.inverse().apply(entries);
+        }
+    }
+}
+
+/// The changes applied since the config was read or last written, and the ones undone after them
+#[derive(Debug, Default)]
+pub struct History {
+    done: Vec<Change>,
+    undone: Vec<Change>,
+}
+
+impl History {
+    /// Applies the change to the entries, it can be undone afterwards. Changes that were undone
+    /// cannot be redone anymore.
+    pub fn apply(&mut self, entries: &mut Vec<SshConfigEntry>, change: Change) {
+        log::debug!("Applying change: {}", change.description);
+        change.apply(entries);
+        self.done.push(change);
+        self.undone.clear();
+    }
+
+    /// Reverts the last change, returning its description.
+    pub fn undo(&mut self, entries: &mut Vec<SshConfigEntry>) -> Option<String> {
+        let change = self.done.pop()?;
+        change.revert(entries);
+        let description = change.description.clone();
+        self.undone.push(change);
+        Some(description)
+    }
+
+    /// Applies again the last change that was undone, returning its description.
+    pub fn redo(&mut self, entries: &mut Vec<SshConfigEntry>) -> Option<String> {
+        let change = self.undone.pop()?;
+        change.apply(entries);
+        let description = change.description.clone();
+        self.done.push(change);
+        Some(description)
+    }
+
+    /// Number of changes that were not written yet.
+    pub fn pending(&self) -> usize {
+        self.done.len()
+    }
+
+    /// Forgets every change, after the entries were written.
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    /// What writing the entries would change in the files touched by the pending changes, files
+    /// that would be left as they are on disk are left out.
+    pub fn pending_writes(&self, entries: &[SshConfigEntry]) -> Vec<PendingWrite> {
+        let mut sources = Vec::<PathBuf>::new();
+        for entry in self.done.iter().flat_map(|change| &change.steps).flat_map(Step::entries) {
+            if !sources.contains(&entry.source) {
+                sources.push(entry.source.clone());
+            }
+        }
+
+        sources.into_iter()
+            .map(|path| {
+                let file_entries = entries.iter()
+                    .filter(|entry| entry.source == path)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let current = fs::read_to_string(&path).unwrap_or_default();
+                PendingWrite {
+                    contents: layout::render(&current, &file_entries),
+                    current,
+                    path,
+                }
+            })
+            .filter(|write| write.current != write.contents)
+            .collect()
+    }
+}
+
+/// A config file with its contents on disk and the contents it would be written with
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingWrite {
+    pub path: PathBuf,
+    pub current: String,
+    pub contents: String,
+}
+
+/// A line of the diff of a file
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Hunk(String),     // `@@ -1,4 +1,5 @@` header
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+impl PendingWrite {
+    /// The unified diff between the file on disk and what would be written, with 3 lines of context.
+    pub fn diff(&self) -> Vec<DiffLine> {
+        let diff = TextDiff::from_lines(&self.current, &self.contents);
+        let mut lines = Vec::new();
+
+        for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+            lines.push(DiffLine::Hunk(hunk.header().to_string()));
+            for change in hunk.iter_changes() {
+                let text = change.value().trim_end_matches('\n').to_string();
+                lines.push(match change.tag() {
+                    ChangeTag::Equal => DiffLine::Context(text),
+                    ChangeTag::Delete => DiffLine::Removed(text),
+                    ChangeTag::Insert => DiffLine::Added(text),
+                });
+            }
+        }
+        lines
+    }
+
+    /// Replaces the file with the new contents, keeping a `.bak` copy.
+    pub fn write(&self) -> io::Result<()> {
+        config::write_atomic(&self.path, &self.contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# hosts of the team
+Host web
+  HostName 10.0.0.1
+  User deploy
+
+# ---- prod ----
+Host db
+    HostName=10.0.0.2
+    # the admin user
+    User admin
+    Port 5432
+
+Host cache
+    HostName 10.0.0.3
+";
+
+    #[test]
+    fn one_edit_is_one_hunk_of_the_diff() {
+        let dir = std::env::temp_dir().join(format!("ssh-config-history-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        fs::write(&path, CONFIG).unwrap();
+        let mut entries = config::read_ssh_config(&path.display().to_string()).unwrap();
+        let mut history = History::default();
+
+        let mut db = entries[1].clone();
+        db.set_option("Port", "6432");
+        let change = Change::replace("edit db".to_string(), &entries, 1, db);
+        history.apply(&mut entries, change);
+
+        let writes = history.pending_writes(&entries);
+        assert_eq!(writes.len(), 1);
+        let diff = writes[0].diff();
+        assert_eq!(diff.iter().filter(|line| matches!(line, DiffLine::Hunk(_))).count(), 1);
+        let changed = diff.iter()
+            .filter(|line| matches!(line, DiffLine::Removed(_) | DiffLine::Added(_)))
+            .collect::<Vec<_>>();
+        assert_eq!(changed, [&DiffLine::Removed("    Port 5432".to_string()), &DiffLine::Added("    Port 6432".to_string())]);
+
+        // Undone, there is nothing left to write
+        history.undo(&mut entries);
+        assert!(history.pending_writes(&entries).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
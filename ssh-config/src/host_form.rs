@@ -0,0 +1,194 @@
+//! This module has the form of the new host wizard: the Host alias, the usual options and the tag,
+//! pre-filled from one of the templates of the settings
+//!
+//! Each field is checked as it is typed, and the host is added as a `Change` under the section of
+//! its tag, so that it can be undone like any other edit.
+
+use std::path::PathBuf;
+use crate::config;
+use crate::entry::{self, EntryKind, SshConfigEntry};
+use crate::history::Change;
+use crate::settings::Template;
+
+/// A field of the form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormField {
+    Host,
+    HostName,
+    User,
+    Port,
+    IdentityFile,
+    ProxyJump,
+    Tag,
+}
+
+impl FormField {
+    /// Every field, in the order of the form
+    pub const ALL: [FormField; 7] = [
+        FormField::Host,
+        FormField::HostName,
+        FormField::User,
+        FormField::Port,
+        FormField::IdentityFile,
+        FormField::ProxyJump,
+        FormField::Tag,
+    ];
+
+    /// Name of the field, the keyword of the option for the option fields.
+    pub fn label(self) -> &'static str {
+        match self {
+            FormField::Host => "Host",
+            FormField::HostName => "HostName",
+            FormField::User => "User",
+            FormField::Port => "Port",
+            FormField::IdentityFile => "IdentityFile",
+            FormField::ProxyJump => "ProxyJump",
+            FormField::Tag => "Tag",
+        }
+    }
+
+    /// Checks a value of the field. Only the Host is required, an empty option is left out.
+    pub fn validate(self, value: &str) -> Result<(), String> {
+        match self {
+            FormField::Host => entry::validate_host(value),
+            FormField::Tag => entry::validate_tag(value),
+            _ if value.is_empty() => Ok(()),
+            option => entry::validate_option(option.label(), value),
+        }
+    }
+
+    /// The value of the field in a template.
+    fn template_value(self, template: &Template) -> Option<&str> {
+        match self {
+            FormField::Host => template.host.as_deref(),
+            FormField::HostName => template.hostname.as_deref(),
+            FormField::User => template.user.as_deref(),
+            FormField::Port => template.port.as_deref(),
+            FormField::IdentityFile => template.identity_file.as_deref(),
+            FormField::ProxyJump => template.proxy_jump.as_deref(),
+            FormField::Tag => template.tag.as_deref(),
+        }
+    }
+}
+
+/// The values the form starts with: those of the template, and `tag` when the template has no tag.
+///
+/// # Returns
+/// One value per field, in the order of `FormField::ALL`.
+pub fn values(template: Option<&Template>, tag: Option<&str>) -> Vec<String> {
+    FormField::ALL.iter()
+        .map(|&field| {
+            let value = template.and_then(|template| field.template_value(template));
+            match (field, value) {
+                (_, Some(value)) => value.to_string(),
+                (FormField::Tag, None) => tag.unwrap_or_default().to_string(),
+                _ => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Checks every field of the form and builds the host, in `source` until it is placed in the file
+/// of its tag.
+pub fn new_entry(values: &[String], source: PathBuf) -> Result<SshConfigEntry, String> {
+    let mut new_entry = SshConfigEntry::new(EntryKind::Host, "", None, source);
+    for (&field, value) in FormField::ALL.iter().zip(values) {
+        let value = value.trim();
+        field.validate(value)?;
+        match field {
+            FormField::Host => new_entry.host = value.to_string(),
+            FormField::Tag => new_entry.set_tag(value.to_string()),
+            _ if value.is_empty() => {}
+            option => new_entry.add_option(option.label().to_string(), value.to_string()),
+        }
+    }
+    Ok(new_entry)
+}
+
+/// Builds the change that adds the host of the form after the last entry of its tag.
+///
+/// # Returns
+/// The change and the index of the host once it is applied.
+pub fn add_change(entries: &[SshConfigEntry], values: &[String], source: PathBuf) -> Result<(Change, usize), String> {
+    let new_entry = new_entry(values, source)?;
+    if let Some(pattern) = entries.iter().find_map(|other| new_entry.shared_pattern(other)) {
+        return Err(format!("Host '{}' already exists", pattern));
+    }
+
+    // Where the host lands is found by inserting it in a copy of the entries
+    let description = format!("add {}", new_entry.label());
+    let mut updated = entries.to_vec();
+    let index = config::insert_entry(&mut updated, new_entry);
+    Ok((Change::insert(description, index, updated[index].clone()), index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> Template {
+        Template {
+            name: "lab VM".to_string(),
+            host: Some("lab-".to_string()),
+            user: Some("root".to_string()),
+            port: Some("2222".to_string()),
+            tag: Some("lab".to_string()),
+            ..Template::default()
+        }
+    }
+
+    #[test]
+    fn the_template_fills_the_form() {
+        assert_eq!(values(Some(&template()), Some("prod")), ["lab-", "", "root", "2222", "", "", "lab"]);
+        assert_eq!(values(None, Some("prod")), ["", "", "", "", "", "", "prod"]);
+    }
+
+    #[test]
+    fn fields_are_checked_like_the_options() {
+        assert!(FormField::Port.validate("22").is_ok());
+        assert!(FormField::Port.validate("ssh").is_err());
+        assert!(FormField::Port.validate("").is_ok());
+        assert!(FormField::Host.validate("").is_err());
+        assert!(FormField::Tag.validate("a\nb").is_err());
+        assert!(FormField::Tag.validate("web-servers").is_ok());
+
+        let mut form = values(Some(&template()), None);
+        form[3] = "0".to_string();
+        assert_eq!(new_entry(&form, "config".into()).unwrap_err(), "Port cannot be 0");
+    }
+
+    #[test]
+    fn the_host_goes_to_the_section_of_its_tag() {
+        let mut lab = SshConfigEntry::new(EntryKind::Host, "lab-1", Some("lab".to_string()), "lab.conf".into());
+        lab.add_option("User".to_string(), "root".to_string());
+        let entries = vec![
+            lab,
+            SshConfigEntry::new(EntryKind::Host, "web", Some("prod".to_string()), "config".into()),
+        ];
+
+        let mut form = values(Some(&template()), None);
+        form[0] = "lab-2".to_string();
+        form[1] = "10.0.0.2".to_string();
+        let (change, index) = add_change(&entries, &form, "config".into()).unwrap();
+        assert_eq!(index, 1);
+
+        let mut updated = entries.clone();
+        change.apply(&mut updated);
+        let added = &updated[1];
+        assert_eq!(added.host, "lab-2");
+        assert_eq!(added.source, PathBuf::from("lab.conf"));
+        assert_eq!(added.options, [
+            ("HostName".to_string(), "10.0.0.2".to_string()),
+            ("User".to_string(), "root".to_string()),
+            ("Port".to_string(), "2222".to_string()),
+        ]);
+
+        form[0] = "web".to_string();
+        assert_eq!(add_change(&entries, &form, "config".into()).unwrap_err(), "Host 'web' already exists");
+
+        // An alias of a Host line with several patterns is taken as well
+        let entries = vec![SshConfigEntry::with_options(EntryKind::Host, "web db", None, &[])];
+        form[0] = "DB".to_string();
+        assert_eq!(add_change(&entries, &form, "config".into()).unwrap_err(), "Host 'DB' already exists");
+    }
+}
@@ -0,0 +1,230 @@
+//! This module follows the ProxyJump and ProxyCommand options of a host to find every hop the
+//! connection goes through
+//!
+//! `ProxyJump a,b` connects to `a`, from there to `b` and from there to the host. The first hop is
+//! reached with its own configuration, so its own ProxyJump is followed too, while the other hops
+//! are only used as destinations. A `ProxyCommand` like `ssh -W %h:%p bastion` is a jump through
+//! `bastion`; any other command is shown as it is, it cannot be followed.
+
+use crate::entry::{self, EntryKind, JumpHost, SshConfigEntry};
+use crate::resolve;
+
+/// Options of ssh that take an argument, to find the destination of a `ssh -W` proxy command
+const SSH_OPTIONS_WITH_ARGUMENT: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// A step of the connection, from where it starts to the host itself
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hop {
+    Host {
+        name: String,           // Name as written in ProxyJump, or the alias of the host
+        destination: String,    // `user@hostname:port` after resolving the name
+        entry: Option<usize>,   // Host entry that defines the name, if any
+    },
+    Command(String),            // ProxyCommand that could not be followed
+}
+
+/// The hops to a host, with the problems found while following them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JumpChain {
+    pub hops: Vec<Hop>,             // The last one is the host itself
+    pub cycle: Option<Vec<String>>, // Names that lead back to themselves, the first is repeated last
+}
+
+impl JumpChain {
+    /// Whether the connection goes through anything else than the host itself.
+    pub fn has_jumps(&self) -> bool {
+        self.hops.len() > 1 || self.cycle.is_some()
+    }
+
+    /// Names of the hops that no Host entry defines.
+    pub fn unresolved(&self) -> Vec<&str> {
+        self.hops.iter()
+            .filter_map(|hop| match hop {
+                Hop::Host { name, entry: None, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Follows the jumps to reach `alias`, see the module documentation.
+pub fn jump_chain(entries: &[SshConfigEntry], alias: &str) -> JumpChain {
+    let mut chain = JumpChain::default();
+    let mut stack = vec![alias.to_string()];
+    follow(entries, alias, &mut stack, &mut chain);
+
+    let target = JumpHost { user: None, host: alias.to_string(), port: None };
+    chain.hops.push(host_hop(entries, &target));
+    chain
+}
+
+/// Adds the hops needed to reach `name`, before it. `stack` has the names being followed.
+fn follow(entries: &[SshConfigEntry], name: &str, stack: &mut Vec<String>, chain: &mut JumpChain) {
+    let resolution = resolve::resolve(entries, name);
+    let is_set = |key: &str| resolution.get(key).filter(|value| !value.eq_ignore_ascii_case("none"));
+
+    let jumps = match (is_set("ProxyJump"), is_set("ProxyCommand")) {
+        (Some(jumps), _) => entry::parse_jump_specs(jumps),
+        (None, Some(command)) => match proxy_command_host(command) {
+            Some(host) => vec![host],
+            None => {
+                chain.hops.push(Hop::Command(command.to_string()));
+                return;
+            }
+        },
+        (None, None) => return,
+    };
+
+    for (i, jump) in jumps.iter().enumerate() {
+        if let Some(start) = stack.iter().position(|other| other.eq_ignore_ascii_case(&jump.host)) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(jump.host.clone());
+            chain.cycle = Some(cycle);
+            return;
+        }
+
+        // Only the first hop is reached with its own jumps
+        if i == 0 {
+            stack.push(jump.host.clone());
+            follow(entries, &jump.host, stack, chain);
+            stack.pop();
+            if chain.cycle.is_some() {
+                return;
+            }
+        }
+        chain.hops.push(host_hop(entries, jump));
+    }
+}
+
+/// Resolves a hop to where ssh connects, and finds the Host entry that defines it.
+fn host_hop(entries: &[SshConfigEntry], jump: &JumpHost) -> Hop {
+    let resolution = resolve::resolve(entries, &jump.host);
+    let user = jump.user.as_deref().or(resolution.get("User")).unwrap_or_default();
+    let hostname = resolution.get("HostName").unwrap_or(&jump.host);
+    let port = jump.port.map(|port| port.to_string())
+        .unwrap_or_else(|| resolution.get("Port").unwrap_or("22").to_string());
+
+    // The catch-all `Host *` applies to everything, it does not define a host
+    let entry = entries.iter().position(|entry| {
+        entry.kind == EntryKind::Host && entry.patterns() != ["*"] && entry.matches_host(&jump.host)
+    });
+
+    Hop::Host {
+        name: jump.host.clone(),
+        destination: if user.is_empty() {
+            format!("{}:{}", hostname, port)
+        } else {
+            format!("{}@{}:{}", user, hostname, port)
+        },
+        entry,
+    }
+}
+
+/// The host a `ssh ... -W %h:%p host` proxy command connects through, if the command is one.
+fn proxy_command_host(command: &str) -> Option<JumpHost> {
+    let mut words = command.split_whitespace();
+    let program = words.next()?;
+    if program.rsplit('/').next() != Some("ssh") {
+        return None;
+    }
+
+    let mut destination = None;
+    let mut user = None;
+    let mut port = None;
+    let mut forwards = false;
+    while let Some(word) = words.next() {
+        let Some(flags) = word.strip_prefix('-').filter(|_| destination.is_none()) else {
+            destination = destination.or(Some(word));
+            continue;
+        };
+
+        // In a group like `-qW` the first flag that takes an argument ends the group, the argument
+        // is the rest of the word (`-W%h:%p`) or the next word
+        for (i, flag) in flags.char_indices() {
+            if !SSH_OPTIONS_WITH_ARGUMENT.contains(flag) {
+                continue;
+            }
+            let attached = &flags[i + flag.len_utf8()..];
+            let argument = if attached.is_empty() { words.next()? } else { attached };
+            match flag {
+                'W' => forwards = true,
+                'l' => user = Some(argument.to_string()),
+                'p' => port = argument.parse().ok(),
+                _ => {}
+            }
+            break;
+        }
+    }
+    if !forwards {
+        return None;
+    }
+
+    let mut jump = entry::parse_jump_specs(destination?).into_iter().next()?;
+    jump.user = jump.user.or(user);
+    jump.port = jump.port.or(port);
+    Some(jump)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn destinations(chain: &JumpChain) -> Vec<&str> {
+        chain.hops.iter()
+            .map(|hop| match hop {
+                Hop::Host { destination, .. } => destination.as_str(),
+                Hop::Command(command) => command.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn proxy_jump_chains_follow_the_first_hop_only() {
+        let entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "app", None, &[("ProxyJump", "gate,admin@inner:2200"), ("User", "deploy")]),
+            SshConfigEntry::with_options(EntryKind::Host, "gate", None, &[("HostName", "gate.example.com"), ("ProxyJump", "edge")]),
+            SshConfigEntry::with_options(EntryKind::Host, "inner", None, &[("ProxyJump", "nowhere")]),
+        ];
+
+        let chain = jump_chain(&entries, "app");
+        assert_eq!(destinations(&chain), ["edge:22", "gate.example.com:22", "admin@inner:2200", "deploy@app:22"]);
+        assert_eq!(chain.unresolved(), ["edge"]);
+        assert!(chain.has_jumps());
+        assert!(!jump_chain(&entries, "edge").has_jumps());
+    }
+
+    #[test]
+    fn ssh_proxy_commands_are_followed_in_any_form() {
+        let commands = [
+            ("ssh -W %h:%p bastion", "bastion", None, None),
+            ("ssh -qW %h:%p bastion", "bastion", None, None),
+            ("/usr/bin/ssh -q -W%h:%p -p 2222 bastion", "bastion", None, Some(2222)),
+            ("ssh -l admin -o ServerAliveInterval=5 -W %h:%p bastion.example.com nc", "bastion.example.com", Some("admin"), None),
+            ("ssh -Aq4W [%h]:%p ops@bastion:2200", "bastion", Some("ops"), Some(2200)),
+        ];
+        for (command, name, user, port) in commands {
+            let jump = proxy_command_host(command).unwrap_or_else(|| panic!("{} is not followed", command));
+            assert_eq!((jump.host.as_str(), jump.user.as_deref(), jump.port), (name, user, port), "{}", command);
+        }
+
+        assert_eq!(proxy_command_host("ssh bastion nc %h %p"), None);
+        assert_eq!(proxy_command_host("nc -X 5 -x proxy:1080 %h %p"), None);
+
+        let entries = vec![SshConfigEntry::with_options(EntryKind::Host, "app", None, &[("ProxyCommand", "nc -x proxy:1080 %h %p")])];
+        assert_eq!(destinations(&jump_chain(&entries, "app")), ["nc -x proxy:1080 %h %p", "app:22"]);
+    }
+
+    #[test]
+    fn cycles_are_reported_instead_of_followed() {
+        let entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "a", None, &[("ProxyJump", "b")]),
+            SshConfigEntry::with_options(EntryKind::Host, "b", None, &[("ProxyCommand", "ssh -W %h:%p c")]),
+            SshConfigEntry::with_options(EntryKind::Host, "c", None, &[("ProxyJump", "a")]),
+        ];
+
+        let chain = jump_chain(&entries, "a");
+        assert_eq!(chain.cycle, Some(vec!["a".to_string(), "b".to_string(), "c".to_string(), "a".to_string()]));
+        assert!(chain.has_jumps());
+        assert_eq!(jump_chain(&[SshConfigEntry::with_options(EntryKind::Host, "self", None, &[("ProxyJump", "self")])], "self").cycle, Some(vec!["self".to_string(), "self".to_string()]));
+    }
+}
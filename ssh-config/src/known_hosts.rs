@@ -0,0 +1,322 @@
+//! This module reads `~/.ssh/known_hosts` and links its lines to the entries of the config
+//!
+//! A line is `[marker] hosts keytype key [comment]`. The hosts are a comma separated list of
+//! names, `[name]:port` for ports other than 22 and patterns, or a single `|1|salt|hash` when
+//! `HashKnownHosts` is on, the hash being the HMAC-SHA1 of the name keyed with the salt.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::config;
+use crate::entry::{self, EntryKind, SshConfigEntry};
+use crate::resolve;
+
+/// Marker in front of a known_hosts line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Marker {
+    CertAuthority,  // The key signs the host certificates of the hosts
+    Revoked,        // The key must never be accepted
+}
+
+/// The host part of a known_hosts line
+#[derive(Debug, Clone, PartialEq)]
+pub enum KnownHostNames {
+    Patterns(Vec<String>),
+    Hashed { salt: Vec<u8>, hash: Vec<u8> },
+}
+
+/// A line of known_hosts with a host key
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownHost {
+    pub line: usize,            // Line number, starting at 1
+    pub marker: Option<Marker>,
+    pub names: KnownHostNames,
+    pub key_type: String,
+    pub key: String,            // Base64 of the key blob
+    pub comment: String,
+}
+
+impl KnownHost {
+    /// The host names as written in the file, hashed lines show as `(hashed)`.
+    pub fn names_text(&self) -> String {
+        match &self.names {
+            KnownHostNames::Patterns(patterns) => patterns.join(","),
+            KnownHostNames::Hashed { .. } => "(hashed)".to_string(),
+        }
+    }
+
+    /// The names of a line that are neither patterns nor negated, with their port.
+    pub fn plain_names(&self) -> Vec<(&str, u16)> {
+        let KnownHostNames::Patterns(patterns) = &self.names else {
+            return Vec::new();
+        };
+        patterns.iter()
+            .filter(|pattern| !pattern.starts_with('!') && !has_wildcard(pattern))
+            .map(|pattern| {
+                pattern.strip_prefix('[')
+                    .and_then(|rest| rest.split_once("]:"))
+                    .and_then(|(host, port)| Some((host, port.parse().ok()?)))
+                    .unwrap_or((pattern.as_str(), 22))
+            })
+            .collect()
+    }
+
+    /// Whether this line is for `host` on `port`, the way ssh looks it up.
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        let name = if port == 22 { host.to_string() } else { format!("[{}]:{}", host, port) };
+
+        match &self.names {
+            KnownHostNames::Patterns(patterns) => {
+                let patterns = patterns.iter().map(String::as_str).collect::<Vec<_>>();
+                entry::match_pattern_list(&patterns, &name)
+            }
+            KnownHostNames::Hashed { salt, hash } => {
+                let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(salt) else {
+                    return false;
+                };
+                mac.update(name.as_bytes());
+                mac.verify_slice(hash).is_ok()
+            }
+        }
+    }
+}
+
+/// Parses the contents of a known_hosts file, comments and lines that cannot be read are skipped.
+pub fn parse_known_hosts(contents: &str) -> Vec<KnownHost> {
+    contents.lines()
+        .enumerate()
+        .filter_map(|(i, line)| parse_line(i + 1, line))
+        .collect()
+}
+
+fn parse_line(number: usize, line: &str) -> Option<KnownHost> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace().peekable();
+    let marker = match *fields.peek()? {
+        "@cert-authority" => Some(Marker::CertAuthority),
+        "@revoked" => Some(Marker::Revoked),
+        other if other.starts_with('@') => return None,
+        _ => None,
+    };
+    if marker.is_some() {
+        fields.next();
+    }
+
+    let hosts = fields.next()?;
+    let names = match hosts.strip_prefix("|1|") {
+        Some(hashed) => {
+            let (salt, hash) = hashed.split_once('|')?;
+            KnownHostNames::Hashed {
+                salt: STANDARD.decode(salt).ok()?,
+                hash: STANDARD.decode(hash).ok()?,
+            }
+        }
+        None => KnownHostNames::Patterns(hosts.split(',').map(str::to_string).collect()),
+    };
+
+    Some(KnownHost {
+        line: number,
+        marker,
+        names,
+        key_type: fields.next()?.to_string(),
+        key: fields.next()?.to_string(),
+        comment: fields.collect::<Vec<_>>().join(" "),
+    })
+}
+
+/// Reads a known_hosts file, a missing file has no lines.
+pub fn read_known_hosts(path: &Path) -> io::Result<Vec<KnownHost>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(parse_known_hosts(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// What a known_hosts line is to the config
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Link {
+    pub entries: Vec<usize>,    // Host entries ssh checks against the line
+    pub stale: bool,            // The line names a host of the config that ssh looks up elsewhere now
+}
+
+/// Finds, for each known_hosts line, the Host entries ssh would check against it.
+///
+/// Every alias of a Host entry is resolved to its effective HostName, Port and HostKeyAlias, like
+/// ssh does before looking up the host key. The names of plain lines are also resolved, which
+/// links them to the wildcard entries that apply to them.
+///
+/// A line is stale when it is for an alias of the config that ssh does not look up under that
+/// name or port anymore, e.g. after HostName or Port changed. Lines of hosts the config does not
+/// know are left alone, as are lines with a marker, which apply to many hosts.
+///
+/// # Returns
+/// The link of each line, in the order of `known`.
+pub fn link_entries(known: &[KnownHost], entries: &[SshConfigEntry]) -> Vec<Link> {
+    // (entry, alias, name ssh looks up, port)
+    let targets = entries.iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.kind == EntryKind::Host)
+        .flat_map(|(index, entry)| {
+            entry.patterns()
+                .into_iter()
+                .filter(|pattern| !pattern.starts_with('!') && !has_wildcard(pattern))
+                .map(|alias| {
+                    let (name, port) = lookup(entries, alias);
+                    (index, alias, name, port)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    known.iter()
+        .map(|host| {
+            let mut linked = targets.iter()
+                .filter(|(_, _, name, port)| host.matches(name, *port))
+                .map(|(index, _, _, _)| *index)
+                .collect::<Vec<_>>();
+
+            // Names matched by wildcard entries, when ssh looks them up as they are
+            for (name, port) in host.plain_names() {
+                if lookup(entries, name) != (name.to_string(), port) {
+                    continue;
+                }
+                linked.extend(entries.iter()
+                    .enumerate()
+                    .filter(|(_, entry)| entry.patterns().iter().any(|pattern| has_wildcard(pattern)) && entry.matches_host(name))
+                    .map(|(index, _)| index));
+            }
+            linked.sort_unstable();
+            linked.dedup();
+
+            let stale = host.marker.is_none()
+                && linked.is_empty()
+                && targets.iter().any(|(_, alias, _, port)| host.matches(alias, 22) || host.matches(alias, *port));
+            Link { entries: linked, stale }
+        })
+        .collect()
+}
+
+/// The name and port ssh looks the host key of `alias` up with.
+fn lookup(entries: &[SshConfigEntry], alias: &str) -> (String, u16) {
+    let resolution = resolve::resolve(entries, alias);
+    let name = resolution.get("HostKeyAlias")
+        .or_else(|| resolution.get("HostName"))
+        .unwrap_or(alias)
+        .to_string();
+    let port = resolution.get("Port").and_then(|port| port.parse().ok()).unwrap_or(22);
+    (name, port)
+}
+
+fn has_wildcard(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+/// Removes lines from a known_hosts file, keeping a `.bak` copy. The file is left untouched when
+/// one of the lines changed since it was read.
+pub fn remove_lines(path: &Path, hosts: &[&KnownHost]) -> io::Result<()> {
+    let contents = fs::read_to_string(path)?;
+    let lines = contents.lines().collect::<Vec<_>>();
+
+    for host in hosts {
+        let unchanged = lines.get(host.line - 1).is_some_and(|line| line.contains(&host.key));
+        if !unchanged {
+            return Err(io::Error::other(format!("{} changed since it was read, reload it", path.display())));
+        }
+    }
+
+    let kept = lines.iter()
+        .enumerate()
+        .filter(|(i, _)| !hosts.iter().any(|host| host.line == i + 1))
+        .map(|(_, line)| format!("{}\n", line))
+        .collect::<String>();
+    config::write_atomic(path, &kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `|1|salt|hash` field for `name`, as ssh-keygen -H writes it.
+    fn hashed(name: &str) -> String {
+        let salt = b"0123456789abcdefghij";
+        let mut mac = Hmac::<Sha1>::new_from_slice(salt).unwrap();
+        mac.update(name.as_bytes());
+        format!("|1|{}|{}", STANDARD.encode(salt), STANDARD.encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn lines_are_parsed_with_their_marker_names_and_ports() {
+        let known = parse_known_hosts(&format!(
+            "# comment\n\
+             web,10.0.0.5 ssh-ed25519 AAAA1 me@laptop\n\
+             [git.example.com]:2222 ssh-rsa AAAA2\n\
+             @cert-authority *.example.com ssh-ed25519 AAAA3 ca\n\
+             @revoked old ssh-ed25519 AAAA4\n\
+             @unknown web ssh-ed25519 AAAA5\n\
+             {} ecdsa-sha2-nistp256 AAAA6\n\
+             broken-line\n",
+            hashed("[db]:2200"),
+        ));
+
+        assert_eq!(known.iter().map(|host| host.line).collect::<Vec<_>>(), [2, 3, 4, 5, 7]);
+        assert_eq!(known[0].names, KnownHostNames::Patterns(vec!["web".to_string(), "10.0.0.5".to_string()]));
+        assert_eq!(known[0].comment, "me@laptop");
+        assert_eq!(known[1].plain_names(), [("git.example.com", 2222)]);
+        assert!(known[1].matches("git.example.com", 2222) && !known[1].matches("git.example.com", 22));
+        assert_eq!(known[2].marker, Some(Marker::CertAuthority));
+        assert!(known[2].matches("a.example.com", 22));
+        assert_eq!(known[3].marker, Some(Marker::Revoked));
+
+        assert_eq!(known[4].names_text(), "(hashed)");
+        assert!(known[4].matches("db", 2200));
+        assert!(!known[4].matches("db", 22));
+    }
+
+    #[test]
+    fn lines_are_linked_to_aliases_and_wildcard_entries() {
+        let entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("HostName", "10.0.0.5")]),
+            SshConfigEntry::with_options(EntryKind::Host, "db", None, &[("Port", "2200")]),
+            SshConfigEntry::with_options(EntryKind::Host, "*.lab", None, &[]),
+        ];
+        let known = parse_known_hosts(&format!(
+            "10.0.0.5 ssh-ed25519 AAAA1\n\
+             {} ssh-ed25519 AAAA2\n\
+             box.lab ssh-ed25519 AAAA3\n\
+             github.com ssh-ed25519 AAAA4\n",
+            hashed("[db]:2200"),
+        ));
+
+        let links = link_entries(&known, &entries);
+        assert_eq!(links.iter().map(|link| link.entries.clone()).collect::<Vec<_>>(), [vec![0], vec![1], vec![2], vec![]]);
+        // A host that is not in the config is not stale
+        assert!(links.iter().all(|link| !link.stale));
+    }
+
+    #[test]
+    fn lines_that_ssh_no_longer_looks_up_are_stale() {
+        let entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("HostName", "10.0.0.9")]),
+            SshConfigEntry::with_options(EntryKind::Host, "db", None, &[("Port", "2200")]),
+        ];
+        let known = parse_known_hosts(&format!(
+            "web ssh-ed25519 AAAA1\n\
+             10.0.0.5 ssh-ed25519 AAAA2\n\
+             {} ssh-ed25519 AAAA3\n\
+             @revoked web ssh-ed25519 AAAA4\n",
+            hashed("db"),
+        ));
+
+        let stale = link_entries(&known, &entries).iter().map(|link| link.stale).collect::<Vec<_>>();
+        assert_eq!(stale, [true, false, true, false]);
+    }
+}
@@ -0,0 +1,360 @@
+//! This module splits a config file into the lines of each entry, so that the file is written back
+//! with the lines of the entries as they were
+//!
+//! A block has the lines of an entry: its Host or Match line, options, comments and blank lines up
+//! to the next block. The comments right above a Host line belong to that Host, as does a tag
+//! marker with the lines that follow it. The global options form the first block, from the top of
+//! the file. Writing the entries keeps the block of each entry that did not change, and keeps the
+//! lines of an entry that changed, with their indentation and `=`, apart from its changed options.
+
+use regex::Regex;
+use crate::entry::{EntryKind, SshConfigEntry};
+
+/// What a line of a config file is
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Section(EntryKind, String),  // `Host` or `Match` line with its patterns or criteria
+    Option(String, String),      // `Key value` or `Key=value`
+    Tag(String),                 // `# ---- Tag ----` marker
+    Comment(String),
+    Blank,
+    Other,                       // Not understood by the parser, kept as it is
+}
+
+/// Parses the lines of a config file
+pub struct Parser {
+    section: Regex,
+    option: Regex,
+    tag: Regex,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self {
+            section: Regex::new(r"(?i)^\s*(Host|Match)(?:\s*=\s*|\s+)(.+?)\s*$").unwrap(),
+            option: Regex::new(r"^(\s*)([^\s=]+)(\s*=\s*|\s+)(.+?)\s*$").unwrap(),
+            tag: Regex::new(r"^\s*# -+ (.+?) -+\s*$").unwrap(),  // Specific tag format
+        }
+    }
+
+    pub fn parse(&self, line: &str) -> Line {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            Line::Blank
+        } else if let Some(caps) = self.tag.captures(line) {
+            Line::Tag(caps[1].trim().to_string())
+        } else if trimmed.starts_with('#') {
+            Line::Comment(trimmed.to_string())
+        } else if let Some(caps) = self.section.captures(line) {
+            let kind = if caps[1].eq_ignore_ascii_case("match") { EntryKind::Match } else { EntryKind::Host };
+            Line::Section(kind, caps[2].to_string())
+        } else if let Some(caps) = self.option.captures(line) {
+            Line::Option(caps[2].to_string(), caps[4].to_string())
+        } else {
+            Line::Other
+        }
+    }
+
+    /// An option line with another keyword and value, keeping its indentation and separator.
+    fn replace_option(&self, line: &str, key: &str, value: &str) -> String {
+        match self.option.captures(line) {
+            Some(caps) => format!("{}{}{}{}", &caps[1], key, &caps[3], value),
+            None => format!("{} {}", key, value),
+        }
+    }
+}
+
+/// The lines of an entry in a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub kind: EntryKind,
+    pub host: String,
+    pub line: usize,          // Number of the Host or Match line, or of the first global option
+    pub tag: Option<String>,
+    pub start: usize,         // Index of the first line of the block
+    pub end: usize,           // Index after its last line
+}
+
+/// The blocks of the lines of a file, in order. The lines before the first block belong to no
+/// entry, e.g. a comment at the top of a file without global options.
+pub fn blocks(lines: &[Line]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let first = lines.iter().position(|line| matches!(line, Line::Section(..))).unwrap_or(lines.len());
+    if let Some(option) = lines[..first].iter().position(|line| matches!(line, Line::Option(..))) {
+        blocks.push(Block { kind: EntryKind::Global, host: String::new(), line: option + 1, tag: None, start: 0, end: first });
+    }
+
+    let mut tag = None;
+    for (index, line) in lines.iter().enumerate() {
+        match line {
+            Line::Tag(name) => tag = Some(name.clone()),
+            Line::Section(kind, host) => {
+                let start = leading_start(lines, index);
+                if let Some(previous) = blocks.last_mut() {
+                    previous.end = start;
+                }
+                blocks.push(Block { kind: *kind, host: host.clone(), line: index + 1, tag: tag.clone(), start, end: lines.len() });
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// Where the block of the section at `section` starts: at the first tag marker after the options
+/// above, or else at the comments right above the section line.
+fn leading_start(lines: &[Line], section: usize) -> usize {
+    let gap = lines[..section].iter()
+        .rposition(|line| matches!(line, Line::Section(..) | Line::Option(..) | Line::Other))
+        .map_or(0, |i| i + 1);
+    if let Some(marker) = lines[gap..section].iter().position(|line| matches!(line, Line::Tag(_))) {
+        return gap + marker;
+    }
+    section - lines[gap..section].iter().rev().take_while(|line| matches!(line, Line::Comment(_))).count()
+}
+
+/// Writes the entries of a file over its `original` contents.
+///
+/// The entries are matched with the blocks they were read from by their Host or Match line. The
+/// block of an unchanged entry is copied, and in the block of a changed entry only the changed
+/// options and the tag marker are written again. Entries without a block are written at their
+/// place like a new file would have them: the tag marker, the comments, the section line and the
+/// options.
+pub fn render(original: &str, entries: &[SshConfigEntry]) -> String {
+    let parser = Parser::new();
+    let raw = original.lines().collect::<Vec<_>>();
+    let lines = raw.iter().map(|line| parser.parse(line)).collect::<Vec<_>>();
+    let blocks = blocks(&lines);
+
+    let mut writer = Writer { parser: &parser, lines: Vec::new(), tag: None };
+    // The lines of no entry, e.g. a comment at the top of the file, stay where they are
+    let preamble = blocks.first().map_or(raw.len(), |block| block.start);
+    for line in &raw[..preamble] {
+        writer.push(line.to_string());
+    }
+
+    let mut used = vec![false; blocks.len()];
+    let mut found = entries.iter()
+        .map(|entry| {
+            let found = (0..blocks.len())
+                .filter(|&i| !used[i] && blocks[i].kind == entry.kind && blocks[i].host == entry.host)
+                .min_by_key(|&i| blocks[i].line != entry.line);
+            if let Some(i) = found {
+                used[i] = true;
+            }
+            found
+        })
+        .collect::<Vec<_>>();
+    // A renamed entry keeps the block at its line, when no other entry took it
+    for (entry, found) in entries.iter().zip(found.iter_mut()).filter(|(entry, found)| found.is_none() && entry.line != 0) {
+        *found = (0..blocks.len()).find(|&i| !used[i] && blocks[i].kind == entry.kind && blocks[i].line == entry.line);
+        if let Some(i) = *found {
+            used[i] = true;
+        }
+    }
+
+    let mut previous: Option<usize> = None;
+    for (entry, found) in entries.iter().zip(found) {
+        match found {
+            Some(i) => {
+                // Blocks that were not next to each other are separated like new ones
+                let follows = previous.map_or(i == 0, |previous| previous + 1 == i);
+                if !follows && lines[blocks[i].start] != Line::Blank {
+                    writer.separate();
+                }
+                let block = &blocks[i];
+                writer.block(entry, &raw[block.start..block.end], &lines[block.start..block.end]);
+                previous = Some(i);
+            }
+            None => {
+                writer.separate();
+                writer.entry(entry);
+                previous = None;
+            }
+        }
+    }
+
+    // A block moved to the end brings the blank lines that separated it from the next one
+    let blank_lines = lines.iter().rev().take_while(|line| **line == Line::Blank).count();
+    let written = writer.lines.iter().rev().take_while(|line| line.trim().is_empty()).count();
+    writer.lines.truncate(writer.lines.len() - written.saturating_sub(blank_lines));
+
+    let mut output = writer.lines.join("\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+/// The lines written so far and the tag they leave in effect
+struct Writer<'a> {
+    parser: &'a Parser,
+    lines: Vec<String>,
+    tag: Option<String>,
+}
+
+impl Writer<'_> {
+    fn push(&mut self, line: String) {
+        if let Line::Tag(tag) = self.parser.parse(&line) {
+            self.tag = Some(tag);
+        }
+        self.lines.push(line);
+    }
+
+    /// A blank line between two entries, unless there is one already.
+    fn separate(&mut self) {
+        if self.lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    /// The marker of the tag of an entry, when it is not the tag in effect already.
+    fn marker(&self, entry: &SshConfigEntry) -> Option<String> {
+        match &entry.tag {
+            Some(tag) if entry.kind != EntryKind::Global && self.tag.as_ref() != Some(tag) => Some(format!("# ---- {} ----", tag)),
+            _ => None,
+        }
+    }
+
+    /// Writes an entry that has no block, as in a new file.
+    fn entry(&mut self, entry: &SshConfigEntry) {
+        if let Some(marker) = self.marker(entry) {
+            self.push(marker);
+        }
+        for comment in &entry.comments {
+            self.push(comment.clone());
+        }
+        let indent = match entry.kind {
+            EntryKind::Global => "",
+            EntryKind::Host | EntryKind::Match => {
+                self.push(format!("{} {}", entry.kind.keyword(), entry.host));
+                "    "
+            }
+        };
+        for (key, value) in &entry.options {
+            self.push(format!("{}{} {}", indent, key, value));
+        }
+    }
+
+    /// Writes an entry over the lines of its block.
+    fn block(&mut self, entry: &SshConfigEntry, raw: &[&str], lines: &[Line]) {
+        let section = lines.iter().position(|line| matches!(line, Line::Section(..))).unwrap_or(0);
+        let markers = (0..section).filter(|&i| matches!(lines[i], Line::Tag(_))).collect::<Vec<_>>();
+        let tag_in_effect = match markers.last().map(|&i| &lines[i]) {
+            Some(Line::Tag(tag)) => Some(tag.clone()),
+            _ => self.tag.clone(),
+        };
+        // The markers above the entry are written again when its tag changed
+        let retag = entry.kind != EntryKind::Global && tag_in_effect != entry.tag;
+        let marker = if retag { self.marker(entry) } else { None };
+
+        let indent = raw.iter().zip(lines)
+            .find(|(_, line)| matches!(line, Line::Option(..)))
+            .map(|(raw, _)| raw[..raw.len() - raw.trim_start().len()].to_string())
+            .unwrap_or_else(|| if entry.kind == EntryKind::Global { String::new() } else { "    ".to_string() });
+        let new_option = |(key, value): &(String, String)| format!("{}{} {}", indent, key, value);
+
+        let mut comments = entry.comments.iter().collect::<Vec<_>>();
+        let mut options = &entry.options[..];
+        let mut output = Vec::new();
+        let mut after_options = None;
+        if markers.is_empty() {
+            output.extend(marker.clone());
+        }
+
+        for (i, (raw, line)) in raw.iter().zip(lines).enumerate() {
+            match line {
+                Line::Tag(_) if retag && i < section => {
+                    if Some(&i) == markers.first() {
+                        output.extend(marker.clone());
+                    }
+                }
+                // A comment that is no longer in the entry is left out
+                Line::Comment(text) => {
+                    if let Some(position) = comments.iter().position(|comment| *comment == text) {
+                        comments.remove(position);
+                        output.push(raw.to_string());
+                    }
+                }
+                Line::Option(key, value) => {
+                    let same = |(k, v): &(String, String)| k == key && v == value;
+                    match options.iter().position(same) {
+                        // Unchanged, after the options that were added above it
+                        Some(found) => {
+                            output.extend(options[..found].iter().map(new_option));
+                            output.push(raw.to_string());
+                            options = &options[found + 1..];
+                        }
+                        // Changed value
+                        None if options.first().is_some_and(|(k, _)| k.eq_ignore_ascii_case(key)) => {
+                            output.push(self.parser.replace_option(raw, &options[0].0, &options[0].1));
+                            options = &options[1..];
+                        }
+                        // Removed
+                        None => {}
+                    }
+                    after_options = Some(output.len());
+                }
+                Line::Section(_, host) => {
+                    if *host == entry.host {
+                        output.push(raw.to_string());
+                    } else {
+                        let indent = &raw[..raw.len() - raw.trim_start().len()];
+                        output.push(format!("{}{} {}", indent, entry.kind.keyword(), entry.host));
+                    }
+                    after_options = Some(output.len());
+                }
+                _ => output.push(raw.to_string()),
+            }
+        }
+
+        // Added options go after the last option, before the blank lines and comments that follow
+        let added = options.iter().map(new_option).chain(comments.iter().map(|comment| format!("{}{}", indent, comment)));
+        let at = after_options.unwrap_or(output.len());
+        output.splice(at..at, added.collect::<Vec<_>>());
+        for line in output {
+            self.push(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<Line> {
+        let parser = Parser::new();
+        text.lines().map(|line| parser.parse(line)).collect()
+    }
+
+    #[test]
+    fn lines_are_parsed_with_either_separator() {
+        assert_eq!(parse("Port=2222\n  User = deploy\nHost=web db\n# note\n# ---- prod ----\n\nHost\n# -- web-servers --\n"), [
+            Line::Option("Port".to_string(), "2222".to_string()),
+            Line::Option("User".to_string(), "deploy".to_string()),
+            Line::Section(EntryKind::Host, "web db".to_string()),
+            Line::Comment("# note".to_string()),
+            Line::Tag("prod".to_string()),
+            Line::Blank,
+            Line::Other,
+            Line::Tag("web-servers".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn comments_above_a_host_belong_to_it() {
+        let text = "# header\nServerAliveInterval 60\n\nHost a\n    User x\n    # about a\n\n# ---- prod ----\n# about b\nHost b\n";
+        let ranges = blocks(&parse(text)).into_iter()
+            .map(|block| (block.host, block.tag, block.start, block.end))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, [
+            (String::new(), None, 0, 3),
+            ("a".to_string(), None, 3, 7),
+            ("b".to_string(), Some("prod".to_string()), 7, 10),
+        ]);
+
+        // Without global options the top of the file is no entry's
+        let ranges = blocks(&parse("# header\n\n# about a\nHost a\n")).into_iter().map(|block| block.start).collect::<Vec<_>>();
+        assert_eq!(ranges, [2]);
+    }
+}
@@ -0,0 +1,304 @@
+//! This module checks the parsed config entries for mistakes and insecure settings
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::entry::{self, EntryKind, SshConfigEntry, SshOption};
+
+/// Client keywords documented in ssh_config(5), in lower case
+const KNOWN_KEYWORDS: &[&str] = &[
+    "addkeystoagent", "addressfamily", "batchmode", "bindaddress", "bindinterface",
+    "canonicaldomains", "canonicalizefallbacklocal", "canonicalizehostname", "canonicalizemaxdots",
+    "canonicalizepermittedcnames", "casignaturealgorithms", "certificatefile", "channeltimeout",
+    "challengeresponseauthentication", "checkhostip", "ciphers", "clearallforwardings",
+    "compression", "connectionattempts", "connecttimeout", "controlmaster", "controlpath",
+    "controlpersist", "dynamicforward", "enableescapecommandline", "enablesshkeysign", "escapechar",
+    "exitonforwardfailure", "fingerprinthash", "forkafterauthentication", "forwardagent",
+    "forwardx11", "forwardx11timeout", "forwardx11trusted", "gatewayports", "globalknownhostsfile",
+    "gssapiauthentication", "gssapidelegatecredentials", "hashknownhosts",
+    "hostbasedacceptedalgorithms", "hostbasedauthentication", "hostkeyalgorithms", "hostkeyalias",
+    "hostname", "identitiesonly", "identityagent", "identityfile", "ignoreunknown", "include",
+    "ipqos", "kbdinteractiveauthentication", "kbdinteractivedevices", "kexalgorithms",
+    "knownhostscommand", "localcommand", "localforward", "loglevel", "logverbose", "macs",
+    "nohostauthenticationforlocalhost", "numberofpasswordprompts", "obscurekeystroketiming",
+    "passwordauthentication", "permitlocalcommand", "permitremoteopen", "pkcs11provider", "port",
+    "preferredauthentications", "proxycommand", "proxyjump", "proxyusefdpass",
+    "pubkeyacceptedalgorithms", "pubkeyacceptedkeytypes", "pubkeyauthentication", "rekeylimit",
+    "remotecommand", "remoteforward", "requesttty", "requiredrsasize", "revokedhostkeys",
+    "securitykeyprovider", "sendenv", "serveralivecountmax", "serveraliveinterval", "sessiontype",
+    "setenv", "stdinnull", "streamlocalbindmask", "streamlocalbindunlink", "stricthostkeychecking",
+    "syslogfacility", "tag", "tcpkeepalive", "tunnel", "tunneldevice", "updatehostkeys",
+    "usekeychain", "user", "userknownhostsfile", "verifyhostkeydns", "visualhostkey",
+    "xauthlocation",
+];
+
+/// How bad a problem found by the linter is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in the config, located by file and line of the option or entry it is about
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub source: PathBuf,
+    pub line: usize,
+    pub entry: usize,       // Index of the entry in the checked list
+    pub message: String,
+}
+
+/// Formats the diagnostic like compilers do: `file:line: severity: message`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}: {}", self.source.display(), self.line, self.severity, self.message)
+    }
+}
+
+/// Checks the entries for problems.
+///
+/// # Arguments
+/// * `entries` - The entries as returned by `read_ssh_config`.
+///
+/// # Returns
+/// The diagnostics sorted by file and line.
+pub fn lint(entries: &[SshConfigEntry]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let ignored = ignored_keywords(entries);
+
+    for (index, entry) in entries.iter().enumerate() {
+        let mut report = |line: usize, severity: Severity, message: String| {
+            diagnostics.push(Diagnostic {
+                severity,
+                source: entry.source.clone(),
+                line,
+                entry: index,
+                message,
+            });
+        };
+
+        check_duplicates(entries, index, &mut report);
+        check_shadowed(entries, index, &mut report);
+
+        for (option, (key, value)) in entry.options.iter().enumerate() {
+            let line = entry.option_line(option);
+            check_option(entries, key, value, &ignored, &mut |severity, message| report(line, severity, message));
+        }
+    }
+
+    diagnostics.sort_by(|a, b| (&a.source, a.line).cmp(&(&b.source, b.line)));
+    diagnostics
+}
+
+/// Whether any of the diagnostics is an error, used for the exit code of `ssh-config lint`.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+/// Patterns of the `IgnoreUnknown` options, the keywords they match are not reported as unknown.
+fn ignored_keywords(entries: &[SshConfigEntry]) -> Vec<String> {
+    entries.iter()
+        .flat_map(|entry| entry.options.iter())
+        .filter(|(key, _)| key.eq_ignore_ascii_case("ignoreunknown"))
+        .flat_map(|(_, value)| value.split(',').map(|pattern| pattern.trim().to_string()))
+        .collect()
+}
+
+fn has_wildcard(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+/// The patterns of the entry that name a single host.
+fn aliases(entry: &SshConfigEntry) -> Vec<&str> {
+    entry.patterns()
+        .into_iter()
+        .filter(|pattern| !pattern.starts_with('!') && !has_wildcard(pattern))
+        .collect()
+}
+
+/// Reports aliases that were already defined by an earlier Host entry.
+fn check_duplicates(entries: &[SshConfigEntry], index: usize, report: &mut impl FnMut(usize, Severity, String)) {
+    for alias in aliases(&entries[index]) {
+        let earlier = entries[..index].iter()
+            .find(|other| aliases(other).iter().any(|other_alias| other_alias.eq_ignore_ascii_case(alias)));
+        if let Some(other) = earlier {
+            report(entries[index].line, Severity::Warning, format!(
+                "Host '{}' is already defined at {}:{}",
+                alias, other.source.display(), other.line,
+            ));
+        }
+    }
+}
+
+/// Reports options of a host that are ignored because an earlier wildcard Host, or the global
+/// options, already set them. ssh uses the first value it finds for each option.
+fn check_shadowed(entries: &[SshConfigEntry], index: usize, report: &mut impl FnMut(usize, Severity, String)) {
+    let entry = &entries[index];
+
+    for alias in aliases(entry) {
+        for other in &entries[..index] {
+            let applies = match other.kind {
+                // Options of an included file are only global when the Include line is
+                EntryKind::Global => !matches!(&other.included_by, Some(by) if by.kind != EntryKind::Global),
+                EntryKind::Host => other.patterns().iter().any(|pattern| has_wildcard(pattern)) && other.matches_host(alias),
+                EntryKind::Match => false,
+            };
+            if !applies {
+                continue;
+            }
+
+            for (option, (key, _)) in entry.options.iter().enumerate() {
+                if entry::is_multi_valued(key) {
+                    continue;
+                }
+                if other.options.iter().any(|(other_key, _)| other_key.eq_ignore_ascii_case(key)) {
+                    report(entry.option_line(option), Severity::Warning, format!(
+                        "{} of Host '{}' is ignored, it is already set by '{}' at {}:{}",
+                        key, alias, other.label(), other.source.display(), other.line,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Checks a single option: its keyword, its value and settings that weaken security.
+fn check_option(
+    entries: &[SshConfigEntry],
+    key: &str,
+    value: &str,
+    ignored: &[String],
+    report: &mut impl FnMut(Severity, String),
+) {
+    let lower_key = key.to_lowercase();
+
+    if !KNOWN_KEYWORDS.contains(&lower_key.as_str()) {
+        if !ignored.iter().any(|pattern| entry::match_pattern(pattern, key)) {
+            report(Severity::Warning, format!("Unknown keyword '{}'", key));
+        }
+        return;
+    }
+
+    let option = match SshOption::parse(key, value) {
+        Ok(option) => option,
+        Err(e) => {
+            report(Severity::Error, e);
+            return;
+        }
+    };
+
+    match option {
+        // Paths with ssh tokens like %d or %h cannot be checked without ssh itself
+        SshOption::IdentityFile(path) if !path.contains('%') && !path.eq_ignore_ascii_case("none") => {
+            let expanded = shellexpand::tilde(&path).into_owned();
+            if !Path::new(&expanded).exists() {
+                report(Severity::Warning, format!("IdentityFile '{}' does not exist", path));
+            }
+        }
+        SshOption::ProxyJump(jumps) => {
+            for hop in entry::parse_jump_hosts(&jumps) {
+                let defined = entries.iter()
+                    .any(|entry| entry.patterns() != ["*"] && entry.matches_host(&hop));
+                if !defined {
+                    report(Severity::Warning, format!("ProxyJump host '{}' is not defined by any Host", hop));
+                }
+            }
+        }
+        SshOption::StrictHostKeyChecking(entry::SshStrictHostKeyChecking::No) => {
+            report(Severity::Warning, "StrictHostKeyChecking no accepts any host key, including the one of an attacker".to_string());
+        }
+        SshOption::UserKnownHostsFile(path) if path.split_whitespace().any(|file| file == "/dev/null") => {
+            report(Severity::Warning, "UserKnownHostsFile /dev/null never remembers host keys".to_string());
+        }
+        SshOption::ForwardX11(true) => {
+            report(Severity::Info, "ForwardX11 yes lets the remote host access your X display".to_string());
+        }
+        SshOption::Other(ref key, ref value) if key.eq_ignore_ascii_case("forwardagent") && value.eq_ignore_ascii_case("yes") => {
+            report(Severity::Info, "ForwardAgent yes lets the remote host use your keys while you are connected".to_string());
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: EntryKind, host: &str, line: usize, options: &[(&str, &str)]) -> SshConfigEntry {
+        let mut entry = SshConfigEntry::with_options(kind, host, None, options);
+        entry.line = line;
+        // The options are on the lines after the Host line, like in a file
+        for (i, (key, value)) in options.iter().enumerate() {
+            entry.option_lines.push((key.to_string(), value.to_string(), line + 1 + i));
+        }
+        entry
+    }
+
+    fn messages(entries: &[SshConfigEntry]) -> Vec<String> {
+        lint(entries).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn duplicate_and_shadowed_hosts_are_reported() {
+        let entries = vec![
+            entry(EntryKind::Global, "", 1, &[("User", "admin")]),
+            entry(EntryKind::Host, "web-*", 3, &[("Port", "2222")]),
+            entry(EntryKind::Host, "web-1 db", 6, &[("Port", "22"), ("IdentityFile", "none"), ("User", "me")]),
+            entry(EntryKind::Host, "DB", 10, &[]),
+        ];
+
+        assert_eq!(messages(&entries), [
+            "config:7: warning: Port of Host 'web-1' is ignored, it is already set by 'web-*' at config:3",
+            "config:9: warning: User of Host 'web-1' is ignored, it is already set by '(global options)' at config:1",
+            "config:9: warning: User of Host 'db' is ignored, it is already set by '(global options)' at config:1",
+            "config:10: warning: Host 'DB' is already defined at config:6",
+        ]);
+    }
+
+    #[test]
+    fn keywords_and_values_are_checked() {
+        let entries = vec![
+            entry(EntryKind::Global, "", 1, &[("IgnoreUnknown", "UseKeychain,X*")]),
+            entry(EntryKind::Host, "web", 3, &[
+                ("Prot", "22"),
+                ("UseKeychain", "yes"),
+                ("XAuthLocationX", "/bin/xauth"),
+                ("Port", "ssh"),
+                ("ProxyJump", "bastion"),
+            ]),
+        ];
+
+        let diagnostics = lint(&entries);
+        assert_eq!(diagnostics.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+            "config:4: warning: Unknown keyword 'Prot'",
+            "config:7: error: Invalid Port 'ssh'",
+            "config:8: warning: ProxyJump host 'bastion' is not defined by any Host",
+        ]);
+        assert!(has_errors(&diagnostics));
+    }
+
+    #[test]
+    fn insecure_settings_are_reported() {
+        let entries = vec![entry(EntryKind::Host, "lab", 1, &[
+            ("StrictHostKeyChecking", "off"),
+            ("UserKnownHostsFile", "/dev/null"),
+            ("ForwardX11", "yes"),
+            ("ForwardAgent", "yes"),
+        ])];
+
+        let severities = lint(&entries).iter().map(|diagnostic| diagnostic.severity).collect::<Vec<_>>();
+        assert_eq!(severities, [Severity::Warning, Severity::Warning, Severity::Info, Severity::Info]);
+        assert!(!has_errors(&lint(&entries)));
+    }
+}
@@ -1,6 +1,7 @@
 // src/list_utils.rs
 
 use ratatui::widgets::ListState;
+use std::collections::BTreeSet;
 
 #[derive(Debug)]
 pub struct ListStateManager {
@@ -8,6 +9,7 @@ pub struct ListStateManager {
     selected_index: usize,
     pub max_display_items: usize,
     state: ListState,
+    marked: BTreeSet<usize>,  // Indices of the marked entries, for the bulk actions
 }
 
 impl ListStateManager {
@@ -18,6 +20,7 @@ impl ListStateManager {
             selected_index: 0,
             max_display_items: 0,
             state: ListState::default(),
+            marked: BTreeSet::new(),
         }
     }
 
@@ -30,10 +33,23 @@ impl ListStateManager {
         if index < self.scroll_offset {
             self.scroll_offset = index;
         } else if index >= self.scroll_offset + self.max_display_items {
-            self.scroll_offset = index - self.max_display_items;
+            self.scroll_offset = (index + 1).saturating_sub(self.max_display_items);
         }
     }
 
+    /// Scrolls the list to `offset`, e.g. to keep it where it was after the items were replaced.
+    /// A selection outside of the visible rows scrolls it again on the next `select`.
+    pub fn set_offset(&mut self, offset: usize) {
+        *self.state.offset_mut() = offset;
+        self.scroll_offset = offset;
+    }
+
+    /// Takes the scroll offset the list was drawn with, which ratatui adjusts when the height of
+    /// the list or the number of items changed since the last `select`.
+    pub fn sync_offset(&mut self) {
+        self.scroll_offset = self.state.offset();
+    }
+
     /// Retrieves the currently selected index
     pub fn get_index(&self) -> usize {
         self.selected_index
@@ -43,4 +59,26 @@ impl ListStateManager {
     pub fn list_state(&mut self) -> &mut ListState {
         &mut self.state
     }
+
+    /// Marks the entry at `index`, or unmarks it when it is marked.
+    pub fn toggle_mark(&mut self, index: usize) {
+        if !self.marked.remove(&index) {
+            self.marked.insert(index);
+        }
+    }
+
+    /// Marks every given entry, those already marked stay marked.
+    pub fn mark_all(&mut self, indices: impl IntoIterator<Item = usize>) {
+        self.marked.extend(indices);
+    }
+
+    /// Unmarks everything, e.g. after the entries moved and the indices changed.
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    /// Indices of the marked entries, in the order of the entries.
+    pub fn marked(&self) -> &BTreeSet<usize> {
+        &self.marked
+    }
 }
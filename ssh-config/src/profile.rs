@@ -0,0 +1,151 @@
+//! This module has the config files that can be opened, the profiles, and compares two of them
+//!
+//! The profiles are `~/.ssh/config`, the files named in the `[profiles]` table of the settings and
+//! the file given with `-F`. Two configs are compared by their entries, an entry being the same in
+//! both when it has the same `Host` or `Match` line, whichever file it was included from.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::config;
+use crate::entry::SshConfigEntry;
+use crate::settings::Profile;
+
+/// The profiles to pick from: `~/.ssh/config` first, then those of the settings and `opened` when
+/// it is none of them. A file is only listed once, with its first name.
+pub fn profiles(configured: &[Profile], opened: &Path) -> Vec<Profile> {
+    let mut profiles = vec![Profile { name: "default".to_string(), path: config::default_path() }];
+    let opened = Profile { name: "opened".to_string(), path: opened.to_path_buf() };
+    for profile in configured.iter().chain(std::iter::once(&opened)) {
+        if !profiles.iter().any(|other| other.path == profile.path) {
+            profiles.push(profile.clone());
+        }
+    }
+    profiles
+}
+
+/// The file of the profile called `name`, or `name` itself when no profile has that name.
+pub fn find(profiles: &[Profile], name: &str) -> PathBuf {
+    profiles.iter()
+        .find(|profile| profile.name == name)
+        .map_or_else(|| PathBuf::from(shellexpand::tilde(name).into_owned()), |profile| profile.path.clone())
+}
+
+/// An entry that is not the same in two configs
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryDiff {
+    OnlyLeft(String),                   // Label of an entry of the first config only
+    OnlyRight(String),                  // Label of an entry of the second config only
+    Changed(String, Vec<OptionDiff>),   // Entry of both configs with different options
+}
+
+/// An option with different values in two configs, empty on the side that does not have it
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionDiff {
+    pub key: String,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+
+impl fmt::Display for OptionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let values = |values: &[String]| if values.is_empty() { "(none)".to_string() } else { values.join(", ") };
+        write!(f, "{}: {} → {}", self.key, values(&self.left), values(&self.right))
+    }
+}
+
+/// Compares the entries of two configs.
+///
+/// # Returns
+/// The entries that differ: those of `left` in its order, followed by the entries only `right` has.
+pub fn compare(left: &[SshConfigEntry], right: &[SshConfigEntry]) -> Vec<EntryDiff> {
+    let mut diffs = Vec::new();
+    let mut paired = vec![false; right.len()];
+
+    for entry in left {
+        // Entries with the same line are paired in order, e.g. the global options of each file
+        let pair = right.iter()
+            .enumerate()
+            .position(|(i, other)| !paired[i] && other.kind == entry.kind && other.host == entry.host);
+        match pair {
+            Some(i) => {
+                paired[i] = true;
+                let options = compare_options(entry, &right[i]);
+                if !options.is_empty() {
+                    diffs.push(EntryDiff::Changed(entry.label(), options));
+                }
+            }
+            None => diffs.push(EntryDiff::OnlyLeft(entry.label())),
+        }
+    }
+
+    diffs.extend(right.iter()
+        .zip(paired)
+        .filter(|(_, paired)| !paired)
+        .map(|(entry, _)| EntryDiff::OnlyRight(entry.label())));
+    diffs
+}
+
+/// The options of two entries whose values differ, keywords being case-insensitive.
+fn compare_options(left: &SshConfigEntry, right: &SshConfigEntry) -> Vec<OptionDiff> {
+    let mut keys: Vec<&str> = Vec::new();
+    for (key, _) in left.options.iter().chain(&right.options) {
+        if !keys.iter().any(|other| other.eq_ignore_ascii_case(key)) {
+            keys.push(key);
+        }
+    }
+
+    let values = |entry: &SshConfigEntry, key: &str| entry.options.iter()
+        .filter(|(other, _)| other.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.trim().to_string())
+        .collect::<Vec<_>>();
+    keys.into_iter()
+        .map(|key| OptionDiff { key: key.to_string(), left: values(left, key), right: values(right, key) })
+        .filter(|diff| diff.left != diff.right)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::EntryKind;
+
+    #[test]
+    fn hosts_missing_on_a_side_and_different_options_are_found() {
+        let left = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("User", "deploy"), ("Port", "22")]),
+            SshConfigEntry::with_options(EntryKind::Host, "db", None, &[]),
+            SshConfigEntry::with_options(EntryKind::Host, "cache", None, &[("IdentityFile", "~/.ssh/a")]),
+        ];
+        let right = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "bastion", None, &[]),
+            SshConfigEntry::with_options(EntryKind::Host, "cache", None, &[("identityfile", "~/.ssh/a")]),
+            SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("Port", "2222"), ("User", "deploy"), ("ProxyJump", "bastion")]),
+        ];
+
+        assert_eq!(compare(&left, &right), [
+            EntryDiff::Changed("web".to_string(), vec![
+                OptionDiff { key: "Port".to_string(), left: vec!["22".to_string()], right: vec!["2222".to_string()] },
+                OptionDiff { key: "ProxyJump".to_string(), left: vec![], right: vec!["bastion".to_string()] },
+            ]),
+            EntryDiff::OnlyLeft("db".to_string()),
+            EntryDiff::OnlyRight("bastion".to_string()),
+        ]);
+        assert!(compare(&left, &left).is_empty());
+
+        let EntryDiff::Changed(_, options) = &compare(&left, &right)[0] else { unreachable!() };
+        assert_eq!(options[1].to_string(), "ProxyJump: (none) → bastion");
+    }
+
+    #[test]
+    fn the_opened_file_is_listed_once() {
+        let team = vec![Profile { name: "team".to_string(), path: "/srv/team/config".into() }];
+        let names = |profiles: Vec<Profile>| profiles.into_iter().map(|profile| profile.name).collect::<Vec<_>>();
+
+        assert_eq!(names(profiles(&team, &config::default_path())), ["default", "team"]);
+        assert_eq!(names(profiles(&team, &team[0].path)), ["default", "team"]);
+        assert_eq!(names(profiles(&team, Path::new("project.conf"))), ["default", "team", "opened"]);
+
+        assert_eq!(find(&team, "team"), team[0].path);
+        assert_eq!(find(&team, "other.conf"), PathBuf::from("other.conf"));
+    }
+}
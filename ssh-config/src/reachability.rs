@@ -0,0 +1,254 @@
+//! This module checks whether the hosts are reachable, in a worker thread of its own
+//!
+//! A check is a TCP connect to the resolved HostName and Port with a timeout, followed by reading
+//! the identification line every SSH server sends first, e.g. `SSH-2.0-OpenSSH_9.6`.
+
+use std::io::{BufRead, BufReader};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::connect;
+use crate::entry::{EntryKind, SshConfigEntry};
+use crate::resolve;
+
+/// Hosts checked at the same time
+const MAX_PARALLEL: usize = 16;
+
+/// Lines a server may send before its identification line, RFC 4253 allows some
+const MAX_BANNER_LINES: usize = 16;
+
+/// Result of checking a host
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Checking,
+    Up {
+        latency: Duration,          // Time the TCP connect took
+        version: Option<String>,    // Software of the server, from its `SSH-2.0-` line
+    },
+    Down(String),
+    Skipped(String),                // Not checked, e.g. hosts reached through a jump host
+}
+
+impl Status {
+    /// Short text for the status column of the host list.
+    pub fn summary(&self) -> String {
+        match self {
+            Status::Checking => "checking…".to_string(),
+            Status::Up { latency, version: Some(version) } => format!("up {} ms {}", latency.as_millis(), version),
+            Status::Up { latency, version: None } => format!("up {} ms (no SSH banner)", latency.as_millis()),
+            Status::Down(reason) => format!("down: {}", reason),
+            Status::Skipped(reason) => reason.clone(),
+        }
+    }
+}
+
+/// A host to check, `key` is what the result is reported under
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub key: String,
+    pub host: String,
+    pub port: u16,
+}
+
+/// The targets to check for the entries: the resolved HostName and Port of each Host entry that can
+/// be connected to. Hosts behind a ProxyJump or ProxyCommand cannot be reached directly, they are
+/// returned apart with the reason.
+pub fn targets(entries: &[SshConfigEntry]) -> (Vec<Target>, Vec<(String, Status)>) {
+    let mut targets = Vec::new();
+    let mut skipped = Vec::new();
+
+    for entry in entries.iter().filter(|entry| entry.kind == EntryKind::Host) {
+        let Some(alias) = connect::connect_alias(entry) else { continue };
+        let resolution = resolve::resolve(entries, alias);
+
+        let via = ["ProxyJump", "ProxyCommand"].iter()
+            .find(|key| resolution.get(key).is_some_and(|value| !value.eq_ignore_ascii_case("none")));
+        if let Some(via) = via {
+            skipped.push((entry.host.clone(), Status::Skipped(format!("via {}", via))));
+            continue;
+        }
+
+        targets.push(Target {
+            key: entry.host.clone(),
+            host: resolution.get("HostName").unwrap_or(alias).to_string(),
+            port: resolution.get("Port").and_then(|port| port.parse().ok()).unwrap_or(22),
+        });
+    }
+
+    (targets, skipped)
+}
+
+/// Connects to `host`:`port` and reads the SSH identification line of the server.
+///
+/// # Arguments
+/// * `timeout` - Limit for the connect, and again for reading the identification line.
+pub fn check(host: &str, port: u16, timeout: Duration) -> Status {
+    let addresses = match (host, port).to_socket_addrs() {
+        Ok(addresses) => addresses.collect::<Vec<_>>(),
+        Err(e) => return Status::Down(format!("cannot resolve {}: {}", host, e)),
+    };
+
+    let mut last_error = format!("{} has no address", host);
+    for address in addresses {
+        let start = Instant::now();
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => {
+                let latency = start.elapsed();
+                return Status::Up { latency, version: read_version(stream, timeout) };
+            }
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+    Status::Down(last_error)
+}
+
+/// Reads lines until the `SSH-2.0-` (or `SSH-1.99-`) one and returns the software version in it.
+fn read_version(stream: TcpStream, timeout: Duration) -> Option<String> {
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    let mut reader = BufReader::new(stream);
+
+    for _ in 0..MAX_BANNER_LINES {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if let Some(rest) = line.strip_prefix("SSH-2.0-").or_else(|| line.strip_prefix("SSH-1.99-")) {
+            // The software version may be followed by a space and comments
+            return Some(rest.split(' ').next().unwrap_or(rest).to_string());
+        }
+    }
+    None
+}
+
+/// Worker thread that checks the targets it is given and reports each result as soon as it is known
+pub struct Checker {
+    requests: mpsc::Sender<Vec<Target>>,
+}
+
+impl Checker {
+    /// Starts the worker, the results are sent to `results` as `(key, status)`.
+    pub fn spawn<T>(timeout: Duration, results: mpsc::Sender<T>) -> Self
+    where
+        T: From<(String, Status)> + Send + 'static,
+    {
+        let (requests, request_rx) = mpsc::channel::<Vec<Target>>();
+
+        thread::spawn(move || {
+            for targets in request_rx {
+                for chunk in targets.chunks(MAX_PARALLEL) {
+                    thread::scope(|scope| {
+                        for target in chunk {
+                            let results = &results;
+                            scope.spawn(move || {
+                                let status = check(&target.host, target.port, timeout);
+                                log::debug!("{} ({}:{}): {:?}", target.key, target.host, target.port, status);
+                                let _ = results.send(T::from((target.key.clone(), status)));
+                            });
+                        }
+                    });
+                }
+            }
+        });
+
+        Self { requests }
+    }
+
+    /// Queues the targets, they are checked after the ones queued before.
+    pub fn request(&self, targets: Vec<Target>) {
+        if self.requests.send(targets).is_err() {
+            log::error!("The reachability worker is gone");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// A server on a free local port that accepts one connection and sends `banner`.
+    fn server(banner: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.write_all(banner.as_bytes());
+                thread::sleep(TIMEOUT * 2);
+            }
+        });
+        port
+    }
+
+    /// A local port nothing listens on.
+    fn closed_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+    }
+
+    #[test]
+    fn reads_the_server_version() {
+        let port = server("SSH-2.0-OpenSSH_9.6 Ubuntu-3\r\n");
+        match check("127.0.0.1", port, TIMEOUT) {
+            Status::Up { version, .. } => assert_eq!(version.as_deref(), Some("OpenSSH_9.6")),
+            status => panic!("unexpected {:?}", status),
+        }
+    }
+
+    #[test]
+    fn skips_lines_before_the_identification() {
+        let port = server("Welcome\r\nSSH-2.0-dropbear_2022.83\r\n");
+        match check("127.0.0.1", port, TIMEOUT) {
+            Status::Up { version, .. } => assert_eq!(version.as_deref(), Some("dropbear_2022.83")),
+            status => panic!("unexpected {:?}", status),
+        }
+    }
+
+    #[test]
+    fn up_without_banner_when_the_server_is_not_ssh() {
+        let port = server("");
+        assert!(matches!(check("127.0.0.1", port, TIMEOUT), Status::Up { version: None, .. }));
+    }
+
+    #[test]
+    fn down_when_nothing_listens() {
+        assert!(matches!(check("127.0.0.1", closed_port(), TIMEOUT), Status::Down(_)));
+    }
+
+    #[test]
+    fn worker_reports_every_target() {
+        let up = server("SSH-2.0-Test\r\n");
+        let (results_tx, results_rx) = mpsc::channel::<(String, Status)>();
+        let checker = Checker::spawn(TIMEOUT, results_tx);
+        checker.request(vec![
+            Target { key: "up".to_string(), host: "127.0.0.1".to_string(), port: up },
+            Target { key: "down".to_string(), host: "127.0.0.1".to_string(), port: closed_port() },
+        ]);
+
+        let mut results = Vec::new();
+        while results.len() < 2 {
+            match results_rx.recv_timeout(Duration::from_secs(5)) {
+                Ok(result) => results.push(result),
+                Err(_) => break,
+            }
+        }
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[0], (ref key, Status::Down(_)) if key == "down"));
+        assert!(matches!(results[1], (ref key, Status::Up { ref version, .. }) if key == "up" && version.as_deref() == Some("Test")));
+    }
+
+    #[test]
+    fn hosts_behind_a_jump_host_are_skipped() {
+        let direct = SshConfigEntry::with_options(EntryKind::Host, "direct", None, &[("HostName", "10.0.0.1"), ("Port", "2222")]);
+        let inner = SshConfigEntry::with_options(EntryKind::Host, "inner", None, &[("ProxyJump", "direct")]);
+
+        let (targets, skipped) = targets(&[direct, inner]);
+        assert_eq!(targets, vec![Target { key: "direct".to_string(), host: "10.0.0.1".to_string(), port: 2222 }]);
+        assert_eq!(skipped, vec![("inner".to_string(), Status::Skipped("via ProxyJump".to_string()))]);
+    }
+}
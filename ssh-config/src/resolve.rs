@@ -0,0 +1,253 @@
+//! This module computes the effective configuration of a host, like `ssh -G` does
+
+use crate::entry::{self, EntryKind, SshConfigEntry};
+
+/// Where the value of an effective option comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptionSource {
+    Entry(usize),   // Index of the entry that set it
+    Default,        // Built-in default of ssh
+}
+
+/// An option of the effective configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedOption {
+    pub key: String,
+    pub value: String,
+    pub source: OptionSource,
+}
+
+/// The effective configuration of a host
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resolution {
+    pub options: Vec<ResolvedOption>,
+    pub notes: Vec<String>,     // Things that could not be evaluated, like `Match exec`
+}
+
+impl Resolution {
+    /// The effective value of an option, keywords are case-insensitive.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options.iter()
+            .find(|option| option.key.eq_ignore_ascii_case(key))
+            .map(|option| option.value.as_str())
+    }
+}
+
+/// Computes the options ssh would use to connect to `hostname`.
+///
+/// The entries are applied in order. Global options always apply, Host entries apply when their
+/// patterns match (`!pattern` excludes) and Match blocks when their criteria hold. For each option
+/// the first value found wins, except for options like `IdentityFile` that add up. `HostName`,
+/// `User` and `Port` fall back to the ssh defaults when no entry sets them.
+///
+/// Entries of included files are applied where their Include line is, and only when the block with
+/// that line applies, as ssh does.
+///
+/// # Arguments
+/// * `entries` - The entries as returned by `read_ssh_config`.
+/// * `hostname` - The host name given to ssh, e.g. the alias of a Host entry.
+pub fn resolve(entries: &[SshConfigEntry], hostname: &str) -> Resolution {
+    let mut resolution = Resolution::default();
+
+    for index in 0..entries.len() {
+        if entries[index].included_by.is_none() {
+            apply(entries, index, true, hostname, &mut resolution);
+        }
+    }
+
+    let defaults = [
+        ("HostName", hostname.to_string()),
+        ("User", local_user()),
+        ("Port", "22".to_string()),
+    ];
+    for (key, value) in defaults {
+        if resolution.get(key).is_none() {
+            resolution.options.push(ResolvedOption {
+                key: key.to_string(),
+                value,
+                source: OptionSource::Default,
+            });
+        }
+    }
+
+    resolution
+}
+
+/// Applies the options of an entry, and in place of its Include lines the entries they read.
+///
+/// `active` is whether the block holding the Include line of the entry applies. Nothing of an
+/// included file applies under a block that does not, not even its own Host entries.
+fn apply(entries: &[SshConfigEntry], index: usize, active: bool, hostname: &str, resolution: &mut Resolution) {
+    let entry = &entries[index];
+    let applies = active && match entry.kind {
+        EntryKind::Global => true,
+        EntryKind::Host => entry.matches_host(hostname),
+        EntryKind::Match => match_criteria(entry, hostname, resolution),
+    };
+
+    for (key, value) in &entry.options {
+        if key.eq_ignore_ascii_case("include") {
+            for child in 0..entries.len() {
+                if entries[child].included_by.as_ref().is_some_and(|by| by.is_from(entry, value)) {
+                    apply(entries, child, applies, hostname, resolution);
+                }
+            }
+            continue;
+        }
+        if !applies {
+            continue;
+        }
+        if !entry::is_multi_valued(key) && resolution.get(key).is_some() {
+            continue;
+        }
+
+        let value = if key.eq_ignore_ascii_case("hostname") {
+            expand_hostname(value, hostname)
+        } else {
+            value.clone()
+        };
+        resolution.options.push(ResolvedOption {
+            key: key.clone(),
+            value,
+            source: OptionSource::Entry(index),
+        });
+    }
+}
+
+/// Replaces the `%h` and `%%` tokens ssh accepts in `HostName`.
+fn expand_hostname(value: &str, hostname: &str) -> String {
+    value.replace("%%", "\u{0}").replace("%h", hostname).replace('\u{0}', "%")
+}
+
+fn local_user() -> String {
+    std::env::var("USER").unwrap_or_default()
+}
+
+/// Splits the criteria of a Match line into words, keeping double quoted arguments together.
+fn split_criteria(criteria: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+
+    for c in criteria.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Evaluates the criteria of a Match entry for `hostname`.
+///
+/// `all`, `host`, `originalhost`, `user` and `localuser` are evaluated. Criteria that need ssh
+/// itself, like `exec` or `canonical`, are taken as not matching and noted in the resolution.
+fn match_criteria(entry: &SshConfigEntry, hostname: &str, resolution: &mut Resolution) -> bool {
+    let words = split_criteria(&entry.host);
+    let mut words = words.iter();
+
+    while let Some(word) = words.next() {
+        let (negated, criterion) = match word.strip_prefix('!') {
+            Some(criterion) => (true, criterion.to_lowercase()),
+            None => (false, word.to_lowercase()),
+        };
+
+        let matched = match criterion.as_str() {
+            "all" => true,
+            "host" | "originalhost" | "user" | "localuser" => {
+                let patterns = words.next().map(String::as_str).unwrap_or_default();
+                let patterns = patterns.split(',').collect::<Vec<_>>();
+                let subject = match criterion.as_str() {
+                    // `host` is checked against the HostName when one was set before the Match
+                    "host" => resolution.get("HostName").map_or(hostname.to_string(), str::to_string),
+                    "originalhost" => hostname.to_string(),
+                    "user" => resolution.get("User").map_or_else(local_user, str::to_string),
+                    _ => local_user(),
+                };
+                entry::match_pattern_list(&patterns, &subject)
+            }
+            _ => {
+                resolution.notes.push(format!(
+                    "Match {} at {}:{} was skipped, '{}' cannot be evaluated here",
+                    entry.host, entry.source.display(), entry.line, criterion,
+                ));
+                return false;
+            }
+        };
+
+        if matched == negated {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(resolution: &Resolution, key: &str) -> Vec<(String, OptionSource)> {
+        resolution.options.iter()
+            .filter(|option| option.key.eq_ignore_ascii_case(key))
+            .map(|option| (option.value.clone(), option.source))
+            .collect()
+    }
+
+    #[test]
+    fn the_first_value_wins_across_host_match_and_global() {
+        let entries = vec![
+            SshConfigEntry::with_options(EntryKind::Global, "", None, &[("Port", "2200"), ("IdentityFile", "~/.ssh/global")]),
+            SshConfigEntry::with_options(EntryKind::Match, "host web", None, &[("User", "matched"), ("Port", "2201")]),
+            SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("user", "deploy"), ("HostName", "%h.example.com"), ("IdentityFile", "~/.ssh/web")]),
+            SshConfigEntry::with_options(EntryKind::Host, "*", None, &[("User", "fallback"), ("HostName", "other")]),
+        ];
+
+        let web = resolve(&entries, "web");
+        assert_eq!(sources(&web, "Port"), [("2200".to_string(), OptionSource::Entry(0))]);
+        assert_eq!(sources(&web, "User"), [("matched".to_string(), OptionSource::Entry(1))]);
+        assert_eq!(sources(&web, "HostName"), [("web.example.com".to_string(), OptionSource::Entry(2))]);
+        // IdentityFile adds up instead
+        assert_eq!(sources(&web, "IdentityFile"), [
+            ("~/.ssh/global".to_string(), OptionSource::Entry(0)),
+            ("~/.ssh/web".to_string(), OptionSource::Entry(2)),
+        ]);
+
+        let db = resolve(&entries, "db");
+        assert_eq!(db.get("user"), Some("fallback"));
+        assert_eq!(db.get("HostName"), Some("other"));
+    }
+
+    #[test]
+    fn unset_options_fall_back_to_the_ssh_defaults() {
+        let resolution = resolve(&[SshConfigEntry::with_options(EntryKind::Host, "!db *", None, &[("Port", "2222")])], "db");
+        assert_eq!(sources(&resolution, "HostName"), [("db".to_string(), OptionSource::Default)]);
+        assert_eq!(sources(&resolution, "Port"), [("22".to_string(), OptionSource::Default)]);
+        assert_eq!(resolution.get("User"), Some(local_user().as_str()));
+        assert_eq!(expand_hostname("%h.%%h", "web"), "web.%h");
+    }
+
+    #[test]
+    fn match_criteria_are_evaluated_or_noted() {
+        let entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "web", None, &[("HostName", "web.internal")]),
+            SshConfigEntry::with_options(EntryKind::Match, "host \"*.internal,db\" !originalhost db", None, &[("Port", "2201")]),
+            SshConfigEntry::with_options(EntryKind::Match, "exec \"test -f /etc/lab\"", None, &[("Port", "2202")]),
+            SshConfigEntry::with_options(EntryKind::Match, "all", None, &[("Port", "2203")]),
+        ];
+        assert_eq!(split_criteria("exec \"test -f x\" host a"), ["exec", "test -f x", "host", "a"]);
+
+        let web = resolve(&entries, "web");
+        assert_eq!(web.get("Port"), Some("2201"));
+        assert_eq!(web.notes, ["Match exec \"test -f /etc/lab\" at config:0 was skipped, 'exec' cannot be evaluated here"]);
+        assert_eq!(resolve(&entries, "db").get("Port"), Some("2203"));
+    }
+}
@@ -0,0 +1,273 @@
+//! This module has the fuzzy search of the host list, in the spirit of fzf
+//!
+//! A query is made of terms separated by spaces and every term has to match. A term is matched
+//! against the host, HostName, User and tag of an entry, or only against one of them when it is
+//! scoped like `user:deploy` or `tag:prod`. The characters of a term have to appear in order but
+//! not next to each other, so `wb1` finds `web-1`.
+
+use crate::entry::SshConfigEntry;
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_BOUNDARY: i64 = 10;     // First character, or right after `-`, `.`, `_`, ...
+const BONUS_FIRST_CHAR: i64 = 4;    // Extra for matching at the very start of the text
+const PENALTY_GAP_START: i64 = 8;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+/// The parts of an entry a term can match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Host,
+    HostName,
+    User,
+    Tag,
+}
+
+impl Field {
+    pub const ALL: [Field; 4] = [Field::Host, Field::HostName, Field::User, Field::Tag];
+
+    /// Name used to scope a term, e.g. `user` in `user:deploy`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Host => "host",
+            Field::HostName => "hostname",
+            Field::User => "user",
+            Field::Tag => "tag",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Field> {
+        Field::ALL.iter().copied().find(|field| field.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The text of this field in an entry, the label is used as host so that Match blocks and the
+    /// global options can be found too.
+    pub fn value(&self, entry: &SshConfigEntry) -> Option<String> {
+        match self {
+            Field::Host => Some(entry.label()),
+            Field::HostName => entry.get_option("HostName").map(str::to_string),
+            Field::User => entry.get_option("User").map(str::to_string),
+            Field::Tag => entry.tag.clone(),
+        }
+    }
+}
+
+/// A term of a query, optionally scoped to a field
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    field: Option<Field>,
+    pattern: String,
+}
+
+fn parse_query(query: &str) -> Vec<Term> {
+    query.split_whitespace()
+        .map(|word| match word.split_once(':') {
+            Some((name, pattern)) => match Field::from_name(name) {
+                Some(field) => Term { field: Some(field), pattern: pattern.to_string() },
+                None => Term { field: None, pattern: word.to_string() },
+            },
+            None => Term { field: None, pattern: word.to_string() },
+        })
+        .filter(|term| !term.pattern.is_empty())
+        .collect()
+}
+
+/// Result of matching a pattern against a text
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    pub positions: Vec<usize>,  // Indices of the matched characters (not bytes) of the text
+}
+
+fn is_boundary(previous: Option<char>) -> bool {
+    !matches!(previous, Some(c) if c.is_alphanumeric())
+}
+
+/// Matches `pattern` against `text`, looking for its characters in order.
+///
+/// The search is case-insensitive unless the pattern has an upper case letter, like the smart case
+/// of fzf. Among all the ways the pattern fits in the text the one with the best score is taken:
+/// every character counts, characters next to each other or at the start of a word count more
+/// and gaps between them count less.
+///
+/// # Returns
+/// `None` when the characters of the pattern do not all appear in the text.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let normalize = |c: char| if case_sensitive { c } else { c.to_ascii_lowercase() };
+
+    let pattern = pattern.chars().map(normalize).collect::<Vec<_>>();
+    let original = text.chars().collect::<Vec<_>>();
+    let text = original.iter().copied().map(normalize).collect::<Vec<_>>();
+    if pattern.is_empty() || pattern.len() > text.len() {
+        return if pattern.is_empty() { Some(FuzzyMatch { score: 0, positions: Vec::new() }) } else { None };
+    }
+
+    // best[i][j]: score of the best match of pattern[..=i] with pattern[i] at text[j]
+    // from[i][j]: where pattern[i - 1] was matched in that case
+    let mut best = vec![vec![None::<i64>; text.len()]; pattern.len()];
+    let mut from = vec![vec![0usize; text.len()]; pattern.len()];
+
+    for (i, &p) in pattern.iter().enumerate() {
+        for j in i..text.len() {
+            if text[j] != p {
+                continue;
+            }
+
+            let mut bonus = 0;
+            if is_boundary(j.checked_sub(1).map(|k| original[k])) {
+                bonus += BONUS_BOUNDARY;
+            }
+            if j == 0 {
+                bonus += BONUS_FIRST_CHAR;
+            }
+
+            if i == 0 {
+                best[i][j] = Some(SCORE_MATCH + bonus);
+                continue;
+            }
+
+            for k in (i - 1)..j {
+                let Some(previous) = best[i - 1][k] else { continue };
+                let gap = j - k - 1;
+                let score = previous + SCORE_MATCH + bonus + if gap == 0 {
+                    BONUS_CONSECUTIVE
+                } else {
+                    -(PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (gap as i64 - 1))
+                };
+                if !matches!(best[i][j], Some(current) if current >= score) {
+                    best[i][j] = Some(score);
+                    from[i][j] = k;
+                }
+            }
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, score) = best[last].iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .max_by_key(|&(j, score)| (score, std::cmp::Reverse(j)))?;
+
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+/// How an entry matched a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryMatch {
+    pub score: i64,
+    pub fields: Vec<(Field, Vec<usize>)>,   // Matched characters of each field, for highlighting
+}
+
+impl EntryMatch {
+    /// The matched characters of a field, empty when no term matched it.
+    pub fn positions(&self, field: Field) -> Vec<usize> {
+        let mut positions = self.fields.iter()
+            .filter(|(matched, _)| *matched == field)
+            .flat_map(|(_, positions)| positions.iter().copied())
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+}
+
+/// Matches an entry against a query, every term has to match one of the fields it applies to.
+pub fn match_entry(entry: &SshConfigEntry, query: &str) -> Option<EntryMatch> {
+    let mut result = EntryMatch { score: 0, fields: Vec::new() };
+
+    for term in parse_query(query) {
+        let fields = match term.field {
+            Some(field) => vec![field],
+            None => Field::ALL.to_vec(),
+        };
+
+        // Reversed so that on a tie the first field, the host, wins
+        let (field, matched) = fields.into_iter().rev()
+            .filter_map(|field| {
+                let value = field.value(entry)?;
+                fuzzy_match(&term.pattern, &value).map(|matched| (field, matched))
+            })
+            .max_by_key(|(_, matched)| matched.score)?;
+
+        result.score += matched.score;
+        result.fields.push((field, matched.positions));
+    }
+
+    Some(result)
+}
+
+/// Searches the entries.
+///
+/// # Returns
+/// The indices of the matching entries, best score first. Entries with the same score keep the
+/// order of the config. An empty query matches everything.
+pub fn search(entries: &[SshConfigEntry], query: &str) -> Vec<usize> {
+    let mut results = entries.iter()
+        .enumerate()
+        .filter_map(|(index, entry)| match_entry(entry, query).map(|matched| (index, matched.score)))
+        .collect::<Vec<_>>();
+
+    results.sort_by_key(|&(index, score)| (std::cmp::Reverse(score), index));
+    results.into_iter().map(|(index, _)| index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::EntryKind;
+
+    fn positions(pattern: &str, text: &str) -> Option<Vec<usize>> {
+        fuzzy_match(pattern, text).map(|matched| matched.positions)
+    }
+
+    #[test]
+    fn the_matched_characters_are_the_best_placed_ones() {
+        assert_eq!(positions("wb1", "web-1"), Some(vec![0, 2, 4]));
+        // Word starts and runs of characters win over the first place they fit
+        assert_eq!(positions("p", "app-prod"), Some(vec![4]));
+        assert_eq!(positions("db", "adb-db"), Some(vec![4, 5]));
+        assert_eq!(positions("web", "my-web-web"), Some(vec![3, 4, 5]));
+        // Positions count characters, not bytes
+        assert_eq!(positions("é1", "café-1"), Some(vec![3, 5]));
+        assert_eq!(positions("", "web"), Some(vec![]));
+        assert_eq!(positions("bw", "web"), None);
+        assert_eq!(positions("webs", "web"), None);
+    }
+
+    #[test]
+    fn upper_case_makes_the_pattern_case_sensitive() {
+        assert!(fuzzy_match("web", "WEB-1").is_some());
+        assert!(fuzzy_match("Web", "web-1").is_none());
+        assert!(fuzzy_match("WEB", "WEB-1").is_some());
+        assert!(fuzzy_match("ab", "xaxb").unwrap().score < fuzzy_match("ab", "ab").unwrap().score);
+    }
+
+    #[test]
+    fn entries_match_on_every_term_and_keep_the_positions_of_each_field() {
+        let entries = vec![
+            SshConfigEntry::with_options(EntryKind::Host, "web-1", Some("prod"), &[("HostName", "10.0.0.1"), ("User", "deploy")]),
+            SshConfigEntry::with_options(EntryKind::Host, "db", Some("prod"), &[("User", "postgres")]),
+            SshConfigEntry::with_options(EntryKind::Host, "web-2", None, &[("User", "root")]),
+        ];
+
+        let matched = match_entry(&entries[0], "w1 user:dep").unwrap();
+        assert_eq!(matched.positions(Field::Host), [0, 4]);
+        assert_eq!(matched.positions(Field::User), [0, 1, 2]);
+        assert!(matched.positions(Field::Tag).is_empty());
+        assert!(match_entry(&entries[0], "w1 user:root").is_none());
+        // An unknown scope is part of the pattern
+        assert!(match_entry(&entries[0], "nope:web").is_none());
+
+        assert_eq!(search(&entries, "tag:prod"), [0, 1]);
+        assert_eq!(search(&entries, "web"), [0, 2]);
+        assert_eq!(search(&entries, "pos"), [1]);
+        assert_eq!(search(&entries, ""), [0, 1, 2]);
+    }
+}
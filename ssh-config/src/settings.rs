@@ -0,0 +1,664 @@
+//! This module reads the settings of the TUI: the keys of the host list, the color theme, the
+//! templates of the new host form and the config files that can be opened
+//!
+//! The file is `$XDG_CONFIG_HOME/ssh-config/config.toml` (`~/.config/ssh-config/config.toml` when
+//! the variable is not set), for example:
+//!
+//! ```toml
+//! theme = "mine"              # "default", "light" or one of [themes]
+//!
+//! [keys]
+//! connect = ["c", "Enter"]    # `Enter` is taken away from `open`...
+//! open = "o"                  # ...which moves to `o`
+//! redo = "Ctrl+r"
+//! delete = []                 # No key
+//!
+//! [themes.mine]               # Colors left out come from the default theme
+//! border = "green"
+//! highlight = "#ffaf00"
+//!
+//! [templates."lab VM"]        # Values the new host form (`a`) starts with
+//! host = "lab-"
+//! user = "root"
+//! port = 2222
+//! tag = "lab"
+//!
+//! [profiles]                  # Config files to switch to (`p`), or to open with `--profile`
+//! team = "~/src/team-ssh/config"
+//! ```
+//!
+//! Keys given for an action replace its default keys, and are taken away from the actions that
+//! have them by default.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::host_form::{self, FormField};
+
+/// An action of the host list that can be bound to keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Down,
+    Up,
+    First,
+    Last,
+    Open,
+    Search,
+    Back,
+    Quit,
+    Help,
+    Edit,
+    Connect,
+    Lint,
+    Collapse,
+    Expand,
+    NextGroup,
+    PreviousGroup,
+    Tags,
+    MoveTag,
+    Keys,
+    KnownHosts,
+    CheckReachability,
+    Delete,
+    Undo,
+    Redo,
+    Write,
+    Mark,
+    MarkMatches,
+    Bulk,
+    Add,
+    Profiles,
+}
+
+impl Binding {
+    /// Every action, in the order of the help
+    pub const ALL: [Binding; 30] = [
+        Binding::Help,
+        Binding::Quit,
+        Binding::Back,
+        Binding::Down,
+        Binding::Up,
+        Binding::First,
+        Binding::Last,
+        Binding::Open,
+        Binding::Search,
+        Binding::Edit,
+        Binding::Add,
+        Binding::Lint,
+        Binding::Connect,
+        Binding::Collapse,
+        Binding::Expand,
+        Binding::NextGroup,
+        Binding::PreviousGroup,
+        Binding::Tags,
+        Binding::MoveTag,
+        Binding::Keys,
+        Binding::KnownHosts,
+        Binding::CheckReachability,
+        Binding::Profiles,
+        Binding::Mark,
+        Binding::MarkMatches,
+        Binding::Bulk,
+        Binding::Delete,
+        Binding::Undo,
+        Binding::Redo,
+        Binding::Write,
+    ];
+
+    /// Name of the action in the `[keys]` table.
+    pub fn name(self) -> &'static str {
+        match self {
+            Binding::Down => "down",
+            Binding::Up => "up",
+            Binding::First => "first",
+            Binding::Last => "last",
+            Binding::Open => "open",
+            Binding::Search => "search",
+            Binding::Back => "back",
+            Binding::Quit => "quit",
+            Binding::Help => "help",
+            Binding::Edit => "edit",
+            Binding::Connect => "connect",
+            Binding::Lint => "lint",
+            Binding::Collapse => "collapse",
+            Binding::Expand => "expand",
+            Binding::NextGroup => "next_group",
+            Binding::PreviousGroup => "previous_group",
+            Binding::Tags => "tags",
+            Binding::MoveTag => "move_tag",
+            Binding::Keys => "keys",
+            Binding::KnownHosts => "known_hosts",
+            Binding::CheckReachability => "check_reachability",
+            Binding::Delete => "delete",
+            Binding::Undo => "undo",
+            Binding::Redo => "redo",
+            Binding::Write => "write",
+            Binding::Mark => "mark",
+            Binding::MarkMatches => "mark_matches",
+            Binding::Bulk => "bulk",
+            Binding::Add => "add",
+            Binding::Profiles => "profiles",
+        }
+    }
+
+    /// What the action does, for the help.
+    pub fn description(self) -> &'static str {
+        match self {
+            Binding::Down => "Next host",
+            Binding::Up => "Previous host",
+            Binding::First => "First host",
+            Binding::Last => "Last host",
+            Binding::Open => "Show the host, or collapse / expand the selected tag",
+            Binding::Search => "Search mode",
+            Binding::Back => "Back to normal mode",
+            Binding::Quit => "Quit",
+            Binding::Help => "This menu",
+            Binding::Edit => "Edit mode",
+            Binding::Connect => "Connect",
+            Binding::Lint => "Lint diagnostics",
+            Binding::Collapse => "Collapse the tag group",
+            Binding::Expand => "Expand the tag group",
+            Binding::NextGroup => "Next tag group",
+            Binding::PreviousGroup => "Previous tag group",
+            Binding::Tags => "Tag panel",
+            Binding::MoveTag => "Move the host to another tag",
+            Binding::Keys => "SSH keys and the hosts that use them",
+            Binding::KnownHosts => "known_hosts lines and the hosts they belong to",
+            Binding::CheckReachability => "Check whether the hosts are reachable",
+            Binding::Delete => "Delete the host",
+            Binding::Undo => "Undo the last change",
+            Binding::Redo => "Redo the last undone change",
+            Binding::Write => "Show the changes and write them to the config",
+            Binding::Mark => "Mark or unmark the host, Esc unmarks all",
+            Binding::MarkMatches => "Mark the search results, or every host shown",
+            Binding::Bulk => "Bulk actions on the marked hosts",
+            Binding::Add => "Add a host, from a template",
+            Binding::Profiles => "Open another config file, or compare with it",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Binding::Down => &["Down", "j"],
+            Binding::Up => &["Up", "k"],
+            Binding::First => &["Home", "g"],
+            Binding::Last => &["End", "G"],
+            Binding::Open => &["Enter"],
+            Binding::Search => &["/"],
+            Binding::Back => &["Esc"],
+            Binding::Quit => &["q"],
+            Binding::Help => &["h"],
+            Binding::Edit => &["e"],
+            Binding::Connect => &["c"],
+            Binding::Lint => &["l"],
+            Binding::Collapse => &["Left"],
+            Binding::Expand => &["Right"],
+            Binding::NextGroup => &["Tab"],
+            Binding::PreviousGroup => &["Shift+Tab"],
+            Binding::Tags => &["t"],
+            Binding::MoveTag => &["m"],
+            Binding::Keys => &["i"],
+            Binding::KnownHosts => &["K"],
+            Binding::CheckReachability => &["r"],
+            Binding::Delete => &["d"],
+            Binding::Undo => &["u"],
+            Binding::Redo => &["Ctrl+r"],
+            Binding::Write => &["w"],
+            Binding::Mark => &["Space"],
+            Binding::MarkMatches => &["*"],
+            Binding::Bulk => &["b"],
+            Binding::Add => &["a"],
+            Binding::Profiles => &["p"],
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Binding> {
+        Binding::ALL.iter().copied().find(|binding| binding.name() == name)
+    }
+}
+
+/// A key with the Ctrl and Alt modifiers, written like `q`, `G`, `Ctrl+r`, `Enter` or `Shift+Tab`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCombo {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+/// Names of the keys that are not characters
+const KEY_NAMES: [(&str, KeyCode); 15] = [
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("Shift+Tab", KeyCode::BackTab),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+];
+
+impl KeyCombo {
+    /// Whether the key event is this key. Shift is part of the character, `G` is Shift+g.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        key.code == self.code && key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT) == self.modifiers
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if let Some((_, code)) = KEY_NAMES.iter().find(|(name, _)| name.eq_ignore_ascii_case(text)) {
+            return Ok(KeyCombo { code: *code, modifiers: KeyModifiers::NONE });
+        }
+
+        let (modifier, key) = match text.split_once('+') {
+            Some((modifier, key)) if !key.is_empty() => (Some(modifier), key),
+            _ => (None, text),
+        };
+        let modifiers = match modifier.map(str::to_ascii_lowercase).as_deref() {
+            None => KeyModifiers::NONE,
+            Some("ctrl") => KeyModifiers::CONTROL,
+            Some("alt") => KeyModifiers::ALT,
+            Some(other) => return Err(format!("Unknown modifier '{}' in '{}'", other, text)),
+        };
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ if key.eq_ignore_ascii_case("space") => KeyCode::Char(' '),
+            _ => match key.strip_prefix(['F', 'f']).and_then(|n| n.parse().ok()) {
+                Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => KEY_NAMES.iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                    .map(|(_, code)| *code)
+                    .ok_or_else(|| format!("Unknown key '{}'", text))?,
+            },
+        };
+        Ok(KeyCombo { code, modifiers })
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => {
+                let name = KEY_NAMES.iter().find(|(_, other)| *other == code).map_or("?", |(name, _)| name);
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+/// The keys of each action of the host list
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    keys: Vec<(Binding, Vec<KeyCombo>)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = Binding::ALL.iter()
+            .map(|&binding| {
+                let combos = binding.default_keys().iter().map(|key| key.parse().unwrap()).collect();
+                (binding, combos)
+            })
+            .collect();
+        Self { keys }
+    }
+}
+
+impl KeyBindings {
+    /// The action bound to a key, if any.
+    pub fn binding(&self, key: &KeyEvent) -> Option<Binding> {
+        self.keys.iter()
+            .find(|(_, combos)| combos.iter().any(|combo| combo.matches(key)))
+            .map(|(binding, _)| *binding)
+    }
+
+    /// The keys of an action.
+    pub fn keys(&self, binding: Binding) -> &[KeyCombo] {
+        self.keys.iter()
+            .find(|(other, _)| *other == binding)
+            .map_or(&[], |(_, combos)| combos.as_slice())
+    }
+
+    /// Binds `combos` to `binding` in place of its keys, taking them away from the other actions.
+    fn bind(&mut self, binding: Binding, combos: Vec<KeyCombo>) {
+        for (other, keys) in self.keys.iter_mut() {
+            if *other == binding {
+                *keys = combos.clone();
+            } else {
+                keys.retain(|key| !combos.contains(key));
+            }
+        }
+    }
+}
+
+/// The colors of the TUI, by what they are used for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub border: Color,      // Host list, tag panel and the panels of the list
+    pub highlight: Color,   // Selection, search bar, table headers and typed text
+    pub popup: Color,       // Border and title of the entry, edit and review popups
+    pub background: Color,  // Behind the popups
+    pub text: Color,
+    pub muted: Color,       // Inherited values, counts and hints
+    pub accent: Color,      // Match blocks, global options and informational notes
+    pub error: Color,
+    pub warning: Color,
+    pub success: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            border: Color::Blue,
+            highlight: Color::Yellow,
+            popup: Color::Red,
+            background: Color::Black,
+            text: Color::White,
+            muted: Color::DarkGray,
+            accent: Color::Cyan,
+            error: Color::Red,
+            warning: Color::Yellow,
+            success: Color::Green,
+        }
+    }
+}
+
+impl Theme {
+    /// For terminals with a light background
+    fn light() -> Self {
+        Self {
+            border: Color::Blue,
+            highlight: Color::Magenta,
+            popup: Color::Red,
+            background: Color::White,
+            text: Color::Black,
+            muted: Color::Gray,
+            accent: Color::Blue,
+            error: Color::Red,
+            warning: Color::Rgb(0xaf, 0x5f, 0x00),
+            success: Color::Green,
+        }
+    }
+
+    /// The theme with the colors of `file` in place of its own.
+    fn with(mut self, file: &ThemeFile) -> Result<Self, String> {
+        let colors = [
+            (&mut self.border, &file.border),
+            (&mut self.highlight, &file.highlight),
+            (&mut self.popup, &file.popup),
+            (&mut self.background, &file.background),
+            (&mut self.text, &file.text),
+            (&mut self.muted, &file.muted),
+            (&mut self.accent, &file.accent),
+            (&mut self.error, &file.error),
+            (&mut self.warning, &file.warning),
+            (&mut self.success, &file.success),
+        ];
+        for (color, value) in colors {
+            if let Some(value) = value {
+                *color = Color::from_str(value).map_err(|_| format!("Invalid color '{}'", value))?;
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Values of a new host, from `[templates."<name>"]`. Fields left out start empty.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    #[serde(skip)]
+    pub name: String,
+    pub host: Option<String>,
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    #[serde(default, deserialize_with = "number_or_text")]
+    pub port: Option<String>,
+    pub identity_file: Option<String>,
+    pub proxy_jump: Option<String>,
+    pub tag: Option<String>,
+}
+
+/// A value that can be written as a number, like `port = 22`, or as a string.
+fn number_or_text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(i64),
+        Text(String),
+    }
+    Ok(Option::<Value>::deserialize(deserializer)?.map(|value| match value {
+        Value::Number(number) => number.to_string(),
+        Value::Text(text) => text,
+    }))
+}
+
+/// A config file with a name, from `[profiles]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub path: PathBuf,  // With `~` expanded
+}
+
+/// Keys, colors, host templates and profiles of the TUI
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub keys: KeyBindings,
+    pub theme: Theme,
+    pub templates: Vec<Template>,  // In the order of their names
+    pub profiles: Vec<Profile>,    // In the order of their names
+}
+
+/// The settings file as written
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsFile {
+    theme: Option<String>,
+    #[serde(default)]
+    keys: BTreeMap<String, KeyList>,
+    #[serde(default)]
+    themes: BTreeMap<String, ThemeFile>,
+    #[serde(default)]
+    templates: BTreeMap<String, Template>,
+    #[serde(default)]
+    profiles: BTreeMap<String, String>,
+}
+
+/// One key or a list of keys
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    border: Option<String>,
+    highlight: Option<String>,
+    popup: Option<String>,
+    background: Option<String>,
+    text: Option<String>,
+    muted: Option<String>,
+    accent: Option<String>,
+    error: Option<String>,
+    warning: Option<String>,
+    success: Option<String>,
+}
+
+/// Where the settings file is looked for.
+pub fn path() -> PathBuf {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(shellexpand::tilde("~/.config").into_owned()));
+    config_home.join("ssh-config").join("config.toml")
+}
+
+/// Reads the settings file, a missing file gives the default settings.
+pub fn load(path: &Path) -> Result<Settings, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => parse(&contents).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Parses the contents of a settings file.
+pub fn parse(contents: &str) -> Result<Settings, String> {
+    let file: SettingsFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+    let mut settings = Settings::default();
+
+    // Keys given for two actions would leave one of them without it, whichever comes last
+    let mut bound: Vec<(KeyCombo, &str)> = Vec::new();
+    for (name, keys) in &file.keys {
+        let binding = Binding::from_name(name).ok_or_else(|| format!("Unknown action '{}' in [keys]", name))?;
+        let keys = match keys {
+            KeyList::One(key) => vec![key.as_str()],
+            KeyList::Many(keys) => keys.iter().map(String::as_str).collect(),
+        };
+
+        let mut combos = Vec::new();
+        for key in keys {
+            let combo = key.parse::<KeyCombo>()?;
+            if let Some((_, other)) = bound.iter().find(|(other, _)| *other == combo) {
+                return Err(format!("'{}' is bound to both {} and {}", key, other, name));
+            }
+            bound.push((combo, name));
+            combos.push(combo);
+        }
+        settings.keys.bind(binding, combos);
+    }
+
+    settings.theme = match file.theme.as_deref().unwrap_or("default") {
+        name if file.themes.contains_key(name) => Theme::default().with(&file.themes[name])?,
+        "default" => Theme::default(),
+        "light" => Theme::light(),
+        name => return Err(format!("Unknown theme '{}'", name)),
+    };
+
+    // A template with a value the form would refuse is reported now rather than on every use
+    for (name, mut template) in file.templates {
+        template.name = name;
+        let values = host_form::values(Some(&template), None);
+        for (field, value) in FormField::ALL.iter().zip(&values) {
+            if *field != FormField::Host && !value.is_empty() {
+                field.validate(value).map_err(|e| format!("Template '{}': {}", template.name, e))?;
+            }
+        }
+        settings.templates.push(template);
+    }
+
+    settings.profiles = file.profiles.into_iter()
+        .map(|(name, path)| Profile { name, path: PathBuf::from(shellexpand::tilde(&path).into_owned()) })
+        .collect();
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn default_bindings() {
+        let keys = KeyBindings::default();
+        assert_eq!(keys.binding(&key(KeyCode::Char('j'), KeyModifiers::NONE)), Some(Binding::Down));
+        assert_eq!(keys.binding(&key(KeyCode::Char('k'), KeyModifiers::NONE)), Some(Binding::Up));
+        assert_eq!(keys.binding(&key(KeyCode::Char('i'), KeyModifiers::NONE)), Some(Binding::Keys));
+        assert_eq!(keys.binding(&key(KeyCode::Char('G'), KeyModifiers::SHIFT)), Some(Binding::Last));
+        assert_eq!(keys.binding(&key(KeyCode::Char('r'), KeyModifiers::NONE)), Some(Binding::CheckReachability));
+        assert_eq!(keys.binding(&key(KeyCode::Char('r'), KeyModifiers::CONTROL)), Some(Binding::Redo));
+        assert_eq!(keys.binding(&key(KeyCode::BackTab, KeyModifiers::SHIFT)), Some(Binding::PreviousGroup));
+    }
+
+    #[test]
+    fn keys_are_parsed_and_displayed() {
+        for text in ["q", "G", "Ctrl+r", "Alt+x", "Enter", "Shift+Tab", "PageDown", "F5", "Space"] {
+            assert_eq!(text.parse::<KeyCombo>().unwrap().to_string(), text);
+        }
+        assert_eq!("ctrl+R".parse::<KeyCombo>().unwrap().to_string(), "Ctrl+R");
+        assert!("Hyper+x".parse::<KeyCombo>().is_err());
+        assert!("Nope".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn remapped_keys_are_taken_from_their_default_action() {
+        let settings = parse("[keys]\nconnect = [\"c\", \"Enter\"]\nopen = \"o\"\ndelete = []\n").unwrap();
+        let keys = &settings.keys;
+        assert_eq!(keys.binding(&key(KeyCode::Enter, KeyModifiers::NONE)), Some(Binding::Connect));
+        assert_eq!(keys.binding(&key(KeyCode::Char('o'), KeyModifiers::NONE)), Some(Binding::Open));
+        assert_eq!(keys.binding(&key(KeyCode::Char('d'), KeyModifiers::NONE)), None);
+        assert_eq!(keys.keys(Binding::Down).len(), 2);
+    }
+
+    #[test]
+    fn a_key_cannot_be_bound_twice() {
+        let error = parse("[keys]\nquit = \"x\"\nhelp = \"x\"\n").unwrap_err();
+        assert!(error.contains("'x' is bound to both"), "{}", error);
+    }
+
+    #[test]
+    fn themes_override_the_default_colors() {
+        let settings = parse("theme = \"mine\"\n[themes.mine]\nborder = \"green\"\nhighlight = \"#ffaf00\"\n").unwrap();
+        assert_eq!(settings.theme.border, Color::Green);
+        assert_eq!(settings.theme.highlight, Color::Rgb(0xff, 0xaf, 0x00));
+        assert_eq!(settings.theme.popup, Theme::default().popup);
+
+        assert_eq!(parse("theme = \"light\"").unwrap().theme, Theme::light());
+        assert!(parse("theme = \"nope\"").is_err());
+        assert!(parse("theme = \"bad\"\n[themes.bad]\nborder = \"blurple\"\n").is_err());
+        assert!(parse("[colors]\n").is_err());
+    }
+
+    #[test]
+    fn templates_are_read_and_checked() {
+        let settings = parse("[templates.\"lab VM\"]\nuser = \"root\"\nport = 2222\n[templates.prod]\ntag = \"prod\"\n").unwrap();
+        let names = settings.templates.iter().map(|template| template.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["lab VM", "prod"]);
+        assert_eq!(settings.templates[0].port.as_deref(), Some("2222"));
+        assert_eq!(settings.templates[0].user.as_deref(), Some("root"));
+
+        let error = parse("[templates.bad]\nport = \"ssh\"\n").unwrap_err();
+        assert_eq!(error, "Template 'bad': Invalid Port 'ssh'");
+        assert!(parse("[templates.bad]\nUser = \"root\"\n").is_err());
+    }
+
+    #[test]
+    fn profiles_are_config_files_by_name() {
+        let settings = parse("[profiles]\nteam = \"/srv/team/config\"\nhome = \"~/ssh.conf\"\n").unwrap();
+        let names = settings.profiles.iter().map(|profile| profile.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["home", "team"]);
+        assert!(!settings.profiles[0].path.starts_with("~"));
+        assert_eq!(settings.profiles[1].path, PathBuf::from("/srv/team/config"));
+    }
+}
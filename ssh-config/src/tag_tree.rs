@@ -0,0 +1,276 @@
+//! This module groups the entries by tag for the host list, each group can be collapsed
+
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::ListItem,
+};
+use std::collections::{BTreeSet, HashMap};
+use crate::entry::{EntryKind, SshConfigEntry};
+use crate::reachability::Status;
+use crate::settings::Theme;
+
+/// A row of the host list
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeRow {
+    Group(Option<String>),  // Header of a tag group, `None` for the untagged entries
+    Entry(usize),           // Index of the entry
+}
+
+/// A tag with the entries that have it, in the order of the config
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagGroup {
+    pub tag: Option<String>,
+    pub entries: Vec<usize>,
+}
+
+/// The host list as a tree of tag groups.
+///
+/// Groups come in the order their tag first appears in the config. When no entry has a tag the
+/// list is flat, without group headers.
+#[derive(Debug, Default)]
+pub struct TagTree {
+    groups: Vec<TagGroup>,
+    collapsed: Vec<Option<String>>,
+    rows: Vec<TreeRow>,
+}
+
+impl TagTree {
+    pub fn new(entries: &[SshConfigEntry]) -> Self {
+        let mut tree = Self::default();
+        tree.rebuild(entries);
+        tree
+    }
+
+    /// Groups the entries again, after they changed. Collapsed groups stay collapsed.
+    pub fn rebuild(&mut self, entries: &[SshConfigEntry]) {
+        self.groups.clear();
+        for (index, entry) in entries.iter().enumerate() {
+            match self.groups.iter_mut().find(|group| group.tag == entry.tag) {
+                Some(group) => group.entries.push(index),
+                None => self.groups.push(TagGroup { tag: entry.tag.clone(), entries: vec![index] }),
+            }
+        }
+        let groups = &self.groups;
+        self.collapsed.retain(|tag| groups.iter().any(|group| &group.tag == tag));
+        self.update_rows();
+    }
+
+    fn update_rows(&mut self) {
+        self.rows.clear();
+        if self.is_flat() {
+            self.rows.extend(self.groups.iter().flat_map(|group| group.entries.iter().map(|&i| TreeRow::Entry(i))));
+            return;
+        }
+
+        for group in &self.groups {
+            self.rows.push(TreeRow::Group(group.tag.clone()));
+            if !self.collapsed.contains(&group.tag) {
+                self.rows.extend(group.entries.iter().map(|&i| TreeRow::Entry(i)));
+            }
+        }
+    }
+
+    /// Whether the list has no group headers, which is the case when no entry has a tag.
+    pub fn is_flat(&self) -> bool {
+        self.groups.iter().all(|group| group.tag.is_none())
+    }
+
+    pub fn groups(&self) -> &[TagGroup] {
+        &self.groups
+    }
+
+    /// Index of the entry shown at `row`, `None` for group headers.
+    pub fn entry_at(&self, row: usize) -> Option<usize> {
+        match self.rows.get(row) {
+            Some(TreeRow::Entry(index)) => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// The tag of the group `row` belongs to, the row being either its header or one of its entries.
+    pub fn group_at(&self, row: usize) -> Option<Option<String>> {
+        self.rows.get(..=row)?.iter()
+            .rev()
+            .find_map(|row| match row {
+                TreeRow::Group(tag) => Some(tag.clone()),
+                TreeRow::Entry(_) => None,
+            })
+    }
+
+    /// Row of the header of a group.
+    pub fn group_row(&self, tag: &Option<String>) -> Option<usize> {
+        self.rows.iter().position(|row| matches!(row, TreeRow::Group(group) if group == tag))
+    }
+
+    /// Row of an entry, expanding its group when it is collapsed.
+    pub fn entry_row(&mut self, index: usize) -> Option<usize> {
+        let tag = self.groups.iter().find(|group| group.entries.contains(&index))?.tag.clone();
+        self.set_collapsed(&tag, false);
+        self.rows.iter().position(|row| *row == TreeRow::Entry(index))
+    }
+
+    pub fn is_collapsed(&self, tag: &Option<String>) -> bool {
+        self.collapsed.contains(tag)
+    }
+
+    pub fn set_collapsed(&mut self, tag: &Option<String>, collapsed: bool) {
+        self.collapsed.retain(|other| other != tag);
+        if collapsed {
+            self.collapsed.push(tag.clone());
+        }
+        self.update_rows();
+    }
+
+    /// Row of the next (or previous) group header starting from `row`, wrapping around.
+    pub fn next_group_row(&self, row: usize, forward: bool) -> Option<usize> {
+        let headers = self.rows.iter()
+            .enumerate()
+            .filter(|(_, row)| matches!(row, TreeRow::Group(_)))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        if forward {
+            headers.iter().copied().find(|&i| i > row).or_else(|| headers.first().copied())
+        } else {
+            headers.iter().copied().rev().find(|&i| i < row).or_else(|| headers.last().copied())
+        }
+    }
+
+    /// Builds the items shown in the hosts list, with the reachability of the hosts that were
+    /// checked in a column after the names and the marked entries flagged in front of them.
+    pub fn items(&self, entries: &[SshConfigEntry], statuses: &HashMap<String, Status>, marked: &BTreeSet<usize>, theme: &Theme) -> Vec<ListItem<'static>> {
+        let indent = if self.is_flat() { "" } else { "  " };
+        let width = entries.iter().map(|entry| entry.label().chars().count()).max().unwrap_or(0);
+
+        self.rows.iter()
+            .map(|row| match row {
+                TreeRow::Group(tag) => {
+                    let count = self.groups.iter()
+                        .find(|group| &group.tag == tag)
+                        .map_or(0, |group| group.entries.len());
+                    let arrow = if self.is_collapsed(tag) { "▸" } else { "▾" };
+                    ListItem::new(Line::from(vec![
+                        Span::styled(
+                            format!("{} {}", arrow, tag_name(tag)),
+                            Style::default().fg(theme.border).add_modifier(Modifier::BOLD),
+                        ),
+                        Span::styled(format!(" ({})", count), Style::default().fg(theme.muted)),
+                    ]))
+                }
+                TreeRow::Entry(index) => {
+                    let entry = &entries[*index];
+                    // Match blocks and global options are told apart from the hosts
+                    let style = match entry.kind {
+                        EntryKind::Host => Style::default().fg(theme.text),
+                        EntryKind::Match => Style::default().fg(theme.accent),
+                        EntryKind::Global => Style::default().fg(theme.accent).add_modifier(Modifier::ITALIC),
+                    };
+                    let name = format!("{}{:width$}", indent, entry.label(), width = width);
+                    let status = match statuses.get(&entry.host).filter(|_| entry.kind == EntryKind::Host) {
+                        Some(status) => {
+                            let color = match status {
+                                Status::Up { version: Some(_), .. } => theme.success,
+                                Status::Up { version: None, .. } => theme.warning,
+                                Status::Down(_) => theme.error,
+                                Status::Checking | Status::Skipped(_) => theme.muted,
+                            };
+                            Span::styled(format!("  {}", status.summary()), Style::default().fg(color))
+                        }
+                        None => Span::raw(""),
+                    };
+                    ListItem::new(Line::from(vec![mark_column(marked, *index, theme), Span::raw(name), status])).style(style)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Column in front of the entries while some of them are marked, with a dot for the marked ones.
+pub fn mark_column(marked: &BTreeSet<usize>, index: usize, theme: &Theme) -> Span<'static> {
+    if marked.contains(&index) {
+        Span::styled("● ", Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD))
+    } else if marked.is_empty() {
+        Span::raw("")
+    } else {
+        Span::raw("  ")
+    }
+}
+
+/// Name shown for a tag, the untagged entries have a placeholder.
+pub fn tag_name(tag: &Option<String>) -> &str {
+    tag.as_deref().unwrap_or("(untagged)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tagged(tags: &[Option<&str>]) -> Vec<SshConfigEntry> {
+        tags.iter()
+            .enumerate()
+            .map(|(i, tag)| SshConfigEntry::new(EntryKind::Host, &format!("h{}", i), tag.map(str::to_string), "config".into()))
+            .collect()
+    }
+
+    fn tag(name: &str) -> Option<String> {
+        Some(name.to_string())
+    }
+
+    #[test]
+    fn entries_are_grouped_in_the_order_their_tag_appears() {
+        let entries = tagged(&[Some("prod"), None, Some("lab"), Some("prod")]);
+        let tree = TagTree::new(&entries);
+
+        assert!(!tree.is_flat());
+        assert_eq!(tree.groups(), [
+            TagGroup { tag: tag("prod"), entries: vec![0, 3] },
+            TagGroup { tag: None, entries: vec![1] },
+            TagGroup { tag: tag("lab"), entries: vec![2] },
+        ]);
+        assert_eq!(tree.rows, [
+            TreeRow::Group(tag("prod")), TreeRow::Entry(0), TreeRow::Entry(3),
+            TreeRow::Group(None), TreeRow::Entry(1),
+            TreeRow::Group(tag("lab")), TreeRow::Entry(2),
+        ]);
+        assert_eq!(tree.entry_at(2), Some(3));
+        assert_eq!(tree.entry_at(3), None);
+        assert_eq!(tree.group_at(4), Some(None));
+        assert_eq!(tree.group_row(&tag("lab")), Some(5));
+        assert_eq!(tree.items(&entries, &HashMap::new(), &BTreeSet::new(), &Theme::default()).len(), 7);
+
+        // Without tags there are no headers
+        let flat = TagTree::new(&tagged(&[None, None]));
+        assert!(flat.is_flat());
+        assert_eq!(flat.rows, [TreeRow::Entry(0), TreeRow::Entry(1)]);
+        assert_eq!(flat.next_group_row(0, true), None);
+    }
+
+    #[test]
+    fn collapsed_groups_hide_their_entries_until_one_is_needed() {
+        let mut entries = tagged(&[Some("prod"), Some("lab"), Some("lab")]);
+        let mut tree = TagTree::new(&entries);
+
+        tree.set_collapsed(&tag("lab"), true);
+        assert!(tree.is_collapsed(&tag("lab")));
+        assert_eq!(tree.rows, [TreeRow::Group(tag("prod")), TreeRow::Entry(0), TreeRow::Group(tag("lab"))]);
+
+        // Header navigation wraps around
+        assert_eq!(tree.next_group_row(0, true), Some(2));
+        assert_eq!(tree.next_group_row(2, true), Some(0));
+        assert_eq!(tree.next_group_row(0, false), Some(2));
+
+        // A rebuild keeps the group collapsed, jumping to one of its entries expands it
+        entries.push(SshConfigEntry::new(EntryKind::Host, "h3", tag("lab"), "config".into()));
+        tree.rebuild(&entries);
+        assert!(tree.is_collapsed(&tag("lab")));
+        assert_eq!(tree.entry_row(3), Some(5));
+        assert!(!tree.is_collapsed(&tag("lab")));
+
+        // The collapsed state of a tag that is gone is forgotten
+        tree.set_collapsed(&tag("lab"), true);
+        entries.retain(|entry| entry.tag == tag("prod"));
+        tree.rebuild(&entries);
+        assert!(!tree.is_collapsed(&tag("lab")));
+    }
+}
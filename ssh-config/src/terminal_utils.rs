@@ -1,17 +1,18 @@
 // src/terminal_utils.rs
 
-use crossterm::{self, event, terminal, ExecutableCommand};
+use crossterm::{self, cursor, event, terminal, ExecutableCommand};
 use std::io::{self, Write};
 use ratatui as tui;
-use tui::backend::CrosstermBackend;
+use tui::backend::{Backend, CrosstermBackend};
 use tui::Terminal;
 
 
-pub struct TerminalManager<W: Write> {
-    terminal: Terminal<CrosstermBackend<W>>,
+pub struct TerminalManager<B: Backend> {
+    terminal: Terminal<B>,
+    restore: fn(&mut Terminal<B>) -> io::Result<()>,  // Puts the terminal back as it was found
 }
 
-impl<W: Write> TerminalManager<W> {
+impl<W: Write> TerminalManager<CrosstermBackend<W>> {
     /// Creates a new TerminalManager, setting up the terminal in raw mode
     /// and enabling alternate screen and mouse capture.
     pub fn new(mut stdout: W) -> io::Result<Self> {
@@ -22,7 +23,34 @@ impl<W: Write> TerminalManager<W> {
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
 
-        Ok(Self { terminal })
+        Ok(Self { terminal, restore: restore_terminal })
+    }
+
+    /// Hands the terminal over to another program, leaving the alternate screen and raw mode.
+    /// `resume` must be called once the program exits.
+    pub fn suspend(&mut self) -> io::Result<()> {
+        self.terminal.backend_mut().execute(event::DisableMouseCapture)?;
+        self.terminal.backend_mut().execute(terminal::LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        self.terminal.show_cursor()?;
+        Ok(())
+    }
+
+    /// Takes the terminal back after `suspend` and redraws everything on the next `draw`.
+    pub fn resume(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        self.terminal.backend_mut().execute(terminal::EnterAlternateScreen)?;
+        self.terminal.backend_mut().execute(event::EnableMouseCapture)?;
+        self.terminal.clear()?;
+        Ok(())
+    }
+}
+
+impl<B: Backend> TerminalManager<B> {
+    /// Draws on any backend without touching the terminal, e.g. a `TestBackend` in the tests.
+    #[cfg(test)]
+    pub fn with_backend(backend: B) -> io::Result<Self> {
+        Ok(Self { terminal: Terminal::new(backend)?, restore: |_| Ok(()) })
     }
 
     /// Provides a `draw` method that allows controlled access to the `terminal` field for rendering
@@ -33,18 +61,44 @@ impl<W: Write> TerminalManager<W> {
         self.terminal.draw(f).map(|_| ()) // Map the result to `Result<(), Error>`
     }
 
+    /// The backend drawn on, to look at what was drawn.
+    #[cfg(test)]
+    pub fn backend(&self) -> &B {
+        self.terminal.backend()
+    }
+
     /// Restaura o terminal ao seu estado original
     pub fn cleanup(&mut self) -> io::Result<()> {
-        terminal::disable_raw_mode()?;
-        self.terminal.backend_mut().execute(terminal::LeaveAlternateScreen)?;
-        self.terminal.backend_mut().execute(event::DisableMouseCapture)?;
-        self.terminal.show_cursor()?;
-        Ok(())
+        (self.restore)(&mut self.terminal)
     }
 }
 
+/// Leaves raw mode, the alternate screen and mouse capture, undoing `TerminalManager::new`.
+fn restore_terminal<W: Write>(terminal: &mut Terminal<CrosstermBackend<W>>) -> io::Result<()> {
+    terminal::disable_raw_mode()?;
+    terminal.backend_mut().execute(terminal::LeaveAlternateScreen)?;
+    terminal.backend_mut().execute(event::DisableMouseCapture)?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+/// Puts the terminal back before a panic is reported, so that the message can be read and the
+/// shell still works, and logs the panic.
+pub fn restore_on_panic() {
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let mut stdout = io::stdout();
+        let _ = terminal::disable_raw_mode();
+        let _ = stdout.execute(terminal::LeaveAlternateScreen);
+        let _ = stdout.execute(event::DisableMouseCapture);
+        let _ = stdout.execute(cursor::Show);
+        log::error!("{}", info);
+        report(info);
+    }));
+}
+
 // Drop implementation for automatic cleanup as fallback
-impl<W: Write> Drop for TerminalManager<W> {
+impl<B: Backend> Drop for TerminalManager<B> {
     fn drop(&mut self) {
         let _ = self.cleanup();
     }
@@ -1,36 +1,61 @@
-use crate::entry::SshConfigEntry;
+use crate::entry::{EntryKind, SshConfigEntry, SshOption};
+use std::path::{Path, PathBuf};
 
 use ratatui as tui;
 use tui::{
     layout,
-    style::{Color, Modifier, Style},
-    text::{Span, Text, Line},
-    widgets::{Block, Borders, Cell, Row, Table, Paragraph},
+    style::{Modifier, Style},
+    text::{Span, Line},
+    widgets::{Block, Borders, Cell, Clear, List, ListItem, Row, Table, Paragraph, Wrap},
     Frame,
 };
 
-use crate::app::AppMode;
+use crate::app::{AppMode, EditField};
+use crate::bulk::BulkAction;
+use crate::history::DiffLine;
+use crate::host_form::FormField;
+use crate::jump::{self, Hop, JumpChain};
+use crate::keys::KeyReport;
+use crate::known_hosts::Marker;
+use crate::lint::Severity;
+use crate::profile::EntryDiff;
+use crate::connect;
+use crate::error::Error;
+use crate::resolve::{self, OptionSource};
+use crate::search::{self, Field};
+use crate::settings::{Profile, Template, Theme};
+use crate::tag_tree::{tag_name, TagGroup};
 
-pub fn render_popup_table(f: &mut Frame, area: layout::Rect, entry: &SshConfigEntry) {
+/// Renders the entry at `index` with its options, followed by the effective configuration of the
+/// host and the entry each value comes from.
+pub fn render_popup_table(f: &mut Frame, area: layout::Rect, entries: &[SshConfigEntry], index: usize, theme: &Theme) {
+    let entry = &entries[index];
     let popup_block = Block::default()
         .title(Span::styled(
-            format!(" {} ", entry.host),
+            format!(" {} ", entry.label()),
             Style::default()
-                .fg(Color::Red)
+                .fg(theme.popup)
                 .add_modifier(Modifier::BOLD),
         ))
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Red))
-        .style(Style::default().bg(Color::Black));
+        .border_style(Style::default().fg(theme.popup))
+        .style(Style::default().bg(theme.background));
 
-    // Collect rows for each field in the entry
-    let mut rows = vec![
-        Row::new(vec![Cell::from("Host"), Cell::from(entry.host.clone())]),
-    ];
+    // Collect rows for each field in the entry, global options have no Host/Match line
+    let mut rows = Vec::new();
+    if entry.kind != EntryKind::Global {
+        rows.push(Row::new(vec![Cell::from(entry.kind.keyword()), Cell::from(entry.host.clone())]));
+    }
 
-    // Add each option as a row
-    for (key, value) in &entry.options {
-        rows.push(Row::new(vec![Cell::from(key.clone()), Cell::from(value.clone())]));
+    // Add each option as a row, values that are not valid for their keyword are shown in red
+    for option in entry.typed_options() {
+        let (key, value) = option.to_pair();
+        let row = Row::new(vec![Cell::from(key.clone()), Cell::from(value.clone())]);
+        if matches!(option, SshOption::Other(..)) && SshOption::parse(&key, &value).is_err() {
+            rows.push(row.style(Style::default().fg(theme.error)));
+        } else {
+            rows.push(row);
+        }
     }
 
     // Add each comment as a row
@@ -43,22 +68,259 @@ pub fn render_popup_table(f: &mut Frame, area: layout::Rect, entry: &SshConfigEn
         rows.push(Row::new(vec![Cell::from("Tag"), Cell::from(tag.clone())]));
     }
 
+    rows.push(Row::new(vec![Cell::from("File"), Cell::from(entry.source.display().to_string())]));
+
+    // Effective configuration, like `ssh -G <alias>`
+    if let Some(alias) = connect::connect_alias(entry) {
+        let resolution = resolve::resolve(entries, alias);
+        let header = Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD);
+        let inherited = Style::default().fg(theme.muted);
+
+        rows.push(Row::new(vec![Cell::from("")]));
+        rows.push(Row::new(vec![
+            Cell::from(format!("Effective for {}", alias)),
+            Cell::from(""),
+            Cell::from("Set by"),
+        ]).style(header));
+
+        for option in &resolution.options {
+            let (origin, style) = match option.source {
+                OptionSource::Entry(i) if i == index => ("this entry".to_string(), Style::default()),
+                OptionSource::Entry(i) => (format!("{} (line {})", entries[i].label(), entries[i].line), inherited),
+                OptionSource::Default => ("ssh default".to_string(), inherited),
+            };
+            rows.push(Row::new(vec![
+                Cell::from(option.key.clone()),
+                Cell::from(option.value.clone()),
+                Cell::from(origin),
+            ]).style(style));
+        }
+        for note in &resolution.notes {
+            rows.push(Row::new(vec![Cell::from("Note"), Cell::from(note.clone())]).style(inherited));
+        }
+    }
+
     let table = Table::new(
         rows,
         &[
+            layout::Constraint::Percentage(30),
             layout::Constraint::Percentage(40),
-            layout::Constraint::Percentage(60),
+            layout::Constraint::Percentage(30),
         ],
     )
     .block(popup_block)
-    .style(Style::default().fg(Color::White));
+    .style(Style::default().fg(theme.text));
 
-    f.render_widget(table, area);
+    // Hosts reached through jump hosts get the chain drawn below the options
+    let chain = connect::connect_alias(entry).map(|alias| jump::jump_chain(entries, alias));
+    match chain.filter(JumpChain::has_jumps) {
+        Some(chain) => {
+            let lines = jump_chain_lines(&chain, theme);
+            let chunks = layout::Layout::default()
+                .direction(layout::Direction::Vertical)
+                .constraints([
+                    layout::Constraint::Min(3),
+                    layout::Constraint::Length(lines.len() as u16 + 2),
+                ])
+                .split(area);
+
+            let title = match chain.unresolved().as_slice() {
+                [] => " Jump chain ".to_string(),
+                unresolved => format!(" Jump chain, undefined: {} ", unresolved.join(", ")),
+            };
+            let diagram = Paragraph::new(lines)
+                .block(Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(theme.popup)))
+                .style(Style::default().fg(theme.text).bg(theme.background));
+            f.render_widget(table, chunks[0]);
+            f.render_widget(diagram, chunks[1]);
+        }
+        None => f.render_widget(table, area),
+    }
 }
 
-pub fn render_search_bar(frame: &mut Frame, area: layout::Rect, app_mode: &AppMode) {
+/// Draws the hops of a jump chain one under the other, starting from the local machine. Hops no
+/// Host entry defines are shown in red, and a cycle ends the chain with a warning.
+fn jump_chain_lines(chain: &JumpChain, theme: &Theme) -> Vec<Line<'static>> {
+    let faded = Style::default().fg(theme.muted);
+    let missing = Style::default().fg(theme.error);
+    let width = chain.hops.iter()
+        .map(|hop| match hop {
+            Hop::Host { name, .. } => name.chars().count(),
+            Hop::Command(_) => "ProxyCommand".len(),
+        })
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![Line::from(Span::styled("localhost", faded))];
+    for (i, hop) in chain.hops.iter().enumerate() {
+        let indent = "   ".repeat(i);
+        let arrow = Span::styled(format!("{}└─▶ ", indent), faded);
+        let line = match hop {
+            Hop::Host { name, destination, entry } => {
+                let is_target = i + 1 == chain.hops.len();
+                let name_style = if is_target {
+                    Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().add_modifier(Modifier::BOLD)
+                };
+                let note = match entry {
+                    Some(_) => Span::styled(format!("  {}", destination), faded),
+                    None => Span::styled(format!("  {}  not defined by any Host", destination), missing),
+                };
+                Line::from(vec![arrow, Span::styled(format!("{:width$}", name, width = width), name_style), note])
+            }
+            Hop::Command(command) => Line::from(vec![
+                arrow,
+                Span::styled(format!("{:width$}", "ProxyCommand", width = width), Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled(format!("  {}", command), faded),
+            ]),
+        };
+        lines.push(line);
+    }
+
+    if let Some(cycle) = &chain.cycle {
+        lines.push(Line::from(Span::styled(
+            format!("⚠ cycle: {}", cycle.join(" → ")),
+            Style::default().fg(theme.error).add_modifier(Modifier::BOLD),
+        )));
+    }
+    lines
+}
+
+pub fn render_edit_table(f: &mut Frame, area: layout::Rect, app_mode: &AppMode, theme: &Theme) {
+    if let AppMode::Edit { entry, selected, field, input, message, .. } = app_mode {
+        let chunks = layout::Layout::default()
+            .direction(layout::Direction::Vertical)
+            .constraints([
+                layout::Constraint::Min(3),     // Options table
+                layout::Constraint::Length(3),  // Input and status line
+            ])
+            .split(area);
+
+        let editing = Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD);
+        let typed = format!("{}_", input);
+
+        let host = if *field == Some(EditField::Host) { typed.clone() } else { entry.host.clone() };
+        let keyword = if entry.kind == EntryKind::Global { "(global)" } else { entry.kind.keyword() };
+        let mut rows = vec![
+            Row::new(vec![Cell::from(keyword), Cell::from(host)]),
+        ];
+
+        for (i, (key, value)) in entry.options.iter().enumerate() {
+            let value = if *field == Some(EditField::Value(i)) { typed.clone() } else { value.clone() };
+            rows.push(Row::new(vec![Cell::from(key.clone()), Cell::from(value)]));
+        }
+
+        match field {
+            Some(EditField::NewKey) => {
+                rows.push(Row::new(vec![Cell::from(typed.clone()), Cell::from("")]).style(editing));
+            }
+            Some(EditField::NewValue(key)) => {
+                rows.push(Row::new(vec![Cell::from(key.clone()), Cell::from(typed.clone())]).style(editing));
+            }
+            _ => {}
+        }
+
+        let rows = rows.into_iter()
+            .enumerate()
+            .map(|(i, row)| if i == *selected { row.style(editing) } else { row })
+            .collect::<Vec<_>>();
+
+        let table = Table::new(
+            rows,
+            &[
+                layout::Constraint::Percentage(40),
+                layout::Constraint::Percentage(60),
+            ],
+        )
+        .block(Block::default()
+            .title(Span::styled(
+                format!(" Edit: {} ", entry.label()),
+                Style::default()
+                    .fg(theme.popup)
+                    .add_modifier(Modifier::BOLD),
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.popup))
+            .style(Style::default().bg(theme.background)))
+        .style(Style::default().fg(theme.text));
+
+        f.render_widget(table, chunks[0]);
+
+        let status = match (message, field) {
+            (Some(message), _) => Span::styled(message.clone(), Style::default().fg(theme.error)),
+            (None, Some(_)) => Span::raw("Enter: confirm   Esc: cancel"),
+            (None, None) => Span::raw("Enter: edit   a: add   d: delete   r: rename   s: apply   Esc: back"),
+        };
+        let paragraph = Paragraph::new(status)
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.popup)))
+            .style(Style::default().bg(theme.background).fg(theme.text));
+
+        f.render_widget(paragraph, chunks[1]);
+    }
+}
+
+pub fn render_lint_table(f: &mut Frame, area: layout::Rect, app_mode: &AppMode, theme: &Theme) {
+    if let AppMode::Lint { diagnostics, selected } = app_mode {
+        let rows = diagnostics.iter()
+            .enumerate()
+            .map(|(i, diagnostic)| {
+                let color = match diagnostic.severity {
+                    Severity::Error => theme.error,
+                    Severity::Warning => theme.warning,
+                    Severity::Info => theme.accent,
+                };
+                let location = format!(
+                    "{}:{}",
+                    diagnostic.source.file_name().unwrap_or_default().to_string_lossy(),
+                    diagnostic.line,
+                );
+                let row = Row::new(vec![
+                    Cell::from(Span::styled(diagnostic.severity.to_string(), Style::default().fg(color))),
+                    Cell::from(location),
+                    Cell::from(diagnostic.message.clone()),
+                ]);
+                if i == *selected {
+                    row.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    row
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let title = if diagnostics.is_empty() {
+            " Diagnostics (no problems found) ".to_string()
+        } else {
+            format!(" Diagnostics ({}) ", diagnostics.len())
+        };
+
+        let table = Table::new(
+            rows,
+            &[
+                layout::Constraint::Length(8),
+                layout::Constraint::Length(16),
+                layout::Constraint::Min(20),
+            ],
+        )
+        .block(Block::default()
+            .title(Span::styled(title, Style::default().fg(theme.popup).add_modifier(Modifier::BOLD)))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.popup))
+            .style(Style::default().bg(theme.background)))
+        .style(Style::default().fg(theme.text));
+
+        f.render_widget(table, area);
+    }
+}
+
+pub fn render_search_bar(frame: &mut Frame, area: layout::Rect, app_mode: &AppMode, theme: &Theme) {
     if let AppMode::Search { query, matches, .. } = app_mode {
-        let style = Style::default().fg(Color::Yellow);
+        let style = Style::default().fg(theme.highlight);
         
         // O título agora mostra o número de matches
         let title = if matches.is_empty() && query.is_empty() {
@@ -88,34 +350,575 @@ pub fn render_search_bar(frame: &mut Frame, area: layout::Rect, app_mode: &AppMo
     }
 }
 
-pub fn highlight_search_matches(text: &str, query: &str) -> Text<'static> {
-    if query.is_empty() {
-        return Text::from(text.to_string());
+/// Splits `text` into spans with the characters at `positions` highlighted, `positions` being
+/// character indices as returned by the fuzzy search.
+pub fn highlight_search_matches(text: &str, positions: &[usize], style: Style, theme: &Theme) -> Vec<Span<'static>> {
+    let highlight = style.fg(theme.highlight).add_modifier(Modifier::BOLD);
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut current = String::new();
+    let mut current_highlighted = false;
+
+    for (i, c) in text.chars().enumerate() {
+        let highlighted = positions.contains(&i);
+        if highlighted != current_highlighted && !current.is_empty() {
+            let span_style = if current_highlighted { highlight } else { style };
+            spans.push(Span::styled(std::mem::take(&mut current), span_style));
+        }
+        current_highlighted = highlighted;
+        current.push(c);
+    }
+    if !current.is_empty() {
+        spans.push(Span::styled(current, if current_highlighted { highlight } else { style }));
     }
 
-    let mut spans = Vec::new();
-    let query = query.to_lowercase();
-    let mut last_end = 0;
+    spans
+}
+
+/// Row of the host list for a search result: the label with the matched characters highlighted,
+/// followed by the other fields the query matched, e.g. `user:deploy`. `mark` flags the marked
+/// entries, see `tag_tree::mark_column`.
+pub fn search_result_item(entry: &SshConfigEntry, query: &str, mark: Span<'static>, theme: &Theme) -> ListItem<'static> {
+    let Some(matched) = search::match_entry(entry, query) else {
+        return ListItem::new(Line::from(vec![mark, Span::raw(entry.label())]));
+    };
+
+    let mut spans = vec![mark];
+    spans.extend(highlight_search_matches(&entry.label(), &matched.positions(Field::Host), Style::default(), theme));
+    for field in [Field::HostName, Field::User, Field::Tag] {
+        let positions = matched.positions(field);
+        if positions.is_empty() {
+            continue;
+        }
+        let dim = Style::default().fg(theme.muted);
+        spans.push(Span::styled(format!("  {}:", field.name()), dim));
+        spans.extend(highlight_search_matches(&field.value(entry).unwrap_or_default(), &positions, dim, theme));
+    }
+
+    ListItem::new(Line::from(spans))
+}
+
+/// Renders the tag panel next to the host list, with the number of entries of each tag. The
+/// selected tag is highlighted while the panel has the focus.
+pub fn render_tag_panel(f: &mut Frame, area: layout::Rect, groups: &[TagGroup], selected: Option<usize>, theme: &Theme) {
+    let items = groups.iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let line = Line::from(vec![
+                Span::raw(tag_name(&group.tag).to_string()),
+                Span::styled(format!(" {}", group.entries.len()), Style::default().fg(theme.muted)),
+            ]);
+            let style = if Some(i) == selected {
+                Style::default().fg(theme.highlight).add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            ListItem::new(line).style(style)
+        })
+        .collect::<Vec<_>>();
+
+    let border = if selected.is_some() { theme.highlight } else { theme.border };
+    let list = List::new(items)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(border))
+            .title(" Tags ")
+            .title_style(Style::default().fg(border)));
 
-    for (start, _) in text.to_lowercase().match_indices(&query) {
-        // Add the text before the match
-        if start > last_end {
-            spans.push(Span::raw(text[last_end..start].to_string()));
+    f.render_widget(list, area);
+}
+
+/// Renders the prompt to move an entry to another tag: the tag being typed, followed by the
+/// existing tags to pick from.
+pub fn render_move_tag(f: &mut Frame, area: layout::Rect, entries: &[SshConfigEntry], app_mode: &AppMode, theme: &Theme) {
+    if let AppMode::MoveTag { index, tags, selected, input, message } = app_mode {
+        let label = entries.get(*index).map(SshConfigEntry::label).unwrap_or_default();
+
+        let mut lines = vec![
+            Line::from(vec![
+                Span::styled("Tag: ", Style::default().fg(theme.highlight)),
+                Span::raw(input.clone()),
+                Span::styled("█", Style::default().fg(theme.highlight)),
+            ]),
+            Line::from(""),
+        ];
+        for (i, tag) in tags.iter().enumerate() {
+            let style = if Some(i) == *selected {
+                Style::default().fg(theme.highlight).add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            lines.push(Line::from(Span::styled(format!("  {}", tag), style)));
         }
+        lines.push(Line::from(""));
+        lines.push(match message {
+            Some(message) => Line::from(Span::styled(message.clone(), Style::default().fg(theme.error))),
+            None => Line::from(Span::styled(
+                "↑/↓ pick a tag · empty removes the tag · Enter move · Esc cancel",
+                Style::default().fg(theme.muted),
+            )),
+        });
 
-        // Add the highlighted match
-        spans.push(Span::styled(
-            text[start..start + query.len()].to_string(),
-            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
-        ));
+        let paragraph = Paragraph::new(lines)
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border))
+                .title(format!(" Move {} to tag ", label))
+                .title_style(Style::default().fg(theme.border))
+                .style(Style::default().bg(theme.background)));
 
-        last_end = start + query.len();
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
     }
+}
+
+/// Renders the key inventory: each key with the hosts that use it, then the `IdentityFile`s that
+/// point to missing files. Weak keys and missing files are red, unused keys are grey.
+pub fn render_keys_table(f: &mut Frame, area: layout::Rect, entries: &[SshConfigEntry], report: &KeyReport, selected: usize, theme: &Theme) {
+    let label = |index: &usize| entries.get(*index).map(SshConfigEntry::label).unwrap_or_default();
+
+    let key_rows = report.keys.iter().map(|usage| {
+        let key = &usage.key;
+        let bits = key.bits.map_or(String::new(), |bits| format!(" {}", bits));
+        let name = key.path().file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let name = if key.comment.is_empty() { name } else { format!("{} ({})", name, key.comment) };
 
-    // Add any remaining text
-    if last_end < text.len() {
-        spans.push(Span::raw(text[last_end..].to_string()));
+        let (mut status, mut color) = if !usage.entries.is_empty() {
+            (usage.entries.iter().map(label).collect::<Vec<_>>().join(", "), theme.text)
+        } else if key.is_default_identity() {
+            ("default identity".to_string(), theme.text)
+        } else {
+            ("unused".to_string(), theme.muted)
+        };
+        if key.private_path.is_none() {
+            status.push_str(" · no private key");
+        }
+        if let Some(weakness) = key.weakness() {
+            status = format!("{} · {}", status, weakness);
+            color = theme.error;
+        }
+
+        (vec![
+            Cell::from(format!("{}{}", key.type_name(), bits)),
+            Cell::from(key.fingerprint.clone().unwrap_or_else(|| "encrypted".to_string())),
+            Cell::from(name),
+            Cell::from(status),
+        ], color)
+    });
+
+    let missing_rows = report.missing.iter().map(|missing| {
+        (vec![
+            Cell::from("missing"),
+            Cell::from(""),
+            Cell::from(missing.path.clone()),
+            Cell::from(label(&missing.entry)),
+        ], theme.error)
+    });
+
+    let rows = key_rows.chain(missing_rows)
+        .enumerate()
+        .map(|(i, (cells, color))| {
+            let style = Style::default().fg(color);
+            Row::new(cells).style(if i == selected { style.add_modifier(Modifier::REVERSED) } else { style })
+        })
+        .collect::<Vec<_>>();
+
+    let weak = report.keys.iter().filter(|usage| usage.key.weakness().is_some()).count();
+    let title = format!(
+        " Keys ({}, {} weak, {} missing) · Enter jump to host · Esc close ",
+        report.keys.len(), weak, report.missing.len(),
+    );
+
+    let table = Table::new(
+        rows,
+        &[
+            layout::Constraint::Length(14),
+            layout::Constraint::Length(52),
+            layout::Constraint::Percentage(30),
+            layout::Constraint::Min(20),
+        ],
+    )
+    .header(Row::new(vec!["Type", "Fingerprint", "File", "Used by"]).style(Style::default().fg(theme.highlight)))
+    .block(Block::default()
+        .title(Span::styled(title, Style::default().fg(theme.border).add_modifier(Modifier::BOLD)))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border))
+        .style(Style::default().bg(theme.background)));
+
+    f.render_widget(table, area);
+}
+
+/// Renders the lines of known_hosts with the entries they belong to. Stale lines, those of hosts
+/// of the config that ssh looks up under another name or port now, are yellow.
+pub fn render_known_hosts_table(f: &mut Frame, area: layout::Rect, entries: &[SshConfigEntry], app_mode: &AppMode, theme: &Theme) {
+    if let AppMode::KnownHosts { path, hosts, links, selected, deleting, message } = app_mode {
+        let rows = hosts.iter()
+            .zip(links)
+            .enumerate()
+            .map(|(i, (host, link))| {
+                let marker = match host.marker {
+                    Some(Marker::CertAuthority) => "@cert-authority",
+                    Some(Marker::Revoked) => "@revoked",
+                    None => "",
+                };
+                let (used_by, color) = if link.stale {
+                    ("stale".to_string(), theme.warning)
+                } else if link.entries.is_empty() {
+                    ("not in the config".to_string(), theme.muted)
+                } else {
+                    let labels = link.entries.iter().filter_map(|index| entries.get(*index)).map(SshConfigEntry::label);
+                    (labels.collect::<Vec<_>>().join(", "), if host.marker == Some(Marker::Revoked) { theme.error } else { theme.text })
+                };
+
+                let style = Style::default().fg(color);
+                Row::new(vec![
+                    Cell::from(host.line.to_string()),
+                    Cell::from(marker),
+                    Cell::from(host.names_text()),
+                    Cell::from(host.key_type.clone()),
+                    Cell::from(used_by),
+                ]).style(if i == *selected || deleting.as_ref().is_some_and(|lines| lines.contains(&i)) { style.add_modifier(Modifier::REVERSED) } else { style })
+            })
+            .collect::<Vec<_>>();
+
+        let stale = links.iter().filter(|link| link.stale).count();
+        let title = format!(" {} ({} lines, {} stale) ", path.display(), hosts.len(), stale);
+        let help = match (deleting, message) {
+            (Some(lines), _) => format!("Delete {} line(s)? Enter confirm · Esc cancel", lines.len()),
+            (None, Some(message)) => message.clone(),
+            (None, None) => "Enter jump to host · d delete line · D delete all stale · Esc close".to_string(),
+        };
+
+        let table = Table::new(
+            rows,
+            &[
+                layout::Constraint::Length(5),
+                layout::Constraint::Length(15),
+                layout::Constraint::Percentage(35),
+                layout::Constraint::Length(20),
+                layout::Constraint::Min(10),
+            ],
+        )
+        .header(Row::new(vec!["Line", "Marker", "Hosts", "Key type", "Config entry"]).style(Style::default().fg(theme.highlight)))
+        .block(Block::default()
+            .title(Span::styled(title, Style::default().fg(theme.border).add_modifier(Modifier::BOLD)))
+            .title_bottom(Span::styled(format!(" {} ", help), Style::default().fg(theme.muted)))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.border))
+            .style(Style::default().bg(theme.background)));
+
+        f.render_widget(table, area);
+    }
+}
+
+/// Renders the diff of the files that would be written, before writing them.
+pub fn render_review(f: &mut Frame, area: layout::Rect, app_mode: &AppMode, theme: &Theme) {
+    if let AppMode::Review { writes, scroll, quitting, message } = app_mode {
+        let chunks = layout::Layout::default()
+            .direction(layout::Direction::Vertical)
+            .constraints([
+                layout::Constraint::Min(3),     // Diff
+                layout::Constraint::Length(3),  // Keys and write status
+            ])
+            .split(area);
+
+        let mut lines = Vec::new();
+        for write in writes {
+            lines.push(Line::from(Span::styled(
+                format!("─── {}", write.path.display()),
+                Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD),
+            )));
+            for line in write.diff() {
+                lines.push(match line {
+                    DiffLine::Hunk(header) => Line::from(Span::styled(header, Style::default().fg(theme.accent))),
+                    DiffLine::Context(text) => Line::from(format!(" {}", text)),
+                    DiffLine::Removed(text) => Line::from(Span::styled(format!("-{}", text), Style::default().fg(theme.error))),
+                    DiffLine::Added(text) => Line::from(Span::styled(format!("+{}", text), Style::default().fg(theme.success))),
+                });
+            }
+            lines.push(Line::from(""));
+        }
+
+        let title = if *quitting { " Unsaved changes " } else { " Changes to write " };
+        let diff = Paragraph::new(lines)
+            .scroll((*scroll as u16, 0))
+            .block(Block::default()
+                .title(Span::styled(title, Style::default().fg(theme.popup).add_modifier(Modifier::BOLD)))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.popup))
+                .style(Style::default().bg(theme.background)))
+            .style(Style::default().fg(theme.text));
+        f.render_widget(diff, chunks[0]);
+
+        let status = match message {
+            Some(message) => Span::styled(message.clone(), Style::default().fg(theme.error)),
+            None if *quitting => Span::raw("Enter: write and quit   Q: quit without writing   Esc: back"),
+            None => Span::raw("Enter: write   ↑/↓: scroll   Esc: back"),
+        };
+        let paragraph = Paragraph::new(status)
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.popup)))
+            .style(Style::default().bg(theme.background).fg(theme.text));
+        f.render_widget(paragraph, chunks[1]);
+    }
+}
+
+/// Renders the bulk actions: the menu of actions, then the input the picked action needs, then the
+/// summary of what it will do.
+pub fn render_bulk(f: &mut Frame, area: layout::Rect, app_mode: &AppMode, theme: &Theme) {
+    if let AppMode::Bulk { marked, selected, action, input, summary, message } = app_mode {
+        let mut lines = Vec::new();
+        let hint = match (action, summary) {
+            (_, Some(summary)) => {
+                lines.extend(summary.iter().map(|line| Line::from(line.clone())));
+                "Enter apply · Esc back"
+            }
+            (Some(action), None) => {
+                lines.push(Line::from(vec![
+                    Span::styled(action.prompt().unwrap_or_default(), Style::default().fg(theme.highlight)),
+                    Span::raw(input.clone()),
+                    Span::styled("█", Style::default().fg(theme.highlight)),
+                ]));
+                "Enter show what will change · Esc back"
+            }
+            (None, None) => {
+                for (i, action) in BulkAction::ALL.iter().enumerate() {
+                    let style = if i == *selected {
+                        Style::default().fg(theme.highlight).add_modifier(Modifier::REVERSED)
+                    } else {
+                        Style::default().fg(theme.text)
+                    };
+                    lines.push(Line::from(Span::styled(format!("  {}", action.label()), style)));
+                }
+                "↑/↓ pick an action · Enter choose · Esc cancel"
+            }
+        };
+        lines.push(Line::from(""));
+        lines.push(match message {
+            Some(message) => Line::from(Span::styled(message.clone(), Style::default().fg(theme.error))),
+            None => Line::from(Span::styled(hint, Style::default().fg(theme.muted))),
+        });
+
+        let title = match marked.len() {
+            1 => " Bulk action on 1 marked entry ".to_string(),
+            n => format!(" Bulk action on {} marked entries ", n),
+        };
+        let paragraph = Paragraph::new(lines)
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.popup))
+                .title(Span::styled(title, Style::default().fg(theme.popup).add_modifier(Modifier::BOLD)))
+                .style(Style::default().bg(theme.background)))
+            .style(Style::default().fg(theme.text));
+
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+    }
+}
+
+/// Renders the new host form: the template on the first row, then each field with the reason its
+/// value is refused, if it is.
+pub fn render_new_host(f: &mut Frame, area: layout::Rect, app_mode: &AppMode, templates: &[Template], theme: &Theme) {
+    if let AppMode::NewHost { template, selected, values, message } = app_mode {
+        let row_style = |row: usize| if row == *selected {
+            Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.text)
+        };
+        let cursor = |row: usize| if row == *selected { "█" } else { "" };
+
+        let name = template.checked_sub(1)
+            .and_then(|i| templates.get(i))
+            .map_or("(none)", |template| template.name.as_str());
+        let template_row = if templates.is_empty() {
+            format!("{:<14}(none in the settings)", "Template")
+        } else {
+            format!("{:<14}◀ {} ▶", "Template", name)
+        };
+        let mut lines = vec![Line::from(Span::styled(template_row, row_style(0))), Line::from("")];
+
+        for (i, (field, value)) in FormField::ALL.iter().zip(values).enumerate() {
+            let mut spans = vec![
+                Span::styled(format!("{:<14}{}{}", field.label(), value, cursor(i + 1)), row_style(i + 1)),
+            ];
+            // An empty Host is only refused once the host is added
+            if let (false, Err(e)) = (value.is_empty(), field.validate(value.trim())) {
+                spans.push(Span::styled(format!("  ✗ {}", e), Style::default().fg(theme.error)));
+            }
+            lines.push(Line::from(spans));
+        }
+
+        lines.push(Line::from(""));
+        lines.push(match message {
+            Some(message) => Line::from(Span::styled(message.clone(), Style::default().fg(theme.error))),
+            None => Line::from(Span::styled(
+                "↑/↓ field · ←/→ template · Enter add · Esc cancel",
+                Style::default().fg(theme.muted),
+            )),
+        });
+
+        let paragraph = Paragraph::new(lines)
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.popup))
+                .title(Span::styled(" New host ", Style::default().fg(theme.popup).add_modifier(Modifier::BOLD)))
+                .style(Style::default().bg(theme.background)))
+            .style(Style::default().fg(theme.text));
+
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+    }
+}
+
+/// Renders the prompt shown when config files changed on disk while there are unsaved changes.
+pub fn render_conflict(f: &mut Frame, area: layout::Rect, changed: &[PathBuf], pending: usize, theme: &Theme) {
+    let mut lines = vec![Line::from(Span::styled("Changed on disk:", Style::default().fg(theme.warning)))];
+    lines.extend(changed.iter().map(|path| Line::from(format!("  {}", path.display()))));
+    lines.push(Line::from(""));
+    lines.push(Line::from(match pending {
+        1 => "There is 1 unsaved change in the TUI.".to_string(),
+        n => format!("There are {} unsaved changes in the TUI.", n),
+    }));
+    lines.push(Line::from(""));
+    for (key, description) in [
+        ("r", "Reload the files, dropping the unsaved changes"),
+        ("w", "Review the unsaved changes and write them over the files"),
+        ("k", "Keep the unsaved changes: writing them later undoes the changes made on disk, only the review shows them"),
+    ] {
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {}  ", key), Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)),
+            Span::raw(description),
+        ]));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.popup))
+            .title(Span::styled(" Config changed on disk ", Style::default().fg(theme.popup).add_modifier(Modifier::BOLD)))
+            .style(Style::default().bg(theme.background)))
+        .style(Style::default().fg(theme.text))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+/// Renders the profiles, the open one with a `●`.
+pub fn render_profiles(f: &mut Frame, area: layout::Rect, profiles: &[Profile], open: &Path, app_mode: &AppMode, theme: &Theme) {
+    if let AppMode::Profiles { selected, message } = app_mode {
+        let width = profiles.iter().map(|profile| profile.name.chars().count()).max().unwrap_or(0) + 2;
+        let mut lines = Vec::new();
+        for (i, profile) in profiles.iter().enumerate() {
+            let style = if i == *selected {
+                Style::default().fg(theme.highlight).add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            let marker = if profile.path == open { "● " } else { "  " };
+            lines.push(Line::from(vec![
+                Span::styled(marker, Style::default().fg(theme.accent)),
+                Span::styled(format!("{:<width$}{}", profile.name, profile.path.display(), width = width), style),
+            ]));
+        }
+        lines.push(Line::from(""));
+        lines.push(match message {
+            Some(message) => Line::from(Span::styled(message.clone(), Style::default().fg(theme.error))),
+            None => Line::from(Span::styled(
+                "↑/↓ pick · Enter open · d compare · Esc cancel",
+                Style::default().fg(theme.muted),
+            )),
+        });
+
+        let paragraph = Paragraph::new(lines)
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.popup))
+                .title(Span::styled(" Profiles ", Style::default().fg(theme.popup).add_modifier(Modifier::BOLD)))
+                .style(Style::default().bg(theme.background)))
+            .style(Style::default().fg(theme.text));
+
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
     }
+}
+
+/// Renders the entries that differ between the open config and a profile: `-` for the entries of
+/// the open config only, `+` for those of the profile only, and `~` with the options that differ.
+pub fn render_profile_diff(f: &mut Frame, area: layout::Rect, open: &Path, app_mode: &AppMode, theme: &Theme) {
+    if let AppMode::ProfileDiff { profile, diffs, scroll } = app_mode {
+        let mut lines = vec![
+            Line::from(Span::styled(format!("- {}", open.display()), Style::default().fg(theme.error))),
+            Line::from(Span::styled(format!("+ {} ({})", profile.path.display(), profile.name), Style::default().fg(theme.success))),
+            Line::from(""),
+        ];
+        if diffs.is_empty() {
+            lines.push(Line::from(Span::styled("Both configs have the same hosts and options", Style::default().fg(theme.muted))));
+        }
+        for diff in diffs {
+            match diff {
+                EntryDiff::OnlyLeft(label) => {
+                    lines.push(Line::from(Span::styled(format!("- {}", label), Style::default().fg(theme.error))));
+                }
+                EntryDiff::OnlyRight(label) => {
+                    lines.push(Line::from(Span::styled(format!("+ {}", label), Style::default().fg(theme.success))));
+                }
+                EntryDiff::Changed(label, options) => {
+                    lines.push(Line::from(Span::styled(format!("~ {}", label), Style::default().fg(theme.warning))));
+                    lines.extend(options.iter().map(|option| Line::from(format!("    {}", option))));
+                }
+            }
+        }
+
+        let paragraph = Paragraph::new(lines)
+            .scroll((*scroll as u16, 0))
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.popup))
+                .title(Span::styled(format!(" Compared with {} ", profile.name), Style::default().fg(theme.popup).add_modifier(Modifier::BOLD)))
+                .title_bottom(Span::styled(" ↑/↓ scroll · Esc back ", Style::default().fg(theme.muted)))
+                .style(Style::default().bg(theme.background)))
+            .style(Style::default().fg(theme.text));
+
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+    }
+}
+
+/// Renders the result of the last action and the number of changes that were not written yet.
+pub fn render_status_bar(f: &mut Frame, area: layout::Rect, message: Option<&str>, pending: usize, theme: &Theme) {
+    let style = Style::default().fg(theme.highlight);
+    let title = match pending {
+        0 => String::new(),
+        1 => " 1 unsaved change, w to write ".to_string(),
+        n => format!(" {} unsaved changes, w to write ", n),
+    };
+
+    let paragraph = Paragraph::new(message.unwrap_or_default().to_string())
+        .style(style)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .border_style(style)
+            .title(title)
+            .title_style(style));
+    f.render_widget(paragraph, area);
+}
+
+/// Renders the error banner in place of the status bar, with what resolves the error.
+pub fn render_error_banner(f: &mut Frame, area: layout::Rect, error: &Error, theme: &Theme) {
+    let style = Style::default().fg(theme.error);
+    let hint = if error.blocks_writes() {
+        " Nothing is written until it is read again "
+    } else {
+        " Esc to dismiss "
+    };
 
-    Text::from(vec![Line::from(spans)])
+    let paragraph = Paragraph::new(error.to_string())
+        .style(style)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .border_style(style)
+            .title(Span::styled(" Error ", style.add_modifier(Modifier::BOLD)))
+            .title_bottom(Span::styled(hint, Style::default().fg(theme.muted))));
+    f.render_widget(paragraph, area);
 }
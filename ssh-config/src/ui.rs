@@ -0,0 +1,414 @@
+//! This module draws the `App`, it only reads the state apart from the scroll of the host list
+
+use ratatui::{
+    layout,
+    style::{Modifier, Style},
+    text::Span,
+    widgets::{self, block, Block, Cell, Row, Table},
+    Frame,
+};
+use crate::app::{App, AppMode};
+use crate::config;
+use crate::settings::{Binding, KeyBindings, Theme};
+use crate::tui_utils::{self, render_bulk, render_conflict, render_edit_table, render_keys_table, render_known_hosts_table, render_lint_table, render_move_tag, render_new_host, render_popup_table, render_profile_diff, render_profiles, render_review, render_error_banner, render_status_bar, render_tag_panel};
+use crate::tag_tree::mark_column;
+use crate::update::TAG_PANEL_WIDTH;
+
+pub fn draw(f: &mut Frame, app: &mut App) {
+    let size = f.size();
+    let theme = app.settings.theme;
+
+    // Criar um layout com espaço para a barra de pesquisa na parte inferior
+    let chunks = layout::Layout::default()
+        .direction(layout::Direction::Vertical)
+        .margin(1)
+        .constraints([
+            layout::Constraint::Min(3),     // Lista principal
+            layout::Constraint::Length(3),  // Barra de pesquisa
+        ].as_ref())
+        .split(size);
+
+    // The tag panel goes on the left of the list when the entries have tags
+    let list_area = if app.tree.is_flat() {
+        chunks[0]
+    } else {
+        let columns = layout::Layout::default()
+            .direction(layout::Direction::Horizontal)
+            .constraints([
+                layout::Constraint::Length(TAG_PANEL_WIDTH),
+                layout::Constraint::Min(10),
+            ])
+            .split(chunks[0]);
+
+        let selected = match app.mode {
+            AppMode::Tags { selected } => Some(selected),
+            _ => None,
+        };
+        render_tag_panel(f, columns[0], app.tree.groups(), selected, &theme);
+        columns[1]
+    };
+
+    // Primeiro renderiza a lista
+    if !app.popup_open {
+        // Filtrar a lista se estiver no modo de busca
+        let items = match &app.mode {
+            // Ranked results, with the matched characters highlighted
+            AppMode::Search { matches, query, .. } if !query.is_empty() => matches.iter()
+                .map(|&idx| tui_utils::search_result_item(&app.entries[idx], query, mark_column(app.list_state.marked(), idx, &theme), &theme))
+                .collect::<Vec<_>>(),
+            _ => app.hosts.clone(),
+        };
+
+        // Resetar a seleção se não houver itens
+        if items.is_empty() {
+            app.list_state.select(0);
+        }
+
+        let title = match (app.list_state.marked().len(), app.settings.keys.keys(Binding::Bulk).first()) {
+            (0, _) => " SSH Hosts ".to_string(),
+            (n, Some(key)) => format!(" SSH Hosts ({} marked, {} for bulk actions) ", n, key),
+            (n, None) => format!(" SSH Hosts ({} marked) ", n),
+        };
+        // Another file than ~/.ssh/config is named, to know which one is edited, when it fits
+        let file = format!(" {} ", app.config_path.display());
+        let file = if app.config_path == config::default_path()
+            || title.chars().count() + file.chars().count() + 4 > list_area.width as usize
+        {
+            String::new()
+        } else {
+            file
+        };
+        let list = widgets::List::new(items)
+            .block(
+                Block::default()
+                    .borders(widgets::Borders::ALL)
+                    .border_style(Style::default().fg(theme.border))
+                    .title(title)
+                    .title_style(Style::default().fg(theme.border))
+                    .title(block::Title::from(file).alignment(layout::Alignment::Right)),
+            )
+            .highlight_symbol(">> ")
+            .highlight_style(Style::default().fg(theme.highlight));
+        f.render_stateful_widget(list, list_area, app.list_state.list_state());
+        app.list_state.sync_offset();
+
+        // An empty or missing config says how to start it
+        if app.entries.is_empty() && app.error.is_none() {
+            let hint = match app.settings.keys.keys(Binding::Add).first() {
+                Some(key) => format!("No hosts yet, {} to add one", key),
+                None => "No hosts yet".to_string(),
+            };
+            let inner = list_area.inner(layout::Margin { horizontal: 2, vertical: 1 });
+            f.render_widget(widgets::Paragraph::new(Span::styled(hint, Style::default().fg(theme.muted))), inner);
+        }
+    }
+
+    // Depois renderiza a barra de pesquisa na parte inferior
+    if app.mode.is_search() {
+        tui_utils::render_search_bar(f, chunks[1], &app.mode, &theme);
+    } else if let Some(error) = &app.error {
+        render_error_banner(f, chunks[1], error, &theme);
+    } else if app.status.is_some() || app.history.pending() > 0 {
+        render_status_bar(f, chunks[1], app.status.as_deref(), app.history.pending(), &theme);
+    }
+
+    // Atualiza o número máximo de itens visíveis
+    app.list_state.max_display_items = (chunks[0].height as usize).saturating_sub(2);
+
+    // Renderiza o popup se necessário
+    if app.popup_open {
+        let popup_area = layout::Rect::new(
+            size.width / 6,
+            size.height / 6,
+            4 * size.width / 6,
+            4 * size.height / 6,
+        );
+
+        match &app.mode {
+            AppMode::Help => render_help(f, size, &app.settings.keys, &app.connect_program, &theme),
+            AppMode::Edit { .. } => render_edit_table(f, popup_area, &app.mode, &theme),
+            AppMode::Lint { .. } => render_lint_table(f, popup_area, &app.mode, &theme),
+            AppMode::Keys { report, selected } => render_keys_table(f, popup_area, &app.entries, report, *selected, &theme),
+            AppMode::KnownHosts { .. } => render_known_hosts_table(f, popup_area, &app.entries, &app.mode, &theme),
+            AppMode::MoveTag { .. } => render_move_tag(f, popup_area, &app.entries, &app.mode, &theme),
+            AppMode::Review { .. } => render_review(f, popup_area, &app.mode, &theme),
+            AppMode::Bulk { .. } => render_bulk(f, popup_area, &app.mode, &theme),
+            AppMode::NewHost { .. } => render_new_host(f, popup_area, &app.mode, &app.settings.templates, &theme),
+            AppMode::Conflict { changed, .. } => render_conflict(f, popup_area, changed, app.history.pending(), &theme),
+            AppMode::Profiles { .. } => render_profiles(f, popup_area, &app.profiles, &app.config_path, &app.mode, &theme),
+            AppMode::ProfileDiff { .. } => render_profile_diff(f, popup_area, &app.config_path, &app.mode, &theme),
+            _ => {
+                if let Some(index) = app.selected_entry() {
+                    render_popup_table(f, popup_area, &app.entries, index, &theme);
+                }
+            }
+        }
+    }
+}
+
+/// Renders the list of the keys and what they do, as they are bound in the settings. Actions
+/// without keys are left out. `area` is the whole screen, the popup is centered in it.
+fn render_help(f: &mut Frame, area: layout::Rect, keys: &KeyBindings, connect_program: &str, theme: &Theme) {
+    let key_style = Style::default().fg(theme.text).add_modifier(Modifier::BOLD);
+    let connect = format!("Connect ({})", connect_program);
+    let commands = Binding::ALL.iter()
+        .filter(|binding| !keys.keys(**binding).is_empty())
+        .map(|binding| {
+            let combos = keys.keys(*binding).iter().map(ToString::to_string).collect::<Vec<_>>();
+            let description = match binding {
+                Binding::Connect => connect.clone(),
+                _ => binding.description().to_string(),
+            };
+            (combos.join(" "), description)
+        })
+        .collect::<Vec<_>>();
+    let key_width = commands.iter().map(|(key, _)| key.chars().count()).max().unwrap_or(0) + 2;
+
+    // The commands go in two columns when one is taller than the screen
+    let columns = if commands.len() + 5 > area.height as usize { 2 } else { 1 };
+    let per_column = commands.len().div_ceil(columns);
+    let cells = |(key, description): &(String, String)| vec![
+        Cell::from(Span::styled(format!("  {}", key), key_style)),
+        Cell::from(description.clone()),
+    ];
+
+    // Linha em branco, seguida dos comandos
+    let rows = std::iter::once(Row::new(vec![Cell::from(""), Cell::from("")]))
+        .chain((0..per_column).map(|i| {
+            Row::new((0..columns).filter_map(|column| commands.get(column * per_column + i)).flat_map(cells))
+        }))
+        .collect::<Vec<_>>();
+
+    // As tall as the commands need, the title and the borders, centered in the screen
+    let height = (rows.len() as u16 + 4).min(area.height);
+    let width = if columns == 1 { 4 * area.width / 6 } else { area.width.saturating_sub(2) };
+    let area = layout::Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    // Criar layout vertical para título e tabela
+    let help_layout = layout::Layout::default()
+        .direction(layout::Direction::Vertical)
+        .constraints([
+            layout::Constraint::Length(2),  // Espaço para o título
+            layout::Constraint::Min(10),    // Espaço para a tabela
+        ])
+        .split(area);
+
+    // Renderizar o título centralizado
+    let title = widgets::Paragraph::new(
+        Span::styled(" Available Commands ", Style::default().fg(theme.text).add_modifier(Modifier::BOLD))
+    )
+        .style(Style::default())
+        .alignment(layout::Alignment::Center);
+    f.render_widget(title, help_layout[0]);
+
+    // Renderizar a tabela
+    let help_block = Block::default()
+        .borders(widgets::Borders::ALL)
+        .border_style(Style::default().fg(theme.border));
+
+    // Largura das teclas mais longas, o resto do espaço para descrições
+    let widths = (0..columns)
+        .flat_map(|_| [layout::Constraint::Length(key_width as u16), layout::Constraint::Fill(1)])
+        .collect::<Vec<_>>();
+    let table = Table::new(rows, widths)
+        .block(help_block)
+        .style(Style::default())
+        .column_spacing(1);                 // Espaço entre colunas
+
+    f.render_widget(table, help_layout[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::{EntryKind, SshConfigEntry};
+    use crate::settings::Settings;
+    use crate::terminal_utils::TerminalManager;
+    use crate::update::{self, AppEvent};
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+    use ratatui::backend::TestBackend;
+    use std::path::PathBuf;
+
+    fn app(hosts: &[&str]) -> App {
+        let entries = hosts.iter()
+            .map(|host| SshConfigEntry::new(EntryKind::Host, host, None, "config".into()))
+            .collect();
+        App::new(entries, PathBuf::from("/nonexistent"), "ssh", Settings::default())
+    }
+
+    fn key(app: &mut App, code: KeyCode) {
+        update::update(app, AppEvent::Input(Event::Key(KeyEvent::new(code, KeyModifiers::NONE))));
+    }
+
+    fn click(app: &mut App, column: u16, row: u16) {
+        update::update(app, AppEvent::Input(Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })));
+    }
+
+    /// Draws the app on a terminal of the given size and returns the text of each line, without
+    /// the trailing spaces.
+    fn screen(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = TerminalManager::with_backend(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| draw(f, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| (0..width).map(|x| buffer.get(x, y).symbol()).collect::<String>().trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn the_selection_moves_with_the_arrows() {
+        let mut app = app(&["web", "db", "cache"]);
+        key(&mut app, KeyCode::Down);
+        key(&mut app, KeyCode::Char('j'));
+
+        assert_eq!(screen(&mut app, 30, 10), [
+            "",
+            " ┌ SSH Hosts ───────────────┐",
+            " │   web                    │",
+            " │   db                     │",
+            " │>> cache                  │",
+            " └──────────────────────────┘",
+            "", "", "", "",
+        ]);
+    }
+
+    #[test]
+    fn the_list_scrolls_to_the_selection() {
+        let mut app = app(&["web", "db", "cache", "mail", "git", "vpn"]);
+        screen(&mut app, 30, 10);
+
+        key(&mut app, KeyCode::End);
+        assert_eq!(screen(&mut app, 30, 10)[2..5], [
+            " │   mail                   │",
+            " │   git                    │",
+            " │>> vpn                    │",
+        ]);
+        assert_eq!(app.list_state.scroll_offset, 3);
+
+        // Going up scrolls only once the selection leaves the top of the list
+        key(&mut app, KeyCode::Up);
+        key(&mut app, KeyCode::Up);
+        assert_eq!(screen(&mut app, 30, 10)[2..5], [
+            " │>> mail                   │",
+            " │   git                    │",
+            " │   vpn                    │",
+        ]);
+        key(&mut app, KeyCode::Up);
+        assert_eq!(screen(&mut app, 30, 10)[2..5], [
+            " │>> cache                  │",
+            " │   mail                   │",
+            " │   git                    │",
+        ]);
+        assert_eq!(app.list_state.scroll_offset, 2);
+
+        // Wrapping around from the top goes back to the end
+        key(&mut app, KeyCode::Home);
+        key(&mut app, KeyCode::Up);
+        assert_eq!(screen(&mut app, 30, 10)[2..5], [
+            " │   mail                   │",
+            " │   git                    │",
+            " │>> vpn                    │",
+        ]);
+    }
+
+    #[test]
+    fn the_search_filters_the_list() {
+        let mut app = app(&["web", "db", "cache", "mail", "git", "vpn"]);
+        key(&mut app, KeyCode::End);
+        screen(&mut app, 30, 10);
+
+        key(&mut app, KeyCode::Char('/'));
+        key(&mut app, KeyCode::Char('a'));
+        assert_eq!(screen(&mut app, 30, 10), [
+            "",
+            " ┌ SSH Hosts ───────────────┐",
+            " │>> cache                  │",
+            " │   mail                   │",
+            " │                          │",
+            " └──────────────────────────┘",
+            " ┌ Search (2 matches) ──────┐",
+            " │a                         │",
+            " └──────────────────────────┘",
+            "",
+        ]);
+    }
+
+    #[test]
+    fn a_click_selects_the_row_under_the_mouse() {
+        let mut app = app(&["web", "db", "cache", "mail", "git", "vpn"]);
+        key(&mut app, KeyCode::End);
+        screen(&mut app, 30, 10);
+
+        // The list is scrolled, the second row shows the fifth host
+        click(&mut app, 5, 3);
+        assert_eq!(app.list_state.get_index(), 4);
+        assert_eq!(screen(&mut app, 30, 10)[2..5], [
+            " │   mail                   │",
+            " │>> git                    │",
+            " │   vpn                    │",
+        ]);
+
+        // Below the last search result there is nothing to select
+        key(&mut app, KeyCode::Char('/'));
+        key(&mut app, KeyCode::Char('a'));
+        screen(&mut app, 30, 10);
+        click(&mut app, 5, 4);
+        assert_eq!(app.list_state.get_index(), 0);
+        click(&mut app, 5, 3);
+        assert_eq!(app.list_state.get_index(), 1);
+    }
+
+    #[test]
+    fn the_help_lists_the_bound_keys() {
+        let mut app = app(&["web"]);
+        app.settings = crate::settings::parse("[keys]\nup = [\"Up\", \"k\"]\nkeys = \"i\"").unwrap();
+        key(&mut app, KeyCode::Char('h'));
+
+        let lines = screen(&mut app, 80, 40);
+        assert!(lines.iter().any(|line| line.contains("Up k      Previous host")));
+        assert!(lines.iter().any(|line| line.contains("i         SSH keys and the hosts that use them")));
+        assert!(lines.iter().any(|line| line.contains("Connect (ssh)")));
+    }
+
+    #[test]
+    fn enter_shows_the_options_of_the_host() {
+        let mut app = app(&["web"]);
+        app.entries[0].add_option("HostName".to_string(), "web.example.com".to_string());
+        key(&mut app, KeyCode::Enter);
+
+        let lines = screen(&mut app, 120, 24);
+        assert!(lines.iter().any(|line| line.contains("┌ web ")));
+        assert!(lines.iter().any(|line| line.contains("HostName") && line.contains("web.example.com")));
+        assert!(lines.iter().any(|line| line.contains("Effective for web")));
+    }
+
+    #[test]
+    fn an_empty_config_says_how_to_add_a_host_and_errors_get_a_banner() {
+        let mut app = app(&[]);
+        assert_eq!(screen(&mut app, 50, 8)[1..4], [
+            " ┌ SSH Hosts ────────────── /nonexistent/config ┐",
+            " │ No hosts yet, a to add one                   │",
+            " └──────────────────────────────────────────────┘",
+        ]);
+
+        let e = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        app.error = Some(crate::error::Error::io(std::path::Path::new("/srv/config"), e));
+        assert_eq!(screen(&mut app, 50, 8)[2..7], [
+            " │                                              │",
+            " └──────────────────────────────────────────────┘",
+            " ┌ Error ───────────────────────────────────────┐",
+            " │/srv/config: permission denied                │",
+            " └ Nothing is written until it is read again ───┘",
+        ]);
+    }
+}
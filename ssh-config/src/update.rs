@@ -0,0 +1,1821 @@
+//! This module has the state machine of the TUI
+//!
+//! Every input, reachability result and signal arrives as an `AppEvent` and goes through `update`,
+//! which turns keys into `Action`s for the current mode and applies them to the `App`. What has to
+//! happen outside of the state, like handing the terminal to ssh or reading a file, is returned as
+//! `Command`s. The files are read and written by `effects`, their result is the next event.
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEventKind};
+use std::path::{Path, PathBuf};
+use crate::app::{App, AppMode, EditField};
+use crate::bulk::{self, BulkAction};
+use crate::config;
+use crate::connect;
+use crate::entry::{self, EntryKind, SshConfigEntry};
+use crate::error::Error;
+use crate::history::{Change, PendingWrite};
+use crate::host_form::{self, FormField};
+use crate::keys::KeyReport;
+use crate::known_hosts::{self, KnownHost};
+use crate::lint;
+use crate::profile;
+use crate::reachability::{self, Status, Target};
+use crate::search;
+use crate::settings::{Binding, KeyBindings, Profile, Template};
+use crate::tag_tree::{self, TagGroup};
+use crate::watch::{self, Changed};
+
+/// Width of the tag panel on the left of the host list
+pub const TAG_PANEL_WIDTH: u16 = 24;
+
+/// Row of the first item of the host list, below the margin and the frame
+const LIST_START: u16 = 2;
+
+/// Something that happened, for `update` to handle
+#[derive(Debug)]
+pub enum AppEvent {
+    Input(Event),
+    Reachability(String, Status),  // Result of checking the host with the given Host line
+    ConfigChanged(Vec<PathBuf>),   // Config files changed on disk
+    Interrupt,                     // Ctrl+C
+    // The connect command exited, with why it could not run and the terminal errors around it
+    Connected { alias: String, connected: Result<(), String>, terminal: Option<Error> },
+    // Results of `Command::Io`
+    Reviewed { writes: Vec<PendingWrite>, quitting: bool },
+    Written { written: Result<usize, String>, quitting: bool },  // Number of files written
+    Exported(Result<String, String>),
+    Summary(Result<Vec<String>, String>),  // What the bulk action will do
+    Linted(Vec<lint::Diagnostic>),
+    Keys(KeyReport),
+    KnownHosts { path: PathBuf, hosts: Result<Vec<KnownHost>, String>, message: Option<String> },
+    Reloaded { changed: Vec<PathBuf>, entries: Result<Vec<SshConfigEntry>, Error> },
+    Profile { profile: Profile, diff: bool, entries: Result<Vec<SshConfigEntry>, Error> },
+}
+
+impl From<(String, Status)> for AppEvent {
+    fn from((key, status): (String, Status)) -> Self {
+        AppEvent::Reachability(key, status)
+    }
+}
+
+impl From<Changed> for AppEvent {
+    fn from(Changed(paths): Changed) -> Self {
+        AppEvent::ConfigChanged(paths)
+    }
+}
+
+/// Work left to the caller of `update`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Quit,
+    Connect(String),                   // Run the connect command for the alias
+    CheckReachability(Vec<Target>),
+    Watch(Vec<PathBuf>),               // Watch these config files instead of the ones watched
+    Io(Io),                            // Read or write files, see `effects::run`
+}
+
+/// Reads and writes of files, each gives back an `AppEvent` with its result
+#[derive(Debug, Clone, PartialEq)]
+pub enum Io {
+    Review { quitting: bool },                       // Render the files the changes touch
+    Write { writes: Vec<PendingWrite>, quitting: bool },
+    Export { marked: Vec<usize>, input: String },    // Write the marked entries to a file
+    Summary { marked: Vec<usize>, action: BulkAction, input: String },  // Describe a bulk action
+    Lint,                                            // Check the entries, IdentityFile paths included
+    Keys,                                            // Read the keys of the ssh directory
+    KnownHosts { delete: Vec<KnownHost> },           // Delete these lines, then read known_hosts
+    Reload(Vec<PathBuf>),                            // Read the config after these files changed
+    Profile { profile: Profile, diff: bool },        // Read a config to open it or compare it
+}
+
+/// What a key does, depending on the mode it was pressed in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    UpdateSelection(usize),
+    Exit,       // Quit, after reviewing the unsaved changes if there are any
+    Search,     // Search event to enter the search mode
+    Normal,     // Normal event to enter the normal mode
+    Popup,      // Open popup with the content of selected entry
+    Help,       // Show help popup
+    Edit,       // Enter edit mode for the selected entry
+    SaveEdit,   // Apply the changes made to the entry being edited
+    Lint,       // Check the entries and show the diagnostics
+    Jump(usize),  // Back to normal mode with the given entry selected
+    Open(usize),  // Jump to the given entry and open the popup with its content
+    Connect,    // Run the connect command (ssh) for the selected entry
+    Collapse(bool),  // Collapse (true) or expand (false) the tag group of the selected row
+    ToggleGroup,     // Collapse or expand the tag group whose header is selected
+    Tags,       // Move the focus to the tag panel
+    JumpTag(Option<String>),  // Back to normal mode with the header of the given tag selected
+    MoveTag,    // Ask for the tag to move the selected entry to
+    SaveMove,   // Move the entry to the tag that was typed
+    Keys,       // Show the SSH keys and the hosts that use them
+    KnownHosts, // Show the lines of known_hosts and the hosts they belong to
+    DeleteKnownHosts,  // Delete the known_hosts lines that were confirmed
+    CheckReachability,  // Check again whether the hosts are reachable
+    Delete,     // Delete the selected entry
+    Undo,       // Revert the last change that was not written yet
+    Redo,       // Apply again the last change that was undone
+    Review,     // Show the diff of the files the changes would write
+    WriteChanges,  // Write the changes to the config files
+    Discard,    // Quit without writing the changes
+    ToggleMark(usize),  // Mark or unmark the given entry
+    MarkMatches,  // Mark the search results, or every entry of the list
+    ClearMarks,   // Unmark every entry
+    Bulk,         // Show the bulk actions for the marked entries
+    Summarize,    // Show what the picked bulk action will do, to confirm it
+    ApplyBulk,    // Apply the bulk action that was confirmed
+    NewHost,      // Show the form of a new host
+    AddHost,      // Add the host of the form
+    Reload,       // Drop the unsaved changes for the entries that changed on disk
+    Profiles,     // Show the config files that can be opened
+    OpenProfile(usize),  // Open the config file of the given profile
+    DiffProfile(usize),  // Compare the open config with the given profile
+}
+
+/// Applies an event to the state.
+///
+/// # Returns
+/// The commands the caller has to run, in order.
+pub fn update(app: &mut App, event: AppEvent) -> Vec<Command> {
+    let mut commands = Vec::new();
+    match event {
+        AppEvent::Input(event) => {
+            if let Some(action) = input_action(app, &event) {
+                log::debug!("{:?} -> {:?}", event, action);
+                perform(app, action, &mut commands);
+            }
+        }
+        AppEvent::Reachability(key, status) => {
+            app.statuses.insert(key, status);
+            app.refresh_hosts();
+        }
+        AppEvent::ConfigChanged(paths) => wait_for_popup(app, paths),
+        AppEvent::Interrupt => {
+            if app.history.pending() > 0 {
+                log::info!("Ctrl+C pressed, {} unsaved changes are lost", app.history.pending());
+            } else {
+                log::info!("Ctrl+C pressed, exiting");
+            }
+            commands.push(Command::Quit);
+        }
+        AppEvent::Reviewed { writes, quitting } => {
+            if writes.is_empty() && !quitting {
+                app.status = Some("No changes to write".to_string());
+            } else {
+                app.mode = AppMode::Review { writes, scroll: 0, quitting, message: None };
+                app.popup_open = true;
+            }
+        }
+        AppEvent::Written { written, quitting } => written_changes(app, written, quitting, &mut commands),
+        AppEvent::Connected { alias, connected, terminal } => {
+            if let Err(e) = connected {
+                log::error!("Failed to connect to {}: {}", alias, e);
+                app.status = Some(format!("Failed to connect to {}: {}", alias, e));
+            }
+            if let Some(e) = terminal {
+                app.error = Some(e);
+            }
+        }
+        AppEvent::Exported(exported) => applied_bulk(app, exported),
+        AppEvent::Summary(summarized) => {
+            if let AppMode::Bulk { summary, message, .. } = &mut app.mode {
+                match summarized {
+                    Ok(lines) => {
+                        *summary = Some(lines);
+                        *message = None;
+                    }
+                    Err(e) => *message = Some(e),
+                }
+            }
+        }
+        AppEvent::Linted(diagnostics) => {
+            app.mode = AppMode::Lint { diagnostics, selected: 0 };
+            app.popup_open = true;
+        }
+        AppEvent::Keys(report) => {
+            app.mode = AppMode::Keys { report, selected: 0 };
+            app.popup_open = true;
+        }
+        AppEvent::KnownHosts { path, hosts, message } => {
+            // Keep the selection near the deleted lines
+            let previous = match app.mode {
+                AppMode::KnownHosts { selected, .. } => selected,
+                _ => 0,
+            };
+            let (hosts, message) = match hosts {
+                Ok(hosts) => (hosts, message),
+                Err(e) => (Vec::new(), Some(e)),
+            };
+            let links = known_hosts::link_entries(&hosts, &app.entries);
+            let selected = previous.min(hosts.len().saturating_sub(1));
+            app.mode = AppMode::KnownHosts { path, hosts, links, selected, deleting: None, message };
+            app.popup_open = true;
+        }
+        AppEvent::Reloaded { changed, entries } => reload(app, &changed, entries, &mut commands),
+        AppEvent::Profile { profile, diff, entries } => read_profile(app, profile, diff, entries, &mut commands),
+    }
+
+    // The files that changed while a popup was open are read once it is closed
+    if !app.changed_on_disk.is_empty() && !keeps_popup(&app.mode) {
+        commands.push(Command::Io(Io::Reload(std::mem::take(&mut app.changed_on_disk))));
+    }
+    commands
+}
+
+/// Whether the mode works on a copy of the entries or on indices into them, like a form being
+/// typed into or a review. Reloading the entries would close it, so a reload waits for it.
+fn keeps_popup(mode: &AppMode) -> bool {
+    !matches!(mode, AppMode::Normal | AppMode::Help | AppMode::Search { .. } | AppMode::Tags { .. })
+}
+
+/// Remembers the files that changed on disk, they are read once no popup needs the entries.
+fn wait_for_popup(app: &mut App, paths: Vec<PathBuf>) {
+    for path in paths {
+        if !app.changed_on_disk.contains(&path) {
+            app.changed_on_disk.push(path);
+        }
+    }
+    if keeps_popup(&app.mode) {
+        let names = app.changed_on_disk.iter().map(|path| display_path(path)).collect::<Vec<_>>();
+        app.status = Some(format!("{} changed on disk, read again once this is closed", names.join(", ")));
+    }
+}
+
+/// The action of an input in the current mode. Keys typed into a field are handled here, they
+/// change the mode without an action.
+fn input_action(app: &mut App, event: &Event) -> Option<Action> {
+    let key = match event {
+        Event::Key(key) => *key,
+        Event::Mouse(mouse) if matches!(mouse.kind, MouseEventKind::Down(_)) => {
+            return mouse_action(app, mouse.column, mouse.row);
+        }
+        _ => return None,
+    };
+
+    // The popups move, confirm and close with the keys of the host list
+    let keys = &app.settings.keys;
+    match &mut app.mode {
+        AppMode::Edit { .. } => handle_edit_mode(key, &mut app.mode, keys),
+        // The diagnostics and keys panels have their own selection
+        AppMode::Lint { .. } | AppMode::Keys { .. } => handle_lint_mode(key, &mut app.mode, keys),
+        AppMode::Tags { .. } => handle_tags_mode(key, &mut app.mode, keys, app.tree.groups()),
+        AppMode::KnownHosts { .. } => handle_known_hosts_mode(key, &mut app.mode, keys),
+        AppMode::MoveTag { .. } => handle_move_tag_mode(key, &mut app.mode),
+        AppMode::Review { .. } => handle_review_mode(key, &mut app.mode, keys),
+        AppMode::Bulk { .. } => handle_bulk_mode(key, &mut app.mode, keys),
+        AppMode::NewHost { .. } => handle_new_host_mode(key, &mut app.mode, &app.settings.templates),
+        AppMode::Conflict { .. } => handle_conflict_mode(key, keys),
+        AppMode::Profiles { .. } => handle_profiles_mode(key, &mut app.mode, keys, app.profiles.len()),
+        AppMode::ProfileDiff { .. } => handle_profile_diff_mode(key, &mut app.mode, keys),
+        // Characters are typed into the query, the other keys work on the results
+        AppMode::Search { .. } if is_typing(&key) && !marks(&key, keys.binding(&key)) => {
+            handle_search_mode(key, &mut app.mode, &app.entries)
+        }
+        _ => normal_action(app, key),
+    }
+}
+
+/// Whether a key types text, rather than being a shortcut with Ctrl or Alt.
+fn is_typing(key: &KeyEvent) -> bool {
+    let shortcut = key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+    matches!(key.code, KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Esc) && !shortcut
+}
+
+/// Whether a key marks entries rather than being typed into the search: Space and `*` by default.
+/// Letters and digits are always typed.
+fn marks(key: &KeyEvent, binding: Option<Binding>) -> bool {
+    let is_alphanumeric = matches!(key.code, KeyCode::Char(c) if c.is_alphanumeric());
+    matches!(binding, Some(Binding::Mark | Binding::MarkMatches)) && !is_alphanumeric
+}
+
+/// Moves the selection of a popup list of `rows` rows with the Down, Up, First and Last keys.
+///
+/// # Returns
+/// Whether the key is one of them.
+fn navigate(binding: Option<Binding>, selected: &mut usize, rows: usize) -> bool {
+    let next = match binding {
+        Some(Binding::Down) => (*selected + 1) % rows.max(1),
+        Some(Binding::Up) => selected.checked_sub(1).unwrap_or(rows.saturating_sub(1)),
+        Some(Binding::First) => 0,
+        Some(Binding::Last) => rows.saturating_sub(1),
+        _ => return false,
+    };
+    *selected = next;
+    true
+}
+
+/// Scrolls a diff with the Down, Up and First keys and Page Up/Down.
+///
+/// # Returns
+/// Whether the key is one of them.
+fn scroll(key: &KeyEvent, binding: Option<Binding>, scroll: &mut usize) -> bool {
+    match (binding, key.code) {
+        (Some(Binding::Down), _) => *scroll += 1,
+        (Some(Binding::Up), _) => *scroll = scroll.saturating_sub(1),
+        (Some(Binding::First), _) => *scroll = 0,
+        (_, KeyCode::PageDown) => *scroll += 10,
+        (_, KeyCode::PageUp) => *scroll = scroll.saturating_sub(10),
+        _ => return false,
+    }
+    true
+}
+
+/// Whether the key is a character typed into a field, rather than a key of a popup.
+fn typed_char(key: &KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => Some(c),
+        _ => None,
+    }
+}
+
+/// The action of a key on the host list, while browsing or on the results of a search. The keys
+/// come from the settings.
+fn normal_action(app: &App, key: KeyEvent) -> Option<Action> {
+    let row = app.list_state.get_index();
+    let rows = app.visible_rows();
+
+    let binding = app.settings.keys.binding(&key)?;
+    match binding {
+        Binding::Down if rows > 0 => Some(Action::UpdateSelection(if row + 1 >= rows { 0 } else { row + 1 })),
+        Binding::Up if rows > 0 => Some(Action::UpdateSelection(if row == 0 { rows - 1 } else { row - 1 })),
+        Binding::First if rows > 0 => Some(Action::UpdateSelection(0)),
+        Binding::Last if rows > 0 => Some(Action::UpdateSelection(rows - 1)),
+        Binding::Down | Binding::Up | Binding::First | Binding::Last => None,
+        Binding::Open => {
+            // In search mode the rows are the matches, otherwise the rows of the tag tree
+            match &app.mode {
+                AppMode::Search { matches, query, .. } if !query.is_empty() => {
+                    matches.get(row).map(|&index| Action::Open(index))
+                }
+                _ if rows == 0 => None,
+                _ if app.tree.entry_at(row).is_none() => Some(Action::ToggleGroup),
+                _ => Some(Action::Popup),
+            }
+        }
+        Binding::Search => Some(Action::Search),
+        // Esc unmarks the entries before anything else
+        Binding::Back if !app.list_state.marked().is_empty() && !app.popup_open && !app.mode.is_search() => {
+            Some(Action::ClearMarks)
+        }
+        Binding::Back => Some(Action::Normal),
+        Binding::Quit => Some(Action::Exit),
+        Binding::Help => Some(Action::Help),
+        Binding::Edit => Some(Action::Edit),
+        Binding::Connect => Some(Action::Connect),
+        Binding::Lint => Some(Action::Lint),
+        Binding::Tags => Some(Action::Tags),
+        Binding::MoveTag => Some(Action::MoveTag),
+        Binding::Keys => Some(Action::Keys),
+        Binding::KnownHosts => Some(Action::KnownHosts),
+        Binding::CheckReachability => Some(Action::CheckReachability),
+        Binding::Delete => Some(Action::Delete),
+        Binding::Undo => Some(Action::Undo),
+        Binding::Redo => Some(Action::Redo),
+        Binding::Write => Some(Action::Review),
+        Binding::Mark => match &app.mode {
+            AppMode::Search { matches, query, .. } if !query.is_empty() => matches.get(row).copied(),
+            _ => app.tree.entry_at(row),
+        }.map(Action::ToggleMark),
+        Binding::MarkMatches => Some(Action::MarkMatches),
+        Binding::Bulk => Some(Action::Bulk),
+        Binding::Add => Some(Action::NewHost),
+        Binding::Profiles => Some(Action::Profiles),
+        Binding::Collapse => Some(Action::Collapse(true)),
+        Binding::Expand => Some(Action::Collapse(false)),
+        Binding::NextGroup | Binding::PreviousGroup => {
+            app.tree.next_group_row(row, binding == Binding::NextGroup).map(Action::UpdateSelection)
+        }
+    }
+}
+
+/// The action of a click: a tag of the tag panel jumps to it, a row of the list selects it.
+fn mouse_action(app: &App, column: u16, row: u16) -> Option<Action> {
+    let line = (row as usize).checked_sub(LIST_START as usize)?;
+
+    if !app.tree.is_flat() && column < 1 + TAG_PANEL_WIDTH {
+        return app.tree.groups().get(line).map(|group| Action::JumpTag(group.tag.clone()));
+    }
+    let index = line + app.list_state.scroll_offset;
+    if app.popup_open || index >= app.visible_rows() {
+        return None;
+    }
+    log::debug!("index = {:?}, scroll offset = {:?}", index, app.list_state.scroll_offset);
+    Some(Action::UpdateSelection(index))
+}
+
+/// Applies an action to the state, adding the commands it needs to `commands`.
+fn perform(app: &mut App, action: Action, commands: &mut Vec<Command>) {
+    match action {
+        Action::UpdateSelection(index) => {
+            log::debug!("Updating selection to index: {}", index);
+            app.list_state.select(index);
+        }
+        Action::Popup => {
+            log::info!("Open a popup with the entry.");
+            app.popup_open = true;
+        }
+        Action::Search => {
+            log::info!("Entering search mode.");
+            app.mode = AppMode::Search {
+                query: String::new(),
+                cursor_position: 0,
+                matches: (0..app.entries.len()).collect(), // Inicialmente, todos os itens são matches
+            };
+        }
+        Action::Normal => {
+            log::info!("Entering normal mode.");
+            // Esc on the list dismisses a terminal error, a config that was not read stays reported
+            if matches!(app.mode, AppMode::Normal) && !app.popup_open && !app.error.as_ref().is_some_and(Error::blocks_writes) {
+                app.error = None;
+            }
+            app.mode = AppMode::Normal;
+            app.popup_open = false;
+            app.clamp_selection();
+        }
+        Action::Exit if app.history.pending() > 0 => {
+            // The changes are shown before quitting, so that they are not lost by mistake
+            log::info!("Exit requested with {} unsaved changes.", app.history.pending());
+            commands.push(Command::Io(Io::Review { quitting: true }));
+        }
+        Action::Exit => {
+            log::info!("Exit signal received.");
+            commands.push(Command::Quit);
+        }
+        Action::Help => {
+            log::info!("Showing help popup.");
+            app.mode = AppMode::Help;
+            app.popup_open = true;
+        }
+        Action::Edit => {
+            log::info!("Entering edit mode.");
+            if let Some(index) = app.selected_entry() {
+                app.mode = AppMode::Edit {
+                    index,
+                    entry: app.entries[index].clone(),
+                    selected: 0,
+                    field: None,
+                    input: String::new(),
+                    message: None,
+                };
+                app.popup_open = true;
+            }
+        }
+        Action::SaveEdit => {
+            if let AppMode::Edit { index, entry, message, .. } = &mut app.mode {
+                log::info!("Applying the changes to {}", entry.label());
+                *message = Some(match edit_change(&app.entries, *index, entry) {
+                    Ok(change) => {
+                        app.history.apply(&mut app.entries, change);
+                        "Applied, w shows the changes and writes them".to_string()
+                    }
+                    Err(e) => {
+                        log::error!("Failed to apply the changes: {}", e);
+                        e
+                    }
+                });
+            }
+            // Refresh the hosts list in case the host was renamed
+            app.refresh_hosts();
+        }
+        Action::Lint => {
+            log::info!("Showing lint diagnostics.");
+            commands.push(Command::Io(Io::Lint));
+        }
+        Action::Jump(index) => {
+            log::info!("Jumping to entry {}", index);
+            app.mode = AppMode::Normal;
+            // The group of the entry is expanded so that its row can be selected
+            let row = app.tree.entry_row(index);
+            app.refresh_hosts();
+            app.list_state.select(row.unwrap_or(0));
+            app.popup_open = false;
+        }
+        Action::Open(index) => {
+            perform(app, Action::Jump(index), commands);
+            perform(app, Action::Popup, commands);
+        }
+        Action::Collapse(_) | Action::ToggleGroup if !app.popup_open => {
+            let row = app.list_state.get_index();
+            let Some(tag) = app.tree.group_at(row) else {
+                return;
+            };
+            let collapsed = match action {
+                Action::Collapse(collapsed) => collapsed,
+                _ => !app.tree.is_collapsed(&tag),
+            };
+            app.tree.set_collapsed(&tag, collapsed);
+            let header = app.tree.group_row(&tag);
+            app.refresh_hosts();
+
+            // Collapsing hides the selected entry, its header gets the selection
+            if let (Some(header), true) = (header, collapsed) {
+                app.list_state.select(header);
+            }
+        }
+        Action::Tags if !app.tree.is_flat() => {
+            log::info!("Focusing the tag panel.");
+            // Start from the tag of the selected row
+            let selected = app.tree.group_at(app.list_state.get_index())
+                .and_then(|tag| app.tree.groups().iter().position(|group| group.tag == tag))
+                .unwrap_or(0);
+            app.mode = AppMode::Tags { selected };
+        }
+        Action::JumpTag(tag) => {
+            log::info!("Jumping to tag {}", tag_tree::tag_name(&tag));
+            app.mode = AppMode::Normal;
+            app.tree.set_collapsed(&tag, false);
+            let row = app.tree.group_row(&tag);
+            app.refresh_hosts();
+            app.list_state.select(row.unwrap_or(0));
+            app.popup_open = false;
+        }
+        Action::Keys => {
+            log::info!("Showing the key inventory.");
+            commands.push(Command::Io(Io::Keys));
+        }
+        Action::CheckReachability => {
+            let (targets, skipped) = reachability::targets(&app.entries);
+            log::info!("Checking the reachability of {} hosts.", targets.len());
+
+            app.statuses = skipped.into_iter()
+                .chain(targets.iter().map(|target| (target.key.clone(), Status::Checking)))
+                .collect();
+            app.refresh_hosts();
+            commands.push(Command::CheckReachability(targets));
+        }
+        Action::KnownHosts => {
+            log::info!("Showing {}", app.ssh_dir.join("known_hosts").display());
+            commands.push(Command::Io(Io::KnownHosts { delete: Vec::new() }));
+        }
+        Action::DeleteKnownHosts => {
+            let AppMode::KnownHosts { hosts, deleting: Some(deleting), .. } = &app.mode else {
+                return;
+            };
+            let delete = deleting.iter().filter_map(|&i| hosts.get(i)).cloned().collect::<Vec<_>>();
+            if !delete.is_empty() {
+                commands.push(Command::Io(Io::KnownHosts { delete }));
+            }
+        }
+        Action::MoveTag => {
+            // The global options have to stay at the top of the file, they cannot be moved
+            let Some(index) = app.selected_entry().filter(|&index| app.entries[index].kind != EntryKind::Global) else {
+                return;
+            };
+            let current = app.entries[index].tag.clone();
+            let tags = app.tree.groups().iter().filter_map(|group| group.tag.clone()).collect::<Vec<_>>();
+            app.mode = AppMode::MoveTag {
+                index,
+                selected: tags.iter().position(|tag| Some(tag) == current.as_ref()),
+                tags,
+                input: current.unwrap_or_default(),
+                message: None,
+            };
+            app.popup_open = true;
+        }
+        Action::SaveMove => {
+            let AppMode::MoveTag { index, input, message, .. } = &mut app.mode else {
+                return;
+            };
+            match config::move_change(&app.entries, *index, input.trim()) {
+                Ok((change, new_index)) => {
+                    app.history.apply(&mut app.entries, change);
+                    app.list_state.clear_marks();
+                    app.refresh_hosts();
+                    perform(app, Action::Jump(new_index), commands);
+                }
+                Err(e) => {
+                    log::error!("Failed to move the entry: {}", e);
+                    *message = Some(e);
+                }
+            }
+        }
+        Action::Connect => {
+            match app.selected_entry().and_then(|index| connect::connect_alias(&app.entries[index])) {
+                Some(alias) => commands.push(Command::Connect(alias.to_string())),
+                None => log::info!("The selected entry has no host to connect to."),
+            }
+        }
+        Action::Delete if !app.popup_open => {
+            let Some(index) = app.selected_entry() else {
+                return;
+            };
+            let change = Change::remove(format!("delete {}", app.entries[index].label()), &app.entries, index);
+            log::info!("{}", change.description);
+            app.status = Some(format!("Done: {} (u to undo)", change.description));
+            app.history.apply(&mut app.entries, change);
+
+            app.list_state.clear_marks();
+            app.refresh_hosts();
+            app.clamp_selection();
+        }
+        Action::Undo | Action::Redo if !app.popup_open => {
+            let is_undo = action == Action::Undo;
+            let description = if is_undo {
+                app.history.undo(&mut app.entries)
+            } else {
+                app.history.redo(&mut app.entries)
+            };
+
+            app.status = Some(match (description, is_undo) {
+                (Some(description), true) => format!("Undone: {}", description),
+                (Some(description), false) => format!("Redone: {}", description),
+                (None, true) => "Nothing to undo".to_string(),
+                (None, false) => "Nothing to redo".to_string(),
+            });
+            app.list_state.clear_marks();
+            app.refresh_hosts();
+            app.clamp_selection();
+        }
+        Action::Review => commands.push(Command::Io(Io::Review { quitting: false })),
+        Action::WriteChanges => {
+            let AppMode::Review { writes, quitting, message, .. } = &mut app.mode else {
+                return;
+            };
+            // The entries of a config that could not be read are missing, writing would drop them
+            if let Some(e) = app.error.as_ref().filter(|e| e.blocks_writes()) {
+                *message = Some(format!("Not written, the config could not be read: {}", e));
+                return;
+            }
+            commands.push(Command::Io(Io::Write { writes: writes.clone(), quitting: *quitting }));
+        }
+        Action::Discard => {
+            log::info!("Quitting without writing {} changes.", app.history.pending());
+            commands.push(Command::Quit);
+        }
+        Action::ToggleMark(index) if !app.popup_open => {
+            app.list_state.toggle_mark(index);
+            app.refresh_hosts();
+            // The next row gets the selection, to mark a few rows in a row
+            let row = app.list_state.get_index();
+            if row + 1 < app.visible_rows() {
+                app.list_state.select(row + 1);
+            }
+        }
+        Action::MarkMatches if !app.popup_open => {
+            let indices = match &app.mode {
+                AppMode::Search { matches, query, .. } if !query.is_empty() => matches.clone(),
+                _ => (0..app.hosts.len()).filter_map(|row| app.tree.entry_at(row)).collect(),
+            };
+            log::info!("Marking {} entries.", indices.len());
+            app.list_state.mark_all(indices);
+            app.status = Some(format!("{} marked", app.list_state.marked().len()));
+            app.refresh_hosts();
+        }
+        Action::ClearMarks => {
+            app.list_state.clear_marks();
+            app.status = None;
+            app.refresh_hosts();
+        }
+        Action::Bulk if !app.popup_open => {
+            if app.list_state.marked().is_empty() {
+                app.status = Some("No marked entries, Space marks the selected one".to_string());
+                return;
+            }
+            app.mode = AppMode::Bulk {
+                marked: app.list_state.marked().iter().copied().collect(),
+                selected: 0,
+                action: None,
+                input: String::new(),
+                summary: None,
+                message: None,
+            };
+            app.popup_open = true;
+        }
+        Action::Summarize => {
+            let AppMode::Bulk { marked, action: Some(action), input, .. } = &app.mode else {
+                return;
+            };
+            commands.push(Command::Io(Io::Summary { marked: marked.clone(), action: *action, input: input.clone() }));
+        }
+        Action::ApplyBulk => {
+            let AppMode::Bulk { marked, action: Some(action), input, .. } = &app.mode else {
+                return;
+            };
+            if *action == BulkAction::Export {
+                commands.push(Command::Io(Io::Export { marked: marked.clone(), input: input.clone() }));
+                return;
+            }
+            let result = bulk::change(&app.entries, marked, *action, input).map(|change| {
+                let done = format!("Done: {} (u to undo)", change.description);
+                app.history.apply(&mut app.entries, change);
+                done
+            });
+            applied_bulk(app, result);
+        }
+        Action::NewHost if !app.popup_open => {
+            log::info!("Showing the new host form.");
+            // The host goes to the tag of the selected row, unless a template says otherwise
+            let tag = app.tree.group_at(app.list_state.get_index()).flatten();
+            app.mode = AppMode::NewHost {
+                template: 0,
+                selected: 1,
+                values: host_form::values(None, tag.as_deref()),
+                message: None,
+            };
+            app.popup_open = true;
+        }
+        Action::AddHost => {
+            let AppMode::NewHost { values, message, .. } = &mut app.mode else {
+                return;
+            };
+            match host_form::add_change(&app.entries, values, app.config_path.clone()) {
+                Ok((change, index)) => {
+                    log::info!("{}", change.description);
+                    app.status = Some(format!("Done: {} (u to undo)", change.description));
+                    app.history.apply(&mut app.entries, change);
+                    app.list_state.clear_marks();
+                    app.refresh_hosts();
+                    perform(app, Action::Jump(index), commands);
+                }
+                Err(e) => {
+                    log::error!("Failed to add the host: {}", e);
+                    *message = Some(e);
+                }
+            }
+        }
+        Action::Reload => {
+            let AppMode::Conflict { entries, .. } = std::mem::replace(&mut app.mode, AppMode::Normal) else {
+                return;
+            };
+            log::info!("Dropping {} unsaved changes for the config on disk.", app.history.pending());
+            app.status = Some(format!("Reloaded, {} unsaved change(s) dropped", app.history.pending()));
+            app.history.clear();
+            app.popup_open = false;
+            replace_entries(app, entries);
+        }
+        Action::Profiles if !app.popup_open => {
+            log::info!("Showing the profiles.");
+            let selected = app.profiles.iter().position(|profile| profile.path == app.config_path).unwrap_or(0);
+            app.mode = AppMode::Profiles { selected, message: None };
+            app.popup_open = true;
+        }
+        Action::OpenProfile(index) | Action::DiffProfile(index) => {
+            let (Some(profile), AppMode::Profiles { message, .. }) = (app.profiles.get(index).cloned(), &mut app.mode) else {
+                return;
+            };
+            let is_diff = matches!(action, Action::DiffProfile(_));
+            if !is_diff && profile.path == app.config_path {
+                perform(app, Action::Normal, commands);
+                return;
+            }
+            // The changes belong to the files of the open config
+            if !is_diff && app.history.pending() > 0 {
+                *message = Some(format!("Write or undo the {} unsaved change(s) first", app.history.pending()));
+                return;
+            }
+
+            commands.push(Command::Io(Io::Profile { profile, diff: is_diff }));
+        }
+        _ => {}
+    }
+}
+
+/// Takes the config files read again after they changed on disk. The unsaved changes are not
+/// dropped without asking: with some, the entries read wait in a conflict prompt. A config that
+/// could not be read is reported in the banner until it is read again. When a popup was opened
+/// meanwhile, the files are read again once it is closed.
+fn reload(app: &mut App, changed: &[PathBuf], read: Result<Vec<SshConfigEntry>, Error>, commands: &mut Vec<Command>) {
+    // A popup opened while the files were read
+    if keeps_popup(&app.mode) {
+        wait_for_popup(app, changed.to_vec());
+        return;
+    }
+    let entries = match read {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Failed to reload {}", e);
+            app.error = Some(e);
+            return;
+        }
+    };
+    if app.error.take().is_some() {
+        app.status = Some(format!("Read {} again", display_path(&app.config_path)));
+    }
+    // The Include lines may have changed
+    commands.push(Command::Watch(watch::config_files(&app.config_path, &entries)));
+
+    // Writing the changes of the TUI gives the entries it already has, the lines aside
+    let same = |a: &SshConfigEntry, b: &SshConfigEntry| SshConfigEntry { line: b.line, option_lines: b.option_lines.clone(), ..a.clone() } == *b;
+    if entries.len() == app.entries.len() && entries.iter().zip(&app.entries).all(|(a, b)| same(a, b)) {
+        return;
+    }
+
+    if app.history.pending() > 0 {
+        log::info!("{:?} changed on disk with {} unsaved changes.", changed, app.history.pending());
+        app.mode = AppMode::Conflict { entries, changed: changed.to_vec() };
+        app.popup_open = true;
+        return;
+    }
+    let names = changed.iter().map(|path| display_path(path)).collect::<Vec<_>>();
+    app.status = Some(format!("Reloaded, {} changed on disk", names.join(", ")));
+    replace_entries(app, entries);
+}
+
+/// Ends the review once the changes were written, quitting when it was opened to quit.
+fn written_changes(app: &mut App, written: Result<usize, String>, quitting: bool, commands: &mut Vec<Command>) {
+    let written = match written {
+        Ok(written) => written,
+        Err(e) => {
+            if let AppMode::Review { message, .. } = &mut app.mode {
+                *message = Some(e);
+            }
+            return;
+        }
+    };
+
+    app.history.clear();
+    if quitting {
+        commands.push(Command::Quit);
+        return;
+    }
+    app.status = Some(format!("Wrote {} file(s), the old versions are in .bak files", written));
+    perform(app, Action::Normal, commands);
+}
+
+/// Closes the bulk action once it is done, or shows why it failed.
+fn applied_bulk(app: &mut App, result: Result<String, String>) {
+    let AppMode::Bulk { action, summary, message, .. } = &mut app.mode else {
+        return;
+    };
+    match result {
+        Ok(done) => {
+            log::info!("{}", done);
+            // The indices of the marked entries changed with the entries
+            if *action != Some(BulkAction::Export) {
+                app.list_state.clear_marks();
+            }
+            app.status = Some(done);
+            app.mode = AppMode::Normal;
+            app.popup_open = false;
+            app.refresh_hosts();
+            app.clamp_selection();
+        }
+        Err(e) => {
+            log::error!("Failed to apply the bulk action: {}", e);
+            *summary = None;
+            *message = Some(e);
+        }
+    }
+}
+
+/// Opens or compares the config of a profile once it was read.
+fn read_profile(app: &mut App, profile: Profile, diff: bool, read: Result<Vec<SshConfigEntry>, Error>, commands: &mut Vec<Command>) {
+    let AppMode::Profiles { message, .. } = &mut app.mode else {
+        return;
+    };
+    let entries = match read {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Failed to read {}", e);
+            *message = Some(format!("Failed to read {}", e));
+            return;
+        }
+    };
+    if diff {
+        log::info!("Comparing {} with {}", app.config_path.display(), profile.path.display());
+        let diffs = profile::compare(&app.entries, &entries);
+        app.mode = AppMode::ProfileDiff { profile, diffs, scroll: 0 };
+    } else {
+        log::info!("Opening {}", profile.path.display());
+        app.open(profile.path.clone(), entries);
+        commands.push(Command::Watch(watch::config_files(&app.config_path, &app.entries)));
+        app.status = Some(format!("Opened {} ({})", profile.name, display_path(&profile.path)));
+    }
+}
+
+/// The path with the home directory written as `~`.
+fn display_path(path: &Path) -> String {
+    let home = shellexpand::tilde("~").into_owned();
+    match path.strip_prefix(&home) {
+        Ok(rest) if !home.is_empty() => format!("~/{}", rest.display()),
+        _ => path.display().to_string(),
+    }
+}
+
+/// Puts the entries read from disk in place of the current ones, keeping the selected entry, the
+/// search and the scroll offset. The views that point into the old entries are closed.
+fn replace_entries(app: &mut App, entries: Vec<SshConfigEntry>) {
+    let previous = app.list_state.get_index();
+    let offset = app.list_state.scroll_offset;
+    let searching = matches!(&app.mode, AppMode::Search { query, .. } if !query.is_empty());
+    let selected = match &app.mode {
+        AppMode::Search { matches, .. } if searching => matches.get(previous).copied(),
+        _ => app.selected_entry(),
+    }.map(|index| app.entries[index].clone());
+    let header = app.tree.group_at(previous);
+
+    app.entries = entries;
+    app.list_state.clear_marks();
+    // The same entry is the one with the same section line in the same file
+    let index = selected.and_then(|selected| app.entries.iter().position(|entry| {
+        entry.kind == selected.kind && entry.host == selected.host && entry.source == selected.source
+    }));
+
+    match &app.mode {
+        AppMode::Search { query, .. } => {
+            let query = query.clone();
+            let matches = search::search(&app.entries, &query);
+            app.mode.update_search(query, matches);
+        }
+        AppMode::Normal | AppMode::Help => {}
+        _ => {
+            app.mode = AppMode::Normal;
+            app.popup_open = false;
+        }
+    }
+
+    let row = match (&app.mode, index) {
+        (AppMode::Search { matches, .. }, Some(index)) if searching => matches.iter().position(|&i| i == index),
+        (_, _) if searching => None,
+        (_, Some(index)) => app.tree.entry_row(index),
+        (_, None) => header.and_then(|tag| app.tree.group_row(&tag)),
+    };
+    app.refresh_hosts();
+    app.list_state.set_offset(offset);
+    app.list_state.select(row.unwrap_or(previous).min(app.visible_rows().saturating_sub(1)));
+
+    // The popup of an entry that is gone is closed
+    if matches!(app.mode, AppMode::Normal) && app.selected_entry().is_none() {
+        app.popup_open = false;
+    }
+}
+
+/// Validates the edited entry and builds the change that puts it in place of the entry at `index`.
+fn edit_change(entries: &[SshConfigEntry], index: usize, edited: &SshConfigEntry) -> Result<Change, String> {
+    if edited.kind != EntryKind::Global {
+        entry::validate_host(&edited.host)?;
+    }
+    for (key, value) in &edited.options {
+        entry::validate_option(key, value)?;
+    }
+    let duplicate = entries.iter()
+        .enumerate()
+        .filter(|&(i, _)| i != index)
+        .find_map(|(_, other)| edited.shared_pattern(other));
+    if let Some(pattern) = duplicate {
+        return Err(format!("Host '{}' already exists", pattern));
+    }
+
+    let Some(current) = entries.get(index) else {
+        return Err(format!("Entry {} no longer exists", index));
+    };
+    if current == edited {
+        return Err("Nothing changed".to_string());
+    }
+
+    // A rename alone is described as such, any other edit by the entry it is about
+    let mut renamed = current.clone();
+    renamed.host = edited.host.clone();
+    let description = if current.host != edited.host && &renamed == edited {
+        format!("rename {} to {}", current.label(), edited.label())
+    } else {
+        format!("edit {}", edited.label())
+    };
+    Ok(Change::replace(description, entries, index, edited.clone()))
+}
+
+/// Keys of the bulk actions: picking the action in the menu, typing what it needs, then
+/// confirming the summary.
+fn handle_bulk_mode(key: KeyEvent, mode: &mut AppMode, keys: &KeyBindings) -> Option<Action> {
+    let AppMode::Bulk { selected, action, input, summary, message, .. } = mode else {
+        return None;
+    };
+    let binding = keys.binding(&key);
+
+    // The summary is shown, waiting for the confirmation
+    if summary.is_some() {
+        match binding {
+            Some(Binding::Open) => return Some(Action::ApplyBulk),
+            Some(Binding::Back) => {
+                *summary = None;
+                // Delete asks for nothing, it goes back to the menu
+                if *action == Some(BulkAction::Delete) {
+                    *action = None;
+                }
+            }
+            _ => {}
+        }
+        return None;
+    }
+
+    // What the action needs is typed, the other keys confirm or go back
+    if action.is_some() {
+        match (typed_char(&key), key.code, binding) {
+            (Some(c), _, _) => input.push(c),
+            (_, KeyCode::Backspace, _) => {
+                input.pop();
+            }
+            (_, _, Some(Binding::Open)) => return Some(Action::Summarize),
+            (_, _, Some(Binding::Back)) => {
+                *action = None;
+                *message = None;
+            }
+            _ => {}
+        }
+        return None;
+    }
+
+    if navigate(binding, selected, BulkAction::ALL.len()) {
+        return None;
+    }
+    match binding {
+        Some(Binding::Back | Binding::Quit) => return Some(Action::Normal),
+        Some(Binding::Open) => {
+            let picked = BulkAction::ALL[*selected];
+            *action = Some(picked);
+            *input = picked.default_input();
+            *message = None;
+            if picked.prompt().is_none() {
+                return Some(Action::Summarize);
+            }
+        }
+        _ => {}
+    }
+    None
+}
+
+/// Keys of the new host form: ←/→ pick the template on the first row, the other rows are typed into.
+fn handle_new_host_mode(key: KeyEvent, mode: &mut AppMode, templates: &[Template]) -> Option<Action> {
+    let AppMode::NewHost { template, selected, values, message } = mode else {
+        return None;
+    };
+    let rows = FormField::ALL.len() + 1;
+
+    match key.code {
+        KeyCode::Esc => return Some(Action::Normal),
+        KeyCode::Enter => return Some(Action::AddHost),
+        KeyCode::Down | KeyCode::Tab => *selected = (*selected + 1) % rows,
+        KeyCode::Up | KeyCode::BackTab => *selected = selected.checked_sub(1).unwrap_or(rows - 1),
+        // Another template fills the form again, the tag stays when the template has none
+        KeyCode::Left | KeyCode::Right if *selected == 0 && !templates.is_empty() => {
+            let count = templates.len() + 1;
+            *template = if key.code == KeyCode::Right { (*template + 1) % count } else { (*template + count - 1) % count };
+            let tag = values[FormField::ALL.len() - 1].clone();
+            *values = host_form::values(template.checked_sub(1).map(|i| &templates[i]), Some(&tag));
+        }
+        KeyCode::Backspace if *selected > 0 => {
+            values[*selected - 1].pop();
+        }
+        KeyCode::Char(c) if *selected > 0 => values[*selected - 1].push(c),
+        _ => return None,
+    }
+    *message = None;
+    None
+}
+
+/// Keys of the conflict prompt: drop the unsaved changes, review them, or keep them for later.
+fn handle_conflict_mode(key: KeyEvent, keys: &KeyBindings) -> Option<Action> {
+    match (key.code, keys.binding(&key)) {
+        (_, Some(Binding::Write)) | (KeyCode::Char('w'), _) => Some(Action::Review),
+        (_, Some(Binding::Back)) | (KeyCode::Char('k'), _) => Some(Action::Normal),
+        (KeyCode::Char('r'), _) => Some(Action::Reload),
+        _ => None,
+    }
+}
+
+fn handle_profiles_mode(key: KeyEvent, mode: &mut AppMode, keys: &KeyBindings, profiles: usize) -> Option<Action> {
+    let AppMode::Profiles { selected, message } = mode else {
+        return None;
+    };
+
+    let binding = keys.binding(&key);
+    if !navigate(binding, selected, profiles) {
+        return match (binding, key.code) {
+            (Some(Binding::Open), _) => Some(Action::OpenProfile(*selected)),
+            (Some(Binding::Back | Binding::Quit), _) => Some(Action::Normal),
+            (_, KeyCode::Char('d')) => Some(Action::DiffProfile(*selected)),
+            _ => None,
+        };
+    }
+    *message = None;
+    None
+}
+
+fn handle_profile_diff_mode(key: KeyEvent, mode: &mut AppMode, keys: &KeyBindings) -> Option<Action> {
+    let AppMode::ProfileDiff { scroll: offset, .. } = mode else {
+        return None;
+    };
+
+    let binding = keys.binding(&key);
+    match binding {
+        _ if scroll(&key, binding, offset) => None,
+        Some(Binding::Back | Binding::Quit) => Some(Action::Normal),
+        _ => None,
+    }
+}
+
+fn handle_search_mode(key: KeyEvent, mode: &mut AppMode, entries: &[SshConfigEntry]) -> Option<Action> {
+    let AppMode::Search { query, .. } = mode else {
+        return None;
+    };
+
+    let mut new_query = query.clone();
+    match key.code {
+        KeyCode::Esc => {
+            log::debug!("ESC pressionado - saindo do modo de busca");
+            return Some(Action::Normal);
+        }
+        // Ignorar a tecla '/' quando já estiver no modo de busca
+        KeyCode::Char('/') => return None,
+        KeyCode::Char(c) => new_query.push(c),
+        KeyCode::Backspace => {
+            new_query.pop();
+        }
+        _ => return None,
+    }
+
+    log::debug!("Nova query: {}", new_query);
+    let matches = search::search(entries, &new_query);
+    log::debug!("Encontrados {} matches", matches.len());
+    mode.update_search(new_query, matches);
+    // The results changed, the best one is selected
+    Some(Action::UpdateSelection(0))
+}
+
+fn handle_edit_mode(key_event: KeyEvent, mode: &mut AppMode, keys: &KeyBindings) -> Option<Action> {
+    let AppMode::Edit { entry, selected, field, input, message, .. } = mode else {
+        return None;
+    };
+
+    match field.take() {
+        // Typing into a field
+        Some(current) => {
+            match key_event.code {
+                KeyCode::Esc => {
+                    log::debug!("Edit of {:?} cancelled", current);
+                    *selected = (*selected).min(entry.options.len());
+                    input.clear();
+                    *message = None;
+                }
+                KeyCode::Enter => {
+                    match apply_edit(entry, &current, input.trim()) {
+                        Ok(next) => {
+                            // Adding an option asks for the key and then for the value
+                            if let Some(EditField::NewValue(_)) = next {
+                                *selected = entry.options.len() + 1;
+                            } else if matches!(current, EditField::NewValue(_)) {
+                                *selected = entry.options.len();
+                            }
+                            *field = next;
+                            input.clear();
+                            *message = None;
+                        }
+                        Err(e) => {
+                            *field = Some(current);
+                            *message = Some(e);
+                        }
+                    }
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                    *field = Some(current);
+                }
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    *field = Some(current);
+                }
+                _ => *field = Some(current),
+            }
+            None
+        }
+        // Navigating the rows of the entry
+        None => {
+            let binding = keys.binding(&key_event);
+            if navigate(binding, selected, entry.options.len() + 1) {
+                return None;
+            }
+            match key_event.code {
+                _ if binding == Some(Binding::Back) => Some(Action::Normal),
+                KeyCode::Enter | KeyCode::Char('e') if *selected > 0 => {
+                    *field = Some(EditField::Value(*selected - 1));
+                    *input = entry.options[*selected - 1].1.clone();
+                    *message = None;
+                    None
+                }
+                KeyCode::Enter | KeyCode::Char('e') | KeyCode::Char('r') => {
+                    if entry.kind == EntryKind::Global {
+                        *message = Some("Global options have no name".to_string());
+                    } else {
+                        *selected = 0;
+                        *field = Some(EditField::Host);
+                        *input = entry.host.clone();
+                        *message = None;
+                    }
+                    None
+                }
+                KeyCode::Char('a') => {
+                    *selected = entry.options.len() + 1;
+                    *field = Some(EditField::NewKey);
+                    input.clear();
+                    *message = None;
+                    None
+                }
+                KeyCode::Char('d') => {
+                    if *selected > 0 {
+                        let (key, _) = entry.options.remove(*selected - 1);
+                        log::debug!("Option {} removed from {}", key, entry.host);
+                        *selected = (*selected).min(entry.options.len());
+                        *message = None;
+                    }
+                    None
+                }
+                KeyCode::Char('s') => Some(Action::SaveEdit),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Applies the typed `input` to the `field` of the entry after validating it.
+///
+/// # Returns
+/// The next field to type into, used when adding an option to go from its key to its value.
+fn apply_edit(entry: &mut SshConfigEntry, field: &EditField, input: &str) -> Result<Option<EditField>, String> {
+    match field {
+        EditField::Host => {
+            entry::validate_host(input)?;
+            entry.host = input.to_string();
+            Ok(None)
+        }
+        EditField::Value(index) => {
+            let key = entry.options[*index].0.clone();
+            entry::validate_option(&key, input)?;
+            entry.options[*index].1 = input.to_string();
+            Ok(None)
+        }
+        EditField::NewKey => {
+            // Validate the name only, the value is asked next
+            entry::validate_option_name(input)?;
+            Ok(Some(EditField::NewValue(input.to_string())))
+        }
+        EditField::NewValue(key) => {
+            entry::validate_option(key, input)?;
+            entry.add_option(key.clone(), input.to_string());
+            Ok(None)
+        }
+    }
+}
+
+fn handle_lint_mode(key_event: KeyEvent, mode: &mut AppMode, keys: &KeyBindings) -> Option<Action> {
+    // The entry each row is about: the entry of a diagnostic, the first host using a key or
+    // the host naming a missing key file
+    let (rows, selected) = match mode {
+        AppMode::Lint { diagnostics, selected } => {
+            (diagnostics.iter().map(|diagnostic| Some(diagnostic.entry)).collect::<Vec<_>>(), selected)
+        }
+        AppMode::Keys { report, selected } => {
+            let keys = report.keys.iter().map(|usage| usage.entries.first().copied());
+            let missing = report.missing.iter().map(|missing| Some(missing.entry));
+            (keys.chain(missing).collect(), selected)
+        }
+        _ => return None,
+    };
+
+    let binding = keys.binding(&key_event);
+    match binding {
+        _ if navigate(binding, selected, rows.len()) => None,
+        // Select the entry the row is about
+        Some(Binding::Open) => rows.get(*selected).copied().flatten().map(Action::Jump),
+        Some(Binding::Back | Binding::Quit) => Some(Action::Normal),
+        _ => None,
+    }
+}
+
+fn handle_tags_mode(key_event: KeyEvent, mode: &mut AppMode, keys: &KeyBindings, groups: &[TagGroup]) -> Option<Action> {
+    let AppMode::Tags { selected } = mode else {
+        return None;
+    };
+
+    let binding = keys.binding(&key_event);
+    match binding {
+        _ if navigate(binding, selected, groups.len()) => None,
+        Some(Binding::Open) => groups.get(*selected).map(|group| Action::JumpTag(group.tag.clone())),
+        Some(Binding::Back | Binding::Tags | Binding::Quit) => Some(Action::Normal),
+        _ => None,
+    }
+}
+
+fn handle_move_tag_mode(key_event: KeyEvent, mode: &mut AppMode) -> Option<Action> {
+    let AppMode::MoveTag { tags, selected, input, message, .. } = mode else {
+        return None;
+    };
+
+    match key_event.code {
+        // Picking an existing tag fills the input with it
+        KeyCode::Down | KeyCode::Up if !tags.is_empty() => {
+            let next = match (key_event.code, *selected) {
+                (KeyCode::Down, Some(i)) => (i + 1) % tags.len(),
+                (KeyCode::Up, Some(0)) | (KeyCode::Up, None) => tags.len() - 1,
+                (KeyCode::Up, Some(i)) => i - 1,
+                _ => 0,
+            };
+            *selected = Some(next);
+            *input = tags[next].clone();
+            None
+        }
+        KeyCode::Char(c) => {
+            input.push(c);
+            *selected = None;
+            *message = None;
+            None
+        }
+        KeyCode::Backspace => {
+            input.pop();
+            *selected = None;
+            *message = None;
+            None
+        }
+        KeyCode::Enter => Some(Action::SaveMove),
+        KeyCode::Esc => Some(Action::Normal),
+        _ => None,
+    }
+}
+
+fn handle_known_hosts_mode(key_event: KeyEvent, mode: &mut AppMode, keys: &KeyBindings) -> Option<Action> {
+    let AppMode::KnownHosts { hosts, links, selected, deleting, message, .. } = mode else {
+        return None;
+    };
+    let binding = keys.binding(&key_event);
+
+    // The lines to delete are shown, waiting for the confirmation
+    if deleting.is_some() {
+        match binding {
+            Some(Binding::Open) => return Some(Action::DeleteKnownHosts),
+            Some(Binding::Back) => *deleting = None,
+            _ => {}
+        }
+        return None;
+    }
+
+    if navigate(binding, selected, hosts.len()) {
+        return None;
+    }
+    match (binding, key_event.code) {
+        (Some(Binding::Open), _) => links.get(*selected).and_then(|link| link.entries.first()).map(|&index| Action::Jump(index)),
+        (Some(Binding::Delete), _) if !hosts.is_empty() => {
+            *deleting = Some(vec![*selected]);
+            None
+        }
+        (_, KeyCode::Char('D')) => {
+            let stale = (0..links.len()).filter(|&i| links[i].stale).collect::<Vec<_>>();
+            if stale.is_empty() {
+                *message = Some("No stale lines".to_string());
+            } else {
+                *deleting = Some(stale);
+            }
+            None
+        }
+        (Some(Binding::Back | Binding::Quit), _) => Some(Action::Normal),
+        _ => None,
+    }
+}
+
+fn handle_review_mode(key_event: KeyEvent, mode: &mut AppMode, keys: &KeyBindings) -> Option<Action> {
+    let AppMode::Review { scroll: offset, quitting, .. } = mode else {
+        return None;
+    };
+
+    let binding = keys.binding(&key_event);
+    match (binding, key_event.code) {
+        _ if scroll(&key_event, binding, offset) => None,
+        (Some(Binding::Open | Binding::Write), _) => Some(Action::WriteChanges),
+        (_, KeyCode::Char('Q')) if *quitting => Some(Action::Discard),
+        (Some(Binding::Back | Binding::Quit), _) => Some(Action::Normal),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects;
+    use crate::settings::Settings;
+
+    fn app(hosts: &[&str]) -> App {
+        let entries = hosts.iter()
+            .map(|host| SshConfigEntry::new(EntryKind::Host, host, None, "config".into()))
+            .collect();
+        App::new(entries, PathBuf::from("/nonexistent"), "ssh", Settings::default())
+    }
+
+    fn key(code: KeyCode) -> AppEvent {
+        AppEvent::Input(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+    }
+
+    fn type_keys(app: &mut App, text: &str) -> Vec<Command> {
+        text.chars().flat_map(|c| run(app, key(KeyCode::Char(c)))).collect()
+    }
+
+    /// Like `update`, with the files read and written as the TUI does.
+    fn run(app: &mut App, event: AppEvent) -> Vec<Command> {
+        let mut commands = Vec::new();
+        for command in update(app, event) {
+            match command {
+                Command::Io(io) => {
+                    let event = effects::run(app, io);
+                    commands.extend(run(app, event));
+                }
+                command => commands.push(command),
+            }
+        }
+        commands
+    }
+
+    fn query(app: &App) -> Option<&str> {
+        match &app.mode {
+            AppMode::Search { query, .. } => Some(query),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn q_quits() {
+        let mut app = app(&["web"]);
+        assert_eq!(update(&mut app, key(KeyCode::Char('q'))), vec![Command::Quit]);
+    }
+
+    #[test]
+    fn keys_are_typed_into_the_search() {
+        let mut app = app(&["web", "db"]);
+        update(&mut app, key(KeyCode::Char('/')));
+
+        assert!(type_keys(&mut app, "qhd").is_empty());
+        assert_eq!(query(&app), Some("qhd"));
+        assert!(!app.popup_open);
+
+        update(&mut app, key(KeyCode::Backspace));
+        assert_eq!(query(&app), Some("qh"));
+        update(&mut app, key(KeyCode::Esc));
+        assert!(matches!(app.mode, AppMode::Normal));
+    }
+
+    #[test]
+    fn enter_opens_the_selected_search_result() {
+        let mut app = app(&["web", "db", "cache"]);
+        update(&mut app, key(KeyCode::Char('/')));
+        type_keys(&mut app, "db");
+        update(&mut app, key(KeyCode::Enter));
+
+        assert!(matches!(app.mode, AppMode::Normal));
+        assert!(app.popup_open);
+        assert_eq!(app.selected_entry(), Some(1));
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut app = app(&["a", "b", "c"]);
+        update(&mut app, key(KeyCode::Up));
+        assert_eq!(app.list_state.get_index(), 2);
+        update(&mut app, key(KeyCode::Down));
+        assert_eq!(app.list_state.get_index(), 0);
+    }
+
+    #[test]
+    fn navigation_in_an_empty_list_does_nothing() {
+        let mut app = app(&[]);
+        update(&mut app, key(KeyCode::Down));
+        update(&mut app, key(KeyCode::Up));
+        update(&mut app, key(KeyCode::Enter));
+        assert_eq!(app.list_state.get_index(), 0);
+    }
+
+    #[test]
+    fn delete_can_be_undone_and_redone() {
+        let mut app = app(&["a", "b"]);
+        update(&mut app, key(KeyCode::Char('d')));
+        assert_eq!(app.entries.len(), 1);
+        assert_eq!(app.history.pending(), 1);
+
+        update(&mut app, key(KeyCode::Char('u')));
+        assert_eq!(app.entries.iter().map(|entry| entry.host.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(app.status.as_deref(), Some("Undone: delete a"));
+
+        update(&mut app, AppEvent::Input(Event::Key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL))));
+        assert_eq!(app.entries.len(), 1);
+        assert_eq!(app.entries[0].host, "b");
+    }
+
+    #[test]
+    fn quitting_with_unsaved_changes_shows_them_first() {
+        let mut app = app(&["a", "b"]);
+        update(&mut app, key(KeyCode::Char('d')));
+
+        assert_eq!(update(&mut app, key(KeyCode::Char('q'))), [Command::Io(Io::Review { quitting: true })]);
+        update(&mut app, AppEvent::Reviewed { writes: Vec::new(), quitting: true });
+        assert!(matches!(app.mode, AppMode::Review { quitting: true, .. }));
+        assert_eq!(update(&mut app, key(KeyCode::Char('Q'))), vec![Command::Quit]);
+    }
+
+    #[test]
+    fn reachability_results_are_stored() {
+        let mut app = app(&["a"]);
+        update(&mut app, AppEvent::Reachability("a".to_string(), Status::Down("refused".to_string())));
+        assert_eq!(app.statuses.get("a"), Some(&Status::Down("refused".to_string())));
+    }
+
+    #[test]
+    fn marked_entries_get_a_bulk_action_once_confirmed() {
+        let mut app = app(&["web", "db", "mail"]);
+
+        // Space marks and moves down, `*` marks the search results
+        update(&mut app, key(KeyCode::Char(' ')));
+        update(&mut app, key(KeyCode::Char('/')));
+        type_keys(&mut app, "mail*");
+        assert_eq!(query(&app), Some("mail"));
+        assert_eq!(app.list_state.marked().iter().copied().collect::<Vec<_>>(), [0, 2]);
+
+        // Space marks or unmarks a single search result
+        type_keys(&mut app, " ");
+        assert_eq!(query(&app), Some("mail"));
+        assert_eq!(app.list_state.marked().iter().copied().collect::<Vec<_>>(), [0]);
+        type_keys(&mut app, " ");
+        update(&mut app, key(KeyCode::Esc));
+
+        update(&mut app, key(KeyCode::Char('b')));
+        update(&mut app, key(KeyCode::Enter));
+        type_keys(&mut app, "User deploy");
+        run(&mut app, key(KeyCode::Enter));
+        assert!(matches!(&app.mode, AppMode::Bulk { summary: Some(summary), .. } if summary.len() == 3));
+        assert!(app.entries.iter().all(|entry| entry.options.is_empty()));
+
+        update(&mut app, key(KeyCode::Enter));
+        assert_eq!(app.entries[0].get_option("User"), Some("deploy"));
+        assert_eq!(app.entries[1].get_option("User"), None);
+        assert_eq!(app.entries[2].get_option("User"), Some("deploy"));
+        assert_eq!(app.history.pending(), 1);
+        assert!(app.list_state.marked().is_empty());
+    }
+
+    #[test]
+    fn popups_move_with_the_keys_of_the_host_list() {
+        let mut app = app(&["web"]);
+        app.entries[0].add_option("User".to_string(), "deploy".to_string());
+        app.entries[0].add_option("Port".to_string(), "22".to_string());
+        let selected = |app: &App| match app.mode {
+            AppMode::Edit { selected, .. } => selected,
+            _ => panic!("{:?}", app.mode),
+        };
+
+        update(&mut app, key(KeyCode::Char('e')));
+        update(&mut app, key(KeyCode::Char('G')));
+        assert_eq!(selected(&app), 2);
+        update(&mut app, key(KeyCode::Char('k')));
+        assert_eq!(selected(&app), 1);
+
+        // A remapped key works in the popups too
+        app.settings = crate::settings::parse("[keys]\ndown = \"n\"\n").unwrap();
+        update(&mut app, key(KeyCode::Char('n')));
+        assert_eq!(selected(&app), 2);
+        update(&mut app, key(KeyCode::Esc));
+        assert!(matches!(app.mode, AppMode::Normal));
+    }
+
+    #[test]
+    fn files_and_ssh_are_left_to_the_caller() {
+        let mut app = app(&["web"]);
+        app.entries[0].add_option("IdentityFile".to_string(), "~/.ssh/missing_key".to_string());
+        assert_eq!(update(&mut app, key(KeyCode::Char('l'))), [Command::Io(Io::Lint)]);
+        assert!(matches!(app.mode, AppMode::Normal));
+
+        let connected = Err("No such file or directory".to_string());
+        update(&mut app, AppEvent::Connected { alias: "web".to_string(), connected, terminal: None });
+        assert_eq!(app.status.as_deref(), Some("Failed to connect to web: No such file or directory"));
+    }
+
+    #[test]
+    fn a_new_host_is_added_from_a_template() {
+        let mut app = app(&["web"]);
+        app.settings.templates.push(Template {
+            name: "lab VM".to_string(),
+            user: Some("root".to_string()),
+            tag: Some("lab".to_string()),
+            ..Template::default()
+        });
+
+        update(&mut app, key(KeyCode::Char('a')));
+        update(&mut app, key(KeyCode::Up));
+        update(&mut app, key(KeyCode::Right));
+        update(&mut app, key(KeyCode::Down));
+        type_keys(&mut app, "lab-1");
+        update(&mut app, key(KeyCode::Down));
+        update(&mut app, key(KeyCode::Down));
+        update(&mut app, key(KeyCode::Down));
+        type_keys(&mut app, "x");
+        update(&mut app, key(KeyCode::Enter));
+        assert!(matches!(&app.mode, AppMode::NewHost { message: Some(message), .. } if message == "Invalid Port 'x'"));
+
+        update(&mut app, key(KeyCode::Backspace));
+        update(&mut app, key(KeyCode::Enter));
+        assert!(matches!(app.mode, AppMode::Normal));
+        let added = &app.entries[1];
+        assert_eq!((added.host.as_str(), added.tag.as_deref()), ("lab-1", Some("lab")));
+        assert_eq!(added.options, [("User".to_string(), "root".to_string())]);
+        assert_eq!(app.selected_entry(), Some(1));
+        assert_eq!(app.status.as_deref(), Some("Done: add lab-1 (u to undo)"));
+    }
+
+    /// An app on a config file of its own, in a directory removed by the test.
+    fn app_on_disk(name: &str, contents: &str) -> (App, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("ssh-config-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        std::fs::write(&path, contents).unwrap();
+
+        let entries = config::read_ssh_config(&path.display().to_string()).unwrap();
+        let mut app = App::new(entries, dir.clone(), "ssh", Settings::default());
+        app.config_path = path.clone();
+        (app, path)
+    }
+
+    #[test]
+    fn a_change_on_disk_is_reloaded_keeping_the_selection_and_the_search() {
+        let (mut app, path) = app_on_disk("reload", "Host web\n\nHost db\n\nHost dbx\n");
+        run(&mut app, key(KeyCode::Char('/')));
+        type_keys(&mut app, "db");
+        run(&mut app, key(KeyCode::Down));
+
+        std::fs::write(&path, "Host cache\n\nHost web\n\nHost db\n\nHost db2\n\nHost dbx\n").unwrap();
+        let commands = run(&mut app, AppEvent::ConfigChanged(vec![path.clone()]));
+        assert_eq!(commands, [Command::Watch(vec![path.clone()])]);
+
+        assert_eq!(app.entries.len(), 5);
+        assert_eq!(query(&app), Some("db"));
+        let AppMode::Search { matches, .. } = &app.mode else { panic!("{:?}", app.mode) };
+        assert_eq!(app.entries[matches[app.list_state.get_index()]].host, "dbx");
+        assert_eq!(app.history.pending(), 0);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_change_on_disk_with_unsaved_changes_asks_first() {
+        let (mut app, path) = app_on_disk("conflict", "Host web\n\nHost db\n");
+        run(&mut app, key(KeyCode::Char('d')));
+
+        std::fs::write(&path, "Host web\n\nHost db\n\nHost mail\n").unwrap();
+        run(&mut app, AppEvent::ConfigChanged(vec![path.clone()]));
+        assert!(matches!(app.mode, AppMode::Conflict { .. }));
+        assert_eq!(app.entries.len(), 1);
+
+        run(&mut app, key(KeyCode::Char('r')));
+        assert!(matches!(app.mode, AppMode::Normal));
+        assert_eq!(app.entries.iter().map(|entry| entry.host.as_str()).collect::<Vec<_>>(), ["web", "db", "mail"]);
+        assert_eq!(app.history.pending(), 0);
+
+        // Writing the file from the TUI changes nothing on reload
+        run(&mut app, key(KeyCode::Char('d')));
+        run(&mut app, key(KeyCode::Char('w')));
+        run(&mut app, key(KeyCode::Enter));
+        run(&mut app, AppEvent::ConfigChanged(vec![path.clone()]));
+        assert!(matches!(app.mode, AppMode::Normal));
+        assert_eq!(app.entries.len(), 2);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_change_on_disk_waits_for_the_popup_to_close() {
+        let (mut app, path) = app_on_disk("wait", "Host web\n\nHost db\n");
+        run(&mut app, key(KeyCode::Char('a')));
+        type_keys(&mut app, "cache");
+
+        std::fs::write(&path, "Host web\n\nHost db\n\nHost mail\n").unwrap();
+        assert!(run(&mut app, AppEvent::ConfigChanged(vec![path.clone()])).is_empty());
+        assert!(matches!(&app.mode, AppMode::NewHost { values, .. } if values[0] == "cache"));
+        assert_eq!(app.entries.len(), 2);
+
+        // With the host added the reload asks first, once the form is closed
+        let commands = run(&mut app, key(KeyCode::Enter));
+        assert_eq!(commands, [Command::Watch(vec![path.clone()])]);
+        assert!(matches!(app.mode, AppMode::Conflict { .. }));
+        assert_eq!(app.entries.iter().map(|entry| entry.host.as_str()).collect::<Vec<_>>(), ["web", "db", "cache"]);
+
+        run(&mut app, key(KeyCode::Char('r')));
+        assert_eq!(app.entries.iter().map(|entry| entry.host.as_str()).collect::<Vec<_>>(), ["web", "db", "mail"]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_profile_is_opened_or_compared_with_the_open_config() {
+        let (mut app, path) = app_on_disk("profiles", "Host web\n    Port 22\n\nHost db\n");
+        let team = path.with_file_name("team.conf");
+        std::fs::write(&team, "Host web\n    Port 2222\n\nHost bastion\n").unwrap();
+        app.profiles.push(crate::settings::Profile { name: "team".to_string(), path: team.clone() });
+        let last = app.profiles.len() - 1;
+
+        run(&mut app, key(KeyCode::Char('p')));
+        run(&mut app, key(KeyCode::Down));
+        run(&mut app, key(KeyCode::Char('d')));
+        let AppMode::ProfileDiff { diffs, .. } = &app.mode else { panic!("{:?}", app.mode) };
+        assert_eq!(diffs.len(), 3);
+        run(&mut app, key(KeyCode::Esc));
+
+        // The unsaved changes have to be written or undone first
+        run(&mut app, key(KeyCode::Char('d')));
+        run(&mut app, key(KeyCode::Char('p')));
+        run(&mut app, key(KeyCode::Down));
+        assert!(matches!(app.mode, AppMode::Profiles { selected, .. } if selected == last));
+        run(&mut app, key(KeyCode::Enter));
+        assert!(matches!(&app.mode, AppMode::Profiles { message: Some(_), .. }));
+
+        run(&mut app, key(KeyCode::Esc));
+        run(&mut app, key(KeyCode::Char('u')));
+        run(&mut app, key(KeyCode::Char('p')));
+        run(&mut app, key(KeyCode::Down));
+        let commands = run(&mut app, key(KeyCode::Enter));
+        assert_eq!(commands, [Command::Watch(vec![team.clone()])]);
+        assert_eq!(app.config_path, team);
+        assert_eq!(app.entries.iter().map(|entry| entry.host.as_str()).collect::<Vec<_>>(), ["web", "bastion"]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn every_key_works_on_an_empty_config() {
+        let keys = [
+            KeyCode::Down, KeyCode::Up, KeyCode::Home, KeyCode::End, KeyCode::Enter, KeyCode::Left,
+            KeyCode::Right, KeyCode::Tab, KeyCode::BackTab, KeyCode::Backspace, KeyCode::PageDown,
+        ];
+        let chars = "/jgGhecltmikKrduwab* p";
+
+        // Each key from the list, then the keys of whatever it opened
+        for first in chars.chars().map(KeyCode::Char).chain(keys) {
+            let mut app = app(&[]);
+            run(&mut app, key(first));
+            for code in keys.iter().chain(&[KeyCode::Char('x'), KeyCode::Esc, KeyCode::Esc]) {
+                run(&mut app, key(*code));
+            }
+            assert!(app.entries.len() <= 1, "{:?}", first);
+        }
+    }
+
+    #[test]
+    fn a_config_that_cannot_be_read_is_not_written_over() {
+        let (_, path) = app_on_disk("unreadable", "");
+        std::fs::write(&path, b"Host web\n    User \xff\n").unwrap();
+        let error = config::read_or_empty(&path.display().to_string()).unwrap_err();
+        assert_eq!(error.to_string(), format!("{}, line 2: not valid UTF-8", path.display()));
+        assert!(config::read_or_empty(&path.with_file_name("missing").display().to_string()).unwrap().is_empty());
+
+        let mut app = App::new(Vec::new(), path.parent().unwrap().to_path_buf(), "ssh", Settings::default());
+        app.config_path = path.clone();
+        app.error = Some(error);
+
+        // A host added meanwhile is kept, the file is not
+        run(&mut app, key(KeyCode::Char('a')));
+        type_keys(&mut app, "db");
+        run(&mut app, key(KeyCode::Enter));
+        run(&mut app, key(KeyCode::Char('w')));
+        run(&mut app, key(KeyCode::Enter));
+        assert!(matches!(&app.mode, AppMode::Review { message: Some(message), .. } if message.starts_with("Not written")));
+        assert_eq!(std::fs::read(&path).unwrap(), b"Host web\n    User \xff\n");
+
+        // Esc does not hide why, fixing the file does
+        run(&mut app, key(KeyCode::Esc));
+        run(&mut app, key(KeyCode::Esc));
+        assert!(app.error.is_some());
+        std::fs::write(&path, "Host web\n    User deploy\n").unwrap();
+        run(&mut app, AppEvent::ConfigChanged(vec![path.clone()]));
+        assert!(app.error.is_none());
+        assert!(matches!(app.mode, AppMode::Conflict { .. }));
+        run(&mut app, key(KeyCode::Char('r')));
+        assert_eq!(app.entries.iter().map(|entry| entry.host.as_str()).collect::<Vec<_>>(), ["web"]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn known_hosts_lines_are_only_deleted_once_confirmed() {
+        let dir = std::env::temp_dir().join(format!("ssh-config-known-hosts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("known_hosts");
+        std::fs::write(&path, "web ssh-ed25519 AAAA1\ngithub.com ssh-ed25519 AAAA2\n").unwrap();
+        let mut entries = vec![SshConfigEntry::new(EntryKind::Host, "web", None, "config".into())];
+        entries[0].add_option("HostName".to_string(), "10.0.0.9".to_string());
+        let mut app = App::new(entries, dir, "ssh", Settings::default());
+
+        type_keys(&mut app, "KD");
+        assert!(matches!(&app.mode, AppMode::KnownHosts { deleting: Some(lines), .. } if *lines == [0]));
+        run(&mut app, key(KeyCode::Esc));
+        run(&mut app, key(KeyCode::Enter));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        type_keys(&mut app, "D");
+        run(&mut app, key(KeyCode::Enter));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "github.com ssh-ed25519 AAAA2\n");
+    }
+
+    #[test]
+    fn edited_options_are_kept_as_typed() {
+        let mut entry = SshConfigEntry::new(EntryKind::Host, "web", None, "config".into());
+        entry.add_option("ControlPersist".to_string(), "10m".to_string());
+
+        let next = apply_edit(&mut entry, &EditField::NewKey, "stricthostkeychecking").unwrap();
+        assert_eq!(next, Some(EditField::NewValue("stricthostkeychecking".to_string())));
+        apply_edit(&mut entry, &next.unwrap(), "off").unwrap();
+        apply_edit(&mut entry, &EditField::Value(0), "90m").unwrap();
+        assert!(apply_edit(&mut entry, &EditField::Value(0), "soon").is_err());
+
+        assert_eq!(entry.options, [
+            ("ControlPersist".to_string(), "90m".to_string()),
+            ("stricthostkeychecking".to_string(), "off".to_string()),
+        ]);
+    }
+}
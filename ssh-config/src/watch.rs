@@ -0,0 +1,156 @@
+//! This module watches the config files for changes made outside of the TUI, e.g. in an editor
+//!
+//! The directories of the files are watched rather than the files themselves, since editors often
+//! save by writing a new file and renaming it over the old one, which a watch on the file misses.
+//! Saving a file makes a burst of events, the change is reported once the files are quiet again.
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::entry::SshConfigEntry;
+
+/// How long the files have to be quiet before the change is reported
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// The config files that changed on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct Changed(pub Vec<PathBuf>);
+
+/// The files to watch: the config file and every file its entries come from, through `Include`.
+pub fn config_files(config_path: &Path, entries: &[SshConfigEntry]) -> Vec<PathBuf> {
+    let mut files = vec![config_path.to_path_buf()];
+    for entry in entries {
+        if !files.contains(&entry.source) {
+            files.push(entry.source.clone());
+        }
+    }
+    files
+}
+
+/// Watches the config files and reports their changes from a thread of its own
+pub struct ConfigWatcher {
+    watcher: RecommendedWatcher,
+    files: Arc<Mutex<Vec<PathBuf>>>,  // Files whose events are reported, shared with the thread
+    dirs: Vec<PathBuf>,               // Directories being watched
+}
+
+impl ConfigWatcher {
+    /// Starts watching `files`, their changes are sent to `changes` as `Changed`.
+    pub fn spawn<T>(files: Vec<PathBuf>, changes: mpsc::Sender<T>) -> notify::Result<Self>
+    where
+        T: From<Changed> + Send + 'static,
+    {
+        let (event_tx, event_rx) = mpsc::channel::<notify::Result<notify::Event>>();
+        let watcher = notify::recommended_watcher(event_tx)?;
+        let watched = Arc::new(Mutex::new(Vec::new()));
+
+        let thread_files = Arc::clone(&watched);
+        thread::spawn(move || {
+            let mut changed: Vec<PathBuf> = Vec::new();
+            loop {
+                // Nothing is waited for while no file changed
+                let event = if changed.is_empty() {
+                    event_rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected)
+                } else {
+                    event_rx.recv_timeout(SETTLE)
+                };
+
+                match event {
+                    // Reading a file is not a change
+                    Ok(Ok(event)) if matches!(event.kind, EventKind::Access(_)) => {}
+                    Ok(Ok(event)) => {
+                        let files = thread_files.lock().unwrap_or_else(|e| e.into_inner());
+                        for path in event.paths {
+                            if files.contains(&path) && !changed.contains(&path) {
+                                changed.push(path);
+                            }
+                        }
+                    }
+                    Ok(Err(e)) => log::error!("Failed to watch the config files: {}", e),
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        log::info!("Changed on disk: {:?}", changed);
+                        if changes.send(T::from(Changed(std::mem::take(&mut changed)))).is_err() {
+                            break;
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        let mut config_watcher = Self { watcher, files: watched, dirs: Vec::new() };
+        config_watcher.watch(files);
+        Ok(config_watcher)
+    }
+
+    /// Watches `files` in place of the files watched so far, after `Include` lines changed.
+    pub fn watch(&mut self, files: Vec<PathBuf>) {
+        // A symlinked config changes where the link points to
+        let mut paths = Vec::new();
+        for file in files {
+            if let Ok(target) = fs::canonicalize(&file) {
+                if target != file {
+                    paths.push(target);
+                }
+            }
+            paths.push(file);
+        }
+
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in paths.iter().filter_map(|path| path.parent()) {
+            if !dirs.iter().any(|other| other == dir) {
+                dirs.push(dir.to_path_buf());
+            }
+        }
+
+        for dir in &self.dirs {
+            if dirs.contains(dir) {
+                continue;
+            }
+            if let Err(e) = self.watcher.unwatch(dir) {
+                log::warn!("Failed to stop watching {}: {}", dir.display(), e);
+            }
+        }
+        for dir in &dirs {
+            if self.dirs.contains(dir) {
+                continue;
+            }
+            log::debug!("Watching {}", dir.display());
+            if let Err(e) = self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                log::error!("Failed to watch {}: {}", dir.display(), e);
+            }
+        }
+
+        *self.files.lock().unwrap_or_else(|e| e.into_inner()) = paths;
+        self.dirs = dirs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_saved_file_is_reported_once() {
+        let dir = std::env::temp_dir().join(format!("ssh-config-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = fs::canonicalize(&dir).unwrap().join("config");
+        fs::write(&config, "Host a\n").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _watcher = ConfigWatcher::spawn(vec![config.clone()], tx).unwrap();
+
+        // Another file of the directory is not a change of the config
+        fs::write(dir.join("known_hosts"), "a ssh-ed25519 AAAA\n").unwrap();
+        fs::write(&config, "Host a\n").unwrap();
+        fs::write(&config, "Host a b\n").unwrap();
+
+        let Changed(paths) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(paths, [config]);
+        assert!(rx.recv_timeout(SETTLE * 2).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}